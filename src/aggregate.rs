@@ -0,0 +1,538 @@
+use std::{
+    fmt,
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use crate::{
+    battery_info::{
+        battery_level::BatteryLevel,
+        device_state::DeviceState,
+        device_type::DeviceType,
+        warning_level::WarningLevel,
+        BatteryInfo,
+        BatteryInfoProperties,
+    },
+    battery_interface::{
+        sysfs,
+        Device,
+    },
+};
+
+/// How the percentage of several batteries is combined into one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum AggregationPolicy {
+    /// The energy left in every pack over their combined capacity, like UPower does it
+    #[default]
+    EnergyWeighted,
+    /// The emptiest pack, so one nearly empty pack isn't hidden by a full one
+    Min,
+    /// The fullest pack
+    Max,
+    /// Only the primary pack, see [`Aggregator::with_primary`]
+    PrimaryOnly,
+}
+
+impl AggregationPolicy {
+    pub const ALL: [AggregationPolicy; 4] = [
+        AggregationPolicy::EnergyWeighted,
+        AggregationPolicy::Min,
+        AggregationPolicy::Max,
+        AggregationPolicy::PrimaryOnly,
+    ];
+}
+
+impl fmt::Display for AggregationPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AggregationPolicy::EnergyWeighted => "energy-weighted",
+            AggregationPolicy::Min => "min",
+            AggregationPolicy::Max => "max",
+            AggregationPolicy::PrimaryOnly => "primary-only",
+        })
+    }
+}
+
+impl FromStr for AggregationPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AggregationPolicy::ALL
+            .into_iter()
+            .find(|policy| policy.to_string() == s)
+            .ok_or_else(|| anyhow!("unknown aggregation policy: {:?}", s))
+    }
+}
+
+impl TryFrom<String> for AggregationPolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// One battery's share of an [`Aggregate`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pack {
+    pub id: String,
+    pub percentage: Option<f64>,
+    pub state: Option<DeviceState>,
+    /// Wh
+    pub energy: Option<f64>,
+    /// Wh
+    pub energy_full: Option<f64>,
+    /// W
+    pub energy_rate: Option<f64>,
+}
+
+impl fmt::Display for Pack {
+    /// One line summary, e.g. `BAT0: 3% discharging`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.id.rsplit('/').next().unwrap_or(&self.id))?;
+
+        if let Some(percentage) = self.percentage {
+            write!(f, " {:.0}%", percentage)?;
+        }
+
+        if let Some(state) = self.state {
+            write!(f, " {}", state)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Several batteries combined into one reading, and what each of them contributed.
+#[derive(Debug)]
+pub struct Aggregate {
+    pub info: BatteryInfo,
+    /// Ordered as the devices were given
+    pub packs: Vec<Pack>,
+}
+
+impl Aggregate {
+    /// The emptiest pack, e.g. to warn about it even when the aggregate looks fine. Packs
+    /// without a percentage, or with a bogus NaN one, are left out.
+    pub fn lowest(&self) -> Option<&Pack> {
+        self.packs
+            .iter()
+            .filter_map(|pack| Some((pack, pack.percentage.filter(|p| !p.is_nan())?)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(pack, _)| pack)
+    }
+}
+
+/// Combines the batteries powering the system into one [`BatteryInfo`], the same way for every
+/// backend.
+///
+/// Batteries are devices of type [`DeviceType::Battery`] that power the system, a mouse or a
+/// UPS is left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Aggregator {
+    policy: AggregationPolicy,
+    primary: Option<String>,
+}
+
+impl Aggregator {
+    pub fn new(policy: AggregationPolicy) -> Self {
+        Self {
+            policy,
+            primary: None,
+        }
+    }
+
+    /// The pack [`AggregationPolicy::PrimaryOnly`] reports, by id or its last path segment.
+    /// The first battery if unset or missing.
+    pub fn with_primary(mut self, id: impl Into<String>) -> Self {
+        self.primary = Some(id.into());
+        self
+    }
+
+    pub fn policy(&self) -> AggregationPolicy {
+        self.policy
+    }
+
+    /// `None` when there are no batteries among `devices`.
+    pub fn aggregate(&self, devices: Vec<Device>) -> Option<Aggregate> {
+        let batteries = devices
+            .into_iter()
+            .filter(|device| {
+                device.info.device_type == Some(DeviceType::Battery)
+                    && device.info.power_supply.is_none_or(|p| *p)
+            })
+            .collect::<Vec<_>>();
+
+        if batteries.is_empty() {
+            return None;
+        }
+
+        let packs = batteries.iter().map(pack).collect::<Vec<_>>();
+
+        let info = match self.policy {
+            AggregationPolicy::PrimaryOnly => {
+                let index = self
+                    .primary
+                    .as_deref()
+                    .and_then(|primary| {
+                        batteries.iter().position(|device| {
+                            device.id == primary || device.id.rsplit('/').next() == Some(primary)
+                        })
+                    })
+                    .unwrap_or_default();
+
+                batteries.into_iter().nth(index)?.info
+            }
+            AggregationPolicy::EnergyWeighted => combine(&batteries, &packs, weighted(&packs)),
+            AggregationPolicy::Min => combine(&batteries, &packs, extreme(&packs, f64::min)),
+            AggregationPolicy::Max => combine(&batteries, &packs, extreme(&packs, f64::max)),
+        };
+
+        Some(Aggregate { info, packs })
+    }
+}
+
+fn pack(device: &Device) -> Pack {
+    let info = &device.info;
+
+    Pack {
+        id: device.id.clone(),
+        percentage: info.percentage.map(|p| *p),
+        state: info.device_state,
+        energy: info.energy.map(|e| *e),
+        energy_full: info.energy_full.map(|e| *e),
+        energy_rate: info.energy_rate.map(|e| *e),
+    }
+}
+
+/// Summed energy over summed capacity, the plain average when a pack doesn't report energy.
+fn weighted(packs: &[Pack]) -> Option<f64> {
+    let energy = sum(packs.iter().map(|pack| pack.energy));
+    let energy_full = sum(packs.iter().map(|pack| pack.energy_full));
+
+    match (energy, energy_full) {
+        (Some(energy), Some(full)) if full > 0.0 => Some((energy / full * 100.0).min(100.0)),
+        _ => {
+            let percentages = packs
+                .iter()
+                .map(|pack| pack.percentage)
+                .collect::<Option<Vec<_>>>()?;
+
+            Some(percentages.iter().sum::<f64>() / percentages.len() as f64)
+        }
+    }
+}
+
+fn extreme(packs: &[Pack], pick: fn(f64, f64) -> f64) -> Option<f64> {
+    packs.iter().filter_map(|pack| pack.percentage).reduce(pick)
+}
+
+/// `None` unless every pack has a value.
+fn sum(values: impl Iterator<Item = Option<f64>>) -> Option<f64> {
+    values.sum()
+}
+
+/// The combined reading of `batteries` with the given `percentage`.
+fn combine(batteries: &[Device], packs: &[Pack], percentage: Option<f64>) -> BatteryInfo {
+    let mut batt_info = BatteryInfo::new();
+
+    batt_info.set_propertry(BatteryInfoProperties::DeviceType(DeviceType::Battery));
+    batt_info.set_propertry(BatteryInfoProperties::PowerSupply(true.into()));
+
+    if let Some(percentage) = percentage {
+        batt_info.set_propertry(BatteryInfoProperties::Percentage(percentage.into()));
+    }
+
+    let state = state(packs.iter().filter_map(|pack| pack.state).collect());
+    if let Some(state) = state {
+        batt_info.set_propertry(BatteryInfoProperties::DeviceState(state));
+    }
+
+    let energy = sum(packs.iter().map(|pack| pack.energy));
+    let energy_full = sum(packs.iter().map(|pack| pack.energy_full));
+    // packs that aren't in use report no rate
+    let energy_rate = packs
+        .iter()
+        .filter_map(|pack| pack.energy_rate)
+        .reduce(|a, b| a + b);
+
+    if let Some(energy) = energy {
+        batt_info.set_propertry(BatteryInfoProperties::Energy(energy.into()));
+    }
+    if let Some(energy_full) = energy_full {
+        batt_info.set_propertry(BatteryInfoProperties::EnergyFull(energy_full.into()));
+    }
+    if let Some(energy_rate) = energy_rate {
+        batt_info.set_propertry(BatteryInfoProperties::EnergyRate(energy_rate.into()));
+    }
+
+    if let Some(time_until) = sysfs::time_until(state, energy, energy_full, energy_rate) {
+        batt_info.set_propertry(BatteryInfoProperties::TimeUntil(time_until));
+    }
+
+    let warning_level = batteries
+        .iter()
+        .filter_map(|device| device.info.warning_level)
+        .max_by_key(|level| warning_severity(*level));
+    if let Some(warning_level) = warning_level {
+        batt_info.set_propertry(BatteryInfoProperties::WarningLevel(warning_level));
+    }
+
+    let battery_level = batteries
+        .iter()
+        .filter_map(|device| device.info.battery_level)
+        .max_by_key(|level| level_severity(*level));
+    if let Some(battery_level) = battery_level {
+        batt_info.set_propertry(BatteryInfoProperties::BatteryLevel(battery_level));
+    }
+
+    batt_info
+}
+
+/// Discharging wins, as one pack draining means the system is on battery.
+fn state(states: Vec<DeviceState>) -> Option<DeviceState> {
+    let any = |state| states.contains(&state);
+    let all = |state| states.iter().all(|s| *s == state);
+
+    if states.is_empty() {
+        return None;
+    }
+
+    Some(if any(DeviceState::Discharging) {
+        DeviceState::Discharging
+    } else if any(DeviceState::Charging) {
+        DeviceState::Charging
+    } else if all(DeviceState::FullyCharged) {
+        DeviceState::FullyCharged
+    } else if all(DeviceState::Empty) {
+        DeviceState::Empty
+    } else if any(DeviceState::PendingCharge) {
+        DeviceState::PendingCharge
+    } else if any(DeviceState::PendingDischarge) {
+        DeviceState::PendingDischarge
+    } else if any(DeviceState::FullyCharged) {
+        DeviceState::FullyCharged
+    } else {
+        DeviceState::Unknown
+    })
+}
+
+fn warning_severity(level: WarningLevel) -> u8 {
+    match level {
+        WarningLevel::Action => 4,
+        WarningLevel::Critical => 3,
+        WarningLevel::Low => 2,
+        WarningLevel::Discharging => 1,
+        _ => 0,
+    }
+}
+
+fn level_severity(level: BatteryLevel) -> u8 {
+    match level {
+        BatteryLevel::Critical => 5,
+        BatteryLevel::Low => 4,
+        BatteryLevel::Normal => 3,
+        BatteryLevel::High => 2,
+        BatteryLevel::Full => 1,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::batt_info;
+
+    fn battery(id: &str, energy: f64, energy_full: f64, state: DeviceState, rate: f64) -> Device {
+        let info = batt_info([
+            BatteryInfoProperties::DeviceType(DeviceType::Battery),
+            BatteryInfoProperties::PowerSupply(true.into()),
+            BatteryInfoProperties::Percentage((energy / energy_full * 100.0).into()),
+            BatteryInfoProperties::DeviceState(state),
+            BatteryInfoProperties::Energy(energy.into()),
+            BatteryInfoProperties::EnergyFull(energy_full.into()),
+            BatteryInfoProperties::EnergyRate(rate.into()),
+        ]);
+
+        Device {
+            id: id.to_owned(),
+            info,
+        }
+    }
+
+    /// A ThinkPad with a nearly empty internal pack and a large external one
+    fn thinkpad() -> Vec<Device> {
+        let mut mouse = battery("mouse", 1.0, 2.0, DeviceState::Discharging, 0.0);
+        mouse
+            .info
+            .set_propertry(BatteryInfoProperties::PowerSupply(false.into()));
+
+        vec![
+            battery("BAT0", 0.6, 20.0, DeviceState::Discharging, 0.0),
+            battery("BAT1", 50.0, 70.0, DeviceState::Discharging, 10.0),
+            mouse,
+        ]
+    }
+
+    #[test]
+    fn policies() {
+        let percentages = AggregationPolicy::ALL
+            .into_iter()
+            .map(|policy| {
+                let aggregate = Aggregator::new(policy).aggregate(thinkpad()).unwrap();
+
+                (policy.to_string(), aggregate.info.percentage.map(|p| *p))
+            })
+            .collect::<Vec<_>>();
+
+        insta::assert_debug_snapshot!(percentages, @r###"
+        [
+            (
+                "energy-weighted",
+                Some(
+                    56.22222222222223,
+                ),
+            ),
+            (
+                "min",
+                Some(
+                    3.0,
+                ),
+            ),
+            (
+                "max",
+                Some(
+                    71.42857142857143,
+                ),
+            ),
+            (
+                "primary-only",
+                Some(
+                    3.0,
+                ),
+            ),
+        ]
+        "###);
+
+        let primary = Aggregator::new(AggregationPolicy::PrimaryOnly)
+            .with_primary("BAT1")
+            .aggregate(thinkpad())
+            .unwrap();
+        insta::assert_debug_snapshot!(primary.info.percentage, @r###"
+        Some(
+            Percentage(
+                71.42857142857143,
+            ),
+        )
+        "###);
+    }
+
+    #[test]
+    fn breakdown() {
+        let aggregate = Aggregator::default().aggregate(thinkpad()).unwrap();
+
+        insta::assert_debug_snapshot!(
+            (
+                aggregate.info.device_state,
+                aggregate.info.energy_rate,
+                aggregate.info.time_until,
+                aggregate.lowest().map(|pack| &pack.id),
+            ),
+            @r###"
+        (
+            Some(
+                Discharging,
+            ),
+            Some(
+                EnergyRate(
+                    10.0,
+                ),
+            ),
+            Some(
+                Empty(
+                    18216s,
+                ),
+            ),
+            Some(
+                "BAT0",
+            ),
+        )
+        "###
+        );
+        insta::assert_debug_snapshot!(aggregate.packs, @r###"
+        [
+            Pack {
+                id: "BAT0",
+                percentage: Some(
+                    3.0,
+                ),
+                state: Some(
+                    Discharging,
+                ),
+                energy: Some(
+                    0.6,
+                ),
+                energy_full: Some(
+                    20.0,
+                ),
+                energy_rate: Some(
+                    0.0,
+                ),
+            },
+            Pack {
+                id: "BAT1",
+                percentage: Some(
+                    71.42857142857143,
+                ),
+                state: Some(
+                    Discharging,
+                ),
+                energy: Some(
+                    50.0,
+                ),
+                energy_full: Some(
+                    70.0,
+                ),
+                energy_rate: Some(
+                    10.0,
+                ),
+            },
+        ]
+        "###);
+    }
+
+    #[test]
+    fn bogus_readings() {
+        let devices = vec![
+            // no capacity, 0 / 0 percent
+            battery("BAT0", 0.0, 0.0, DeviceState::Discharging, 1e-300),
+            battery("BAT1", 50.0, 70.0, DeviceState::Discharging, 10.0),
+        ];
+
+        let aggregate = Aggregator::default().aggregate(devices).unwrap();
+
+        insta::assert_debug_snapshot!(aggregate.lowest().map(|pack| &pack.id), @r###"
+        Some(
+            "BAT1",
+        )
+        "###);
+    }
+
+    #[test]
+    fn no_batteries() {
+        assert!(Aggregator::default().aggregate(vec![]).is_none());
+    }
+
+    #[test]
+    fn policy_names_round_trip() {
+        for policy in AggregationPolicy::ALL {
+            assert_eq!(
+                policy.to_string().parse::<AggregationPolicy>().unwrap(),
+                policy
+            );
+        }
+    }
+}
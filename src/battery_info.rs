@@ -13,6 +13,14 @@ use warning_level::*;
 pub mod time_until;
 use time_until::*;
 
+pub mod label;
+
+pub mod event;
+
+pub mod diff;
+
+pub mod tracked;
+
 use crate::battery_interface::BatteryInterface;
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
@@ -66,7 +74,161 @@ impl From<String> for IconName {
     }
 }
 
-#[derive(Debug)]
+/// Energy in Wh
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Energy(f64);
+
+impl std::ops::Deref for Energy {
+    type Target = f64;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<f64> for Energy {
+    fn from(value: f64) -> Self {
+        Self(value)
+    }
+}
+
+/// Power draw in W, positive both when charging and discharging
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct EnergyRate(f64);
+
+impl std::ops::Deref for EnergyRate {
+    type Target = f64;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<f64> for EnergyRate {
+    fn from(value: f64) -> Self {
+        Self(value)
+    }
+}
+
+/// Voltage in V
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Voltage(f64);
+
+impl std::ops::Deref for Voltage {
+    type Target = f64;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<f64> for Voltage {
+    fn from(value: f64) -> Self {
+        Self(value)
+    }
+}
+
+/// Temperature in degrees Celsius
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Temperature(f64);
+
+impl std::ops::Deref for Temperature {
+    type Target = f64;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<f64> for Temperature {
+    fn from(value: f64) -> Self {
+        Self(value)
+    }
+}
+
+/// Number of charge cycles, negative when the backend doesn't know
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChargeCycles(i32);
+
+impl std::ops::Deref for ChargeCycles {
+    type Target = i32;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<i32> for ChargeCycles {
+    fn from(value: i32) -> Self {
+        Self(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Model(String);
+
+impl std::ops::Deref for Model {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<String> for Model {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+/// The backend specific name of the device, e.g. `BAT0`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativePath(String);
+
+impl std::ops::Deref for NativePath {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<String> for NativePath {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+/// A property that was skipped because its value couldn't be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyDiagnostic {
+    key: String,
+    message: String,
+}
+
+impl PropertyDiagnostic {
+    pub(crate) fn new(key: &str, error: &anyhow::Error) -> Self {
+        Self {
+            key: key.to_owned(),
+            message: error.to_string(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// One reading of a device, with the properties the backend knew.
+///
+/// Readings are plain values: clone one to keep it, compare two with `==`, or with
+/// [`BatteryInfo::diff`] to see what changed. Two readings are equal exactly when their diff
+/// is empty, see [`BatteryInfoProperties::same_reading`].
+#[derive(Debug, Default, Clone)]
 pub struct BatteryInfo {
     pub(crate) device_type: Option<DeviceType>,
     pub(crate) device_state: Option<DeviceState>,
@@ -76,8 +238,18 @@ pub struct BatteryInfo {
     pub(crate) icon_name: Option<IconName>,
     pub(crate) time_until: Option<TimeUntil>,
     pub(crate) warning_level: Option<WarningLevel>,
+    pub(crate) energy: Option<Energy>,
+    pub(crate) energy_full: Option<Energy>,
+    pub(crate) energy_rate: Option<EnergyRate>,
+    pub(crate) voltage: Option<Voltage>,
+    pub(crate) temperature: Option<Temperature>,
+    pub(crate) charge_cycles: Option<ChargeCycles>,
+    pub(crate) model: Option<Model>,
+    pub(crate) native_path: Option<NativePath>,
+    pub(crate) diagnostics: Vec<PropertyDiagnostic>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum BatteryInfoProperties {
     DeviceType(DeviceType),
     DeviceState(DeviceState),
@@ -87,24 +259,64 @@ pub enum BatteryInfoProperties {
     IconName(IconName),
     TimeUntil(TimeUntil),
     WarningLevel(WarningLevel),
+    Energy(Energy),
+    EnergyFull(Energy),
+    EnergyRate(EnergyRate),
+    Voltage(Voltage),
+    Temperature(Temperature),
+    ChargeCycles(ChargeCycles),
+    Model(Model),
+    NativePath(NativePath),
 }
 
 impl BatteryInfoProperties {
-    pub fn insert_property_by_mut_ref(self, batt_info: &mut BatteryInfo) {
+    /// The name UPower gives the property, e.g. `Percentage`.
+    pub fn name(&self) -> &'static str {
         match self {
-            Self::DeviceType(device_type) => {
-                batt_info.device_type = Some(device_type);
-            }
-            Self::DeviceState(device_state) => batt_info.device_state = Some(device_state),
-            Self::Percentage(percentage) => batt_info.percentage = Some(percentage),
-            Self::PowerSupply(power_supply) => batt_info.power_supply = Some(power_supply),
-            Self::BatteryLevel(battery_level) => batt_info.battery_level = Some(battery_level),
-            Self::IconName(icon_name) => batt_info.icon_name = Some(icon_name),
-            Self::TimeUntil(time_until) => batt_info.time_until = Some(time_until),
-            Self::WarningLevel(warning_level) => batt_info.warning_level = Some(warning_level),
-        };
+            Self::DeviceType(_) => "Type",
+            Self::DeviceState(_) => "State",
+            Self::Percentage(_) => "Percentage",
+            Self::PowerSupply(_) => "PowerSupply",
+            Self::BatteryLevel(_) => "BatteryLevel",
+            Self::IconName(_) => "IconName",
+            Self::TimeUntil(_) => "TimeUntil",
+            Self::WarningLevel(_) => "WarningLevel",
+            Self::Energy(_) => "Energy",
+            Self::EnergyFull(_) => "EnergyFull",
+            Self::EnergyRate(_) => "EnergyRate",
+            Self::Voltage(_) => "Voltage",
+            Self::Temperature(_) => "Temperature",
+            Self::ChargeCycles(_) => "ChargeCycles",
+            Self::Model(_) => "Model",
+            Self::NativePath(_) => "NativePath",
+        }
+    }
+
+    /// Whether `other` reads the same as this property.
+    ///
+    /// Floats compare as numbers, except that a NaN reads the same as another NaN of the same
+    /// property, so a reading with a bogus value still equals itself.
+    pub fn same_reading(&self, other: &Self) -> bool {
+        match (self.float(), other.float()) {
+            (Some(a), Some(b)) if a.is_nan() && b.is_nan() => self.name() == other.name(),
+            _ => self == other,
+        }
+    }
+
+    fn float(&self) -> Option<f64> {
+        match self {
+            Self::Percentage(value) => Some(**value),
+            Self::Energy(value) | Self::EnergyFull(value) => Some(**value),
+            Self::EnergyRate(value) => Some(**value),
+            Self::Voltage(value) => Some(**value),
+            Self::Temperature(value) => Some(**value),
+            _ => None,
+        }
     }
-    pub fn insert_property(self, mut batt_info: BatteryInfo) {
+
+    /// Set the property on `batt_info`, replacing what's there no matter how recent it is, see
+    /// [`TrackedBatteryInfo`](tracked::TrackedBatteryInfo) to merge updates by their time.
+    pub fn insert_property_by_mut_ref(self, batt_info: &mut BatteryInfo) {
         match self {
             Self::DeviceType(device_type) => {
                 batt_info.device_type = Some(device_type);
@@ -116,22 +328,20 @@ impl BatteryInfoProperties {
             Self::IconName(icon_name) => batt_info.icon_name = Some(icon_name),
             Self::TimeUntil(time_until) => batt_info.time_until = Some(time_until),
             Self::WarningLevel(warning_level) => batt_info.warning_level = Some(warning_level),
-        }
+            Self::Energy(energy) => batt_info.energy = Some(energy),
+            Self::EnergyFull(energy_full) => batt_info.energy_full = Some(energy_full),
+            Self::EnergyRate(energy_rate) => batt_info.energy_rate = Some(energy_rate),
+            Self::Voltage(voltage) => batt_info.voltage = Some(voltage),
+            Self::Temperature(temperature) => batt_info.temperature = Some(temperature),
+            Self::ChargeCycles(charge_cycles) => batt_info.charge_cycles = Some(charge_cycles),
+            Self::Model(model) => batt_info.model = Some(model),
+            Self::NativePath(native_path) => batt_info.native_path = Some(native_path),
+        };
     }
-}
-
-impl Default for BatteryInfo {
-    fn default() -> Self {
-        Self {
-            device_type: None,
-            device_state: None,
-            percentage: None,
-            power_supply: None,
-            battery_level: None,
-            icon_name: None,
-            time_until: None,
-            warning_level: None,
-        }
+    /// Set the property on `batt_info` and hand it back.
+    pub fn insert_property(self, mut batt_info: BatteryInfo) -> BatteryInfo {
+        self.insert_property_by_mut_ref(&mut batt_info);
+        batt_info
     }
 }
 
@@ -147,6 +357,125 @@ impl BatteryInfo {
     pub fn get<T: BatteryInterface>() -> Option<Self> {
         <T as BatteryInterface>::battery_info().ok()
     }
+
+    /// Properties that were skipped while decoding, only ever non-empty in lenient parsing mode.
+    pub fn diagnostics(&self) -> &[PropertyDiagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn device_type(&self) -> Option<DeviceType> {
+        self.device_type
+    }
+
+    pub fn device_state(&self) -> Option<DeviceState> {
+        self.device_state
+    }
+
+    pub fn percentage(&self) -> Option<Percentage> {
+        self.percentage
+    }
+
+    pub fn power_supply(&self) -> Option<PowerSupply> {
+        self.power_supply
+    }
+
+    pub fn battery_level(&self) -> Option<BatteryLevel> {
+        self.battery_level
+    }
+
+    pub fn icon_name(&self) -> Option<&IconName> {
+        self.icon_name.as_ref()
+    }
+
+    pub fn time_until(&self) -> Option<TimeUntil> {
+        self.time_until
+    }
+
+    pub fn warning_level(&self) -> Option<WarningLevel> {
+        self.warning_level
+    }
+
+    pub fn energy(&self) -> Option<Energy> {
+        self.energy
+    }
+
+    pub fn energy_full(&self) -> Option<Energy> {
+        self.energy_full
+    }
+
+    pub fn energy_rate(&self) -> Option<EnergyRate> {
+        self.energy_rate
+    }
+
+    pub fn voltage(&self) -> Option<Voltage> {
+        self.voltage
+    }
+
+    pub fn temperature(&self) -> Option<Temperature> {
+        self.temperature
+    }
+
+    pub fn charge_cycles(&self) -> Option<ChargeCycles> {
+        self.charge_cycles
+    }
+
+    pub fn model(&self) -> Option<&Model> {
+        self.model.as_ref()
+    }
+
+    pub fn native_path(&self) -> Option<&NativePath> {
+        self.native_path.as_ref()
+    }
+
+    /// Every property that is set.
+    pub fn properties(&self) -> Vec<BatteryInfoProperties> {
+        self.slots().into_iter().flatten().collect()
+    }
+
+    /// Every property, in the order of [`BatteryInfoProperties`], `None` where it isn't set.
+    fn slots(&self) -> [Option<BatteryInfoProperties>; 16] {
+        use BatteryInfoProperties as P;
+
+        [
+            self.device_type.map(P::DeviceType),
+            self.device_state.map(P::DeviceState),
+            self.percentage.map(P::Percentage),
+            self.power_supply.map(P::PowerSupply),
+            self.battery_level.map(P::BatteryLevel),
+            self.icon_name.clone().map(P::IconName),
+            self.time_until.map(P::TimeUntil),
+            self.warning_level.map(P::WarningLevel),
+            self.energy.map(P::Energy),
+            self.energy_full.map(P::EnergyFull),
+            self.energy_rate.map(P::EnergyRate),
+            self.voltage.map(P::Voltage),
+            self.temperature.map(P::Temperature),
+            self.charge_cycles.map(P::ChargeCycles),
+            self.model.clone().map(P::Model),
+            self.native_path.clone().map(P::NativePath),
+        ]
+    }
+}
+
+/// Compares the properties only, not the [`BatteryInfo::diagnostics`] of how they were decoded.
+impl PartialEq for BatteryInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.slots()
+            .iter()
+            .zip(other.slots().iter())
+            .all(|(a, b)| same_slot(a, b))
+    }
+}
+
+/// Both unset, or both set and [`BatteryInfoProperties::same_reading`].
+pub(crate) fn same_slot(
+    a: &Option<BatteryInfoProperties>,
+    b: &Option<BatteryInfoProperties>,
+) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.same_reading(b),
+        (a, b) => a.is_none() && b.is_none(),
+    }
 }
 
 #[cfg(test)]
@@ -183,4 +512,39 @@ mod tests {
         insta::assert_debug_snapshot!(time_until.is_some(), @"true");
         insta::assert_debug_snapshot!(warning_level.is_some(), @"true");
     }
+
+    #[test]
+    fn insert_property() {
+        let batt_info =
+            BatteryInfoProperties::Percentage(12.0.into()).insert_property(BatteryInfo::new());
+
+        insta::assert_debug_snapshot!(batt_info.percentage(), @r###"
+        Some(
+            Percentage(
+                12.0,
+            ),
+        )
+        "###);
+    }
+
+    #[test]
+    fn equality() {
+        let mut a = BatteryInfo::new();
+        a.set_propertry(BatteryInfoProperties::Percentage(f64::NAN.into()));
+        a.set_propertry(BatteryInfoProperties::Energy(12.5.into()));
+
+        let mut b = a.clone();
+        b.diagnostics.push(PropertyDiagnostic::new(
+            "Voltage",
+            &anyhow::anyhow!("not a number"),
+        ));
+
+        assert_eq!(a, a);
+        assert_eq!(a, b);
+        assert!(a.diff(&b).is_empty());
+
+        b.set_propertry(BatteryInfoProperties::Energy(12.0.into()));
+        assert_ne!(a, b);
+        assert!(!a.diff(&b).is_empty());
+    }
 }
@@ -1,3 +1,5 @@
+use super::label::Labeled;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BatteryLevel {
     Unknown,
@@ -7,18 +9,21 @@ pub enum BatteryLevel {
     Normal,
     High,
     Full,
+    /// A raw value this crate doesn't know about, e.g. one added in a newer UPower
+    Unrecognized(u32),
 }
 
 impl From<u32> for BatteryLevel {
     fn from(value: u32) -> Self {
         match value {
             1 => BatteryLevel::NotApplicable,
-            2 => BatteryLevel::Low,
-            3 => BatteryLevel::Critical,
-            4 => BatteryLevel::Normal,
-            5 => BatteryLevel::High,
-            6 => BatteryLevel::Full,
-            _ => BatteryLevel::Unknown,
+            3 => BatteryLevel::Low,
+            4 => BatteryLevel::Critical,
+            6 => BatteryLevel::Normal,
+            7 => BatteryLevel::High,
+            8 => BatteryLevel::Full,
+            0 => BatteryLevel::Unknown,
+            other => BatteryLevel::Unrecognized(other),
         }
     }
 }
@@ -27,12 +32,13 @@ impl From<BatteryLevel> for u32 {
     fn from(value: BatteryLevel) -> Self {
         match value {
             BatteryLevel::NotApplicable => 1,
-            BatteryLevel::Low => 2,
-            BatteryLevel::Critical => 3,
-            BatteryLevel::Normal => 4,
-            BatteryLevel::High => 5,
-            BatteryLevel::Full => 6,
+            BatteryLevel::Low => 3,
+            BatteryLevel::Critical => 4,
+            BatteryLevel::Normal => 6,
+            BatteryLevel::High => 7,
+            BatteryLevel::Full => 8,
             BatteryLevel::Unknown => 0,
+            BatteryLevel::Unrecognized(other) => other,
         }
     }
 }
@@ -45,35 +51,94 @@ impl From<BatteryLevel> for (u32, BatteryLevel) {
 }
 
 pub struct BatteryLevelIter {
-    index: u32,
+    index: usize,
 }
 
 impl Iterator for BatteryLevelIter {
     type Item = BatteryLevel;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index > 6 {
-            return None;
-        }
-        let item: BatteryLevel = self.index.into();
+        let item = *BatteryLevel::ALL.get(self.index)?;
         self.index += 1;
         Some(item)
     }
 }
 
 impl BatteryLevel {
+    /// Every BatteryLevel this crate recognizes, ordered by raw value
+    pub const ALL: [BatteryLevel; 7] = [
+        BatteryLevel::Unknown,
+        BatteryLevel::NotApplicable,
+        BatteryLevel::Low,
+        BatteryLevel::Critical,
+        BatteryLevel::Normal,
+        BatteryLevel::High,
+        BatteryLevel::Full,
+    ];
+
     pub fn iter_levels() -> BatteryLevelIter {
         BatteryLevelIter { index: 0 }
     }
 }
 
+impl std::fmt::Display for BatteryLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BatteryLevel::Unknown => "unknown",
+            BatteryLevel::NotApplicable => "none",
+            BatteryLevel::Low => "low",
+            BatteryLevel::Critical => "critical",
+            BatteryLevel::Normal => "normal",
+            BatteryLevel::High => "high",
+            BatteryLevel::Full => "full",
+            BatteryLevel::Unrecognized(other) => return write!(f, "unrecognized-{}", other),
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for BatteryLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(other) = s.strip_prefix("unrecognized-") {
+            return Ok(BatteryLevel::from(other.parse::<u32>()?));
+        }
+
+        BatteryLevel::ALL
+            .into_iter()
+            .find(|v| v.to_string() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown battery level: {:?}", s))
+    }
+}
+
+impl Labeled for BatteryLevel {
+    const KIND: &'static str = "battery-level";
+
+    fn default_label(&self) -> String {
+        let label = match self {
+            BatteryLevel::Unknown => "Unknown",
+            BatteryLevel::NotApplicable => "Not applicable",
+            BatteryLevel::Low => "Low",
+            BatteryLevel::Critical => "Critical",
+            BatteryLevel::Normal => "Normal",
+            BatteryLevel::High => "High",
+            BatteryLevel::Full => "Full",
+            BatteryLevel::Unrecognized(other) => return format!("Unrecognized ({})", other),
+        };
+
+        label.to_owned()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn verify_battery_level_enum() {
-        assert!(BatteryLevel::iter_levels().eq((0u32..=6u32).map(|i| i.into())));
+        assert!(BatteryLevel::iter_levels().eq([0u32, 1, 3, 4, 6, 7, 8].map(BatteryLevel::from)));
 
         insta::assert_debug_snapshot!(BatteryLevel::iter_levels()
             .map(|t| t.into())
@@ -88,26 +153,69 @@ mod tests {
                 NotApplicable,
             ),
             (
-                2,
+                3,
                 Low,
             ),
             (
-                3,
+                4,
                 Critical,
             ),
             (
-                4,
+                6,
                 Normal,
             ),
             (
-                5,
+                7,
                 High,
             ),
             (
-                6,
+                8,
                 Full,
             ),
         ]
         "###)
     }
+
+    #[test]
+    fn unrecognized_battery_level_is_preserved() {
+        let battery_level = BatteryLevel::from(42);
+
+        insta::assert_debug_snapshot!(battery_level, @r###"
+        Unrecognized(
+            42,
+        )
+        "###);
+
+        insta::assert_debug_snapshot!(u32::from(battery_level), @"42");
+    }
+
+    #[test]
+    fn battery_level_names_round_trip() -> anyhow::Result<()> {
+        for battery_level in BatteryLevel::ALL
+            .into_iter()
+            .chain([BatteryLevel::Unrecognized(99)])
+        {
+            assert_eq!(
+                battery_level.to_string().parse::<BatteryLevel>()?,
+                battery_level
+            );
+        }
+
+        insta::assert_debug_snapshot!(BatteryLevel::ALL.map(|v| v.to_string()), @r###"
+        [
+            "unknown",
+            "none",
+            "low",
+            "critical",
+            "normal",
+            "high",
+            "full",
+        ]
+        "###);
+
+        insta::assert_debug_snapshot!("not-a-name".parse::<BatteryLevel>().is_err(), @"true");
+        insta::assert_debug_snapshot!("unrecognized-0".parse::<BatteryLevel>()?, @"Unknown");
+
+        Ok(())
+    }
 }
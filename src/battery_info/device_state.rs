@@ -1,3 +1,5 @@
+use super::label::Labeled;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DeviceState {
     Unknown,
@@ -7,6 +9,8 @@ pub enum DeviceState {
     FullyCharged,
     PendingCharge,
     PendingDischarge,
+    /// A raw value this crate doesn't know about, e.g. one added in a newer UPower
+    Unrecognized(u32),
 }
 
 impl From<u32> for DeviceState {
@@ -18,7 +22,8 @@ impl From<u32> for DeviceState {
             4 => DeviceState::FullyCharged,
             5 => DeviceState::PendingCharge,
             6 => DeviceState::PendingDischarge,
-            _ => DeviceState::Unknown,
+            0 => DeviceState::Unknown,
+            other => DeviceState::Unrecognized(other),
         }
     }
 }
@@ -33,6 +38,7 @@ impl From<DeviceState> for u32 {
             DeviceState::PendingCharge => 5,
             DeviceState::PendingDischarge => 6,
             DeviceState::Unknown => 0,
+            DeviceState::Unrecognized(other) => other,
         }
     }
 }
@@ -45,28 +51,87 @@ impl From<DeviceState> for (u32, DeviceState) {
 }
 
 pub struct DeviceStateIter {
-    index: u32,
+    index: usize,
 }
 
 impl Iterator for DeviceStateIter {
     type Item = DeviceState;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index > 6 {
-            return None;
-        }
-        let item: DeviceState = self.index.into();
+        let item = *DeviceState::ALL.get(self.index)?;
         self.index += 1;
         Some(item)
     }
 }
 
 impl DeviceState {
+    /// Every DeviceState this crate recognizes, ordered by raw value
+    pub const ALL: [DeviceState; 7] = [
+        DeviceState::Unknown,
+        DeviceState::Charging,
+        DeviceState::Discharging,
+        DeviceState::Empty,
+        DeviceState::FullyCharged,
+        DeviceState::PendingCharge,
+        DeviceState::PendingDischarge,
+    ];
+
     pub fn iter_levels() -> DeviceStateIter {
         DeviceStateIter { index: 0 }
     }
 }
 
+impl std::fmt::Display for DeviceState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DeviceState::Unknown => "unknown",
+            DeviceState::Charging => "charging",
+            DeviceState::Discharging => "discharging",
+            DeviceState::Empty => "empty",
+            DeviceState::FullyCharged => "fully-charged",
+            DeviceState::PendingCharge => "pending-charge",
+            DeviceState::PendingDischarge => "pending-discharge",
+            DeviceState::Unrecognized(other) => return write!(f, "unrecognized-{}", other),
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for DeviceState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(other) = s.strip_prefix("unrecognized-") {
+            return Ok(DeviceState::from(other.parse::<u32>()?));
+        }
+
+        DeviceState::ALL
+            .into_iter()
+            .find(|v| v.to_string() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown device state: {:?}", s))
+    }
+}
+
+impl Labeled for DeviceState {
+    const KIND: &'static str = "device-state";
+
+    fn default_label(&self) -> String {
+        let label = match self {
+            DeviceState::Unknown => "Unknown",
+            DeviceState::Charging => "Charging",
+            DeviceState::Discharging => "Discharging",
+            DeviceState::Empty => "Empty",
+            DeviceState::FullyCharged => "Fully charged",
+            DeviceState::PendingCharge => "Waiting to charge",
+            DeviceState::PendingDischarge => "Waiting to discharge",
+            DeviceState::Unrecognized(other) => return format!("Unrecognized ({})", other),
+        };
+
+        label.to_owned()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,4 +175,47 @@ mod tests {
         ]
         "###)
     }
+
+    #[test]
+    fn unrecognized_device_state_is_preserved() {
+        let device_state = DeviceState::from(7);
+
+        insta::assert_debug_snapshot!(device_state, @r###"
+        Unrecognized(
+            7,
+        )
+        "###);
+
+        insta::assert_debug_snapshot!(u32::from(device_state), @"7");
+    }
+
+    #[test]
+    fn device_state_names_round_trip() -> anyhow::Result<()> {
+        for device_state in DeviceState::ALL
+            .into_iter()
+            .chain([DeviceState::Unrecognized(99)])
+        {
+            assert_eq!(
+                device_state.to_string().parse::<DeviceState>()?,
+                device_state
+            );
+        }
+
+        insta::assert_debug_snapshot!(DeviceState::ALL.map(|v| v.to_string()), @r###"
+        [
+            "unknown",
+            "charging",
+            "discharging",
+            "empty",
+            "fully-charged",
+            "pending-charge",
+            "pending-discharge",
+        ]
+        "###);
+
+        insta::assert_debug_snapshot!("not-a-name".parse::<DeviceState>().is_err(), @"true");
+        insta::assert_debug_snapshot!("unrecognized-0".parse::<DeviceState>()?, @"Unknown");
+
+        Ok(())
+    }
 }
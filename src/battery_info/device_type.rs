@@ -1,3 +1,5 @@
+use super::label::Labeled;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum DeviceType {
     Unknown,
@@ -29,6 +31,8 @@ pub enum DeviceType {
     Wearable,
     Toy,
     BluetoothGenreic,
+    /// A raw value this crate doesn't know about, e.g. one added in a newer UPower
+    Unrecognized(u32),
 }
 
 impl From<u32> for DeviceType {
@@ -62,7 +66,8 @@ impl From<u32> for DeviceType {
             26 => Self::Wearable,
             27 => Self::Toy,
             28 => Self::BluetoothGenreic,
-            _ => Self::Unknown,
+            0 => Self::Unknown,
+            other => Self::Unrecognized(other),
         }
     }
 }
@@ -99,6 +104,7 @@ impl From<DeviceType> for u32 {
             DeviceType::Toy => 27,
             DeviceType::BluetoothGenreic => 28,
             DeviceType::Unknown => 0,
+            DeviceType::Unrecognized(other) => other,
         }
     }
 }
@@ -111,35 +117,175 @@ impl From<DeviceType> for (u32, DeviceType) {
 }
 
 impl DeviceType {
+    /// Every DeviceType this crate recognizes, ordered by raw value
+    pub const ALL: [DeviceType; 29] = [
+        Self::Unknown,
+        Self::LinePower,
+        Self::Battery,
+        Self::Ups,
+        Self::Monitor,
+        Self::Mouse,
+        Self::Keyboard,
+        Self::Pda,
+        Self::Phone,
+        Self::MediaPlayer,
+        Self::Tablet,
+        Self::Computer,
+        Self::GamingInput,
+        Self::Pen,
+        Self::Touchpad,
+        Self::Modem,
+        Self::Network,
+        Self::Headset,
+        Self::Speakers,
+        Self::Headphones,
+        Self::Video,
+        Self::OtherAudio,
+        Self::RemoteControl,
+        Self::Printer,
+        Self::Scanner,
+        Self::Camera,
+        Self::Wearable,
+        Self::Toy,
+        Self::BluetoothGenreic,
+    ];
+
     pub fn iter_types() -> DeviceTypeIter {
         DeviceTypeIter { index: 0 }
     }
+
+    /// Devices with a battery of their own that don't power the system, e.g. a mouse or a
+    /// headset.
+    pub fn is_peripheral(&self) -> bool {
+        !matches!(
+            self,
+            Self::Unknown
+                | Self::LinePower
+                | Self::Battery
+                | Self::Ups
+                | Self::Monitor
+                | Self::Computer
+                | Self::Unrecognized(_)
+        )
+    }
 }
 
 pub struct DeviceTypeIter {
-    index: u32,
+    index: usize,
 }
 
 impl Iterator for DeviceTypeIter {
     type Item = DeviceType;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index > 28 {
-            return None;
-        }
-        let item: DeviceType = self.index.into();
+        let item = *DeviceType::ALL.get(self.index)?;
         self.index += 1;
         Some(item)
     }
 }
 
+impl std::fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DeviceType::Unknown => "unknown",
+            DeviceType::LinePower => "line-power",
+            DeviceType::Battery => "battery",
+            DeviceType::Ups => "ups",
+            DeviceType::Monitor => "monitor",
+            DeviceType::Mouse => "mouse",
+            DeviceType::Keyboard => "keyboard",
+            DeviceType::Pda => "pda",
+            DeviceType::Phone => "phone",
+            DeviceType::MediaPlayer => "media-player",
+            DeviceType::Tablet => "tablet",
+            DeviceType::Computer => "computer",
+            DeviceType::GamingInput => "gaming-input",
+            DeviceType::Pen => "pen",
+            DeviceType::Touchpad => "touchpad",
+            DeviceType::Modem => "modem",
+            DeviceType::Network => "network",
+            DeviceType::Headset => "headset",
+            DeviceType::Speakers => "speakers",
+            DeviceType::Headphones => "headphones",
+            DeviceType::Video => "video",
+            DeviceType::OtherAudio => "other-audio",
+            DeviceType::RemoteControl => "remote-control",
+            DeviceType::Printer => "printer",
+            DeviceType::Scanner => "scanner",
+            DeviceType::Camera => "camera",
+            DeviceType::Wearable => "wearable",
+            DeviceType::Toy => "toy",
+            DeviceType::BluetoothGenreic => "bluetooth-generic",
+            DeviceType::Unrecognized(other) => return write!(f, "unrecognized-{}", other),
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for DeviceType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(other) = s.strip_prefix("unrecognized-") {
+            return Ok(DeviceType::from(other.parse::<u32>()?));
+        }
+
+        DeviceType::ALL
+            .into_iter()
+            .find(|v| v.to_string() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown device type: {:?}", s))
+    }
+}
+
+impl Labeled for DeviceType {
+    const KIND: &'static str = "device-type";
+
+    fn default_label(&self) -> String {
+        let label = match self {
+            DeviceType::Unknown => "Unknown",
+            DeviceType::LinePower => "AC power",
+            DeviceType::Battery => "Battery",
+            DeviceType::Ups => "UPS",
+            DeviceType::Monitor => "Monitor",
+            DeviceType::Mouse => "Mouse",
+            DeviceType::Keyboard => "Keyboard",
+            DeviceType::Pda => "PDA",
+            DeviceType::Phone => "Phone",
+            DeviceType::MediaPlayer => "Media player",
+            DeviceType::Tablet => "Tablet",
+            DeviceType::Computer => "Computer",
+            DeviceType::GamingInput => "Gaming input",
+            DeviceType::Pen => "Pen",
+            DeviceType::Touchpad => "Touchpad",
+            DeviceType::Modem => "Modem",
+            DeviceType::Network => "Network device",
+            DeviceType::Headset => "Headset",
+            DeviceType::Speakers => "Speakers",
+            DeviceType::Headphones => "Headphones",
+            DeviceType::Video => "Video device",
+            DeviceType::OtherAudio => "Audio device",
+            DeviceType::RemoteControl => "Remote control",
+            DeviceType::Printer => "Printer",
+            DeviceType::Scanner => "Scanner",
+            DeviceType::Camera => "Camera",
+            DeviceType::Wearable => "Wearable",
+            DeviceType::Toy => "Toy",
+            DeviceType::BluetoothGenreic => "Bluetooth device",
+            DeviceType::Unrecognized(other) => return format!("Unrecognized ({})", other),
+        };
+
+        label.to_owned()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn verify_device_type_enum() {
-        assert!(DeviceType::iter_types().eq((0u32..=28u32).map(|i| DeviceType::from(i))));
+        assert!(DeviceType::iter_types().eq((0u32..=28u32).map(DeviceType::from)));
 
         insta::assert_debug_snapshot!(DeviceType::iter_types()
             .map(|t| t.into())
@@ -264,4 +410,66 @@ mod tests {
         ]
         "###)
     }
+
+    #[test]
+    fn unrecognized_device_type_is_preserved() {
+        let device_type = DeviceType::from(29);
+
+        insta::assert_debug_snapshot!(device_type, @r###"
+        Unrecognized(
+            29,
+        )
+        "###);
+
+        insta::assert_debug_snapshot!(u32::from(device_type), @"29");
+    }
+
+    #[test]
+    fn device_type_names_round_trip() -> anyhow::Result<()> {
+        for device_type in DeviceType::ALL
+            .into_iter()
+            .chain([DeviceType::Unrecognized(99)])
+        {
+            assert_eq!(device_type.to_string().parse::<DeviceType>()?, device_type);
+        }
+
+        insta::assert_debug_snapshot!(DeviceType::ALL.map(|v| v.to_string()), @r###"
+        [
+            "unknown",
+            "line-power",
+            "battery",
+            "ups",
+            "monitor",
+            "mouse",
+            "keyboard",
+            "pda",
+            "phone",
+            "media-player",
+            "tablet",
+            "computer",
+            "gaming-input",
+            "pen",
+            "touchpad",
+            "modem",
+            "network",
+            "headset",
+            "speakers",
+            "headphones",
+            "video",
+            "other-audio",
+            "remote-control",
+            "printer",
+            "scanner",
+            "camera",
+            "wearable",
+            "toy",
+            "bluetooth-generic",
+        ]
+        "###);
+
+        insta::assert_debug_snapshot!("not-a-name".parse::<DeviceType>().is_err(), @"true");
+        insta::assert_debug_snapshot!("unrecognized-0".parse::<DeviceType>()?, @"Unknown");
+
+        Ok(())
+    }
 }
@@ -0,0 +1,292 @@
+use std::fmt;
+
+use super::{
+    device_state::DeviceState,
+    event::BatteryEvent,
+    same_slot,
+    BatteryInfo,
+    BatteryInfoProperties,
+};
+
+/// The percentages [`BatteryInfo::diff`] reports crossing, the default low and critical
+/// thresholds.
+pub const DEFAULT_THRESHOLDS: [f64; 2] = [20.0, 5.0];
+
+/// A property that differs between two readings, `None` on the side where it isn't set.
+///
+/// See [`BatteryInfoProperties::same_reading`] for how values compare.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyChange {
+    pub old: Option<BatteryInfoProperties>,
+    pub new: Option<BatteryInfoProperties>,
+}
+
+impl PropertyChange {
+    /// The name of the property, see [`BatteryInfoProperties::name`].
+    pub fn name(&self) -> &'static str {
+        self.old
+            .as_ref()
+            .or(self.new.as_ref())
+            .map_or("", BatteryInfoProperties::name)
+    }
+}
+
+/// What a difference between two readings means.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffEvent {
+    /// The device state changed, e.g. from charging to discharging
+    StateChanged {
+        old: Option<DeviceState>,
+        new: Option<DeviceState>,
+    },
+    /// The percentage fell to or below `threshold`, or rose above it again
+    PercentageCrossed { threshold: f64, rising: bool },
+    /// One of the events hooks run on, e.g. [`BatteryEvent::AcPlugged`]
+    Battery(BatteryEvent),
+}
+
+impl fmt::Display for DiffEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = |state: &Option<DeviceState>| match state {
+            Some(state) => state.to_string(),
+            None => "none".to_owned(),
+        };
+
+        match self {
+            DiffEvent::StateChanged { old, new } => {
+                write!(f, "state {} -> {}", state(old), state(new))
+            }
+            DiffEvent::PercentageCrossed { threshold, rising } => write!(
+                f,
+                "percentage {} {}%",
+                if *rising { "rose above" } else { "fell to" },
+                threshold
+            ),
+            DiffEvent::Battery(event) => write!(f, "{}", event),
+        }
+    }
+}
+
+/// How a reading differs from an earlier one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryInfoDiff {
+    /// In the order of [`BatteryInfoProperties`]
+    pub changes: Vec<PropertyChange>,
+    pub events: Vec<DiffEvent>,
+}
+
+impl BatteryInfoDiff {
+    /// Whether the readings are the same.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The change of the property named `name`, e.g. `Percentage`.
+    pub fn change(&self, name: &str) -> Option<&PropertyChange> {
+        self.changes.iter().find(|change| change.name() == name)
+    }
+}
+
+impl BatteryInfo {
+    /// How `other`, a later reading, differs from this one, with crossings of the
+    /// [`DEFAULT_THRESHOLDS`].
+    pub fn diff(&self, other: &BatteryInfo) -> BatteryInfoDiff {
+        self.diff_with_thresholds(other, &DEFAULT_THRESHOLDS)
+    }
+
+    /// Like [`BatteryInfo::diff`], reporting crossings of `thresholds` instead.
+    ///
+    /// The events come in order: the state change, crossings from the highest threshold down,
+    /// then [`BatteryEvent::detect`]'s events.
+    pub fn diff_with_thresholds(&self, other: &BatteryInfo, thresholds: &[f64]) -> BatteryInfoDiff {
+        let changes = self
+            .slots()
+            .into_iter()
+            .zip(other.slots())
+            .filter(|(old, new)| !same_slot(old, new))
+            .map(|(old, new)| PropertyChange { old, new })
+            .collect();
+
+        let mut events = Vec::new();
+
+        if self.device_state != other.device_state {
+            events.push(DiffEvent::StateChanged {
+                old: self.device_state,
+                new: other.device_state,
+            });
+        }
+
+        if let (Some(old), Some(new)) = (self.percentage, other.percentage) {
+            let mut thresholds = thresholds.to_vec();
+            thresholds.sort_by(|a, b| b.total_cmp(a));
+
+            for threshold in thresholds {
+                if *old > threshold && *new <= threshold {
+                    events.push(DiffEvent::PercentageCrossed {
+                        threshold,
+                        rising: false,
+                    });
+                } else if *old <= threshold && *new > threshold {
+                    events.push(DiffEvent::PercentageCrossed {
+                        threshold,
+                        rising: true,
+                    });
+                }
+            }
+        }
+
+        events.extend(
+            BatteryEvent::detect(self, other)
+                .into_iter()
+                .map(DiffEvent::Battery),
+        );
+
+        BatteryInfoDiff { changes, events }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::battery_info::{
+        time_until::TimeUntil,
+        warning_level::WarningLevel,
+    };
+    use crate::test_utils::batt_info;
+
+    #[test]
+    fn changes_and_events() {
+        let prev = batt_info(vec![
+            BatteryInfoProperties::DeviceState(DeviceState::Charging),
+            BatteryInfoProperties::Percentage(22.0.into()),
+            BatteryInfoProperties::WarningLevel(WarningLevel::NoWarning),
+            BatteryInfoProperties::TimeUntil(TimeUntil::Full(Duration::from_secs(5400))),
+            BatteryInfoProperties::Model("5B10W13975".to_owned().into()),
+        ]);
+        let next = batt_info(vec![
+            BatteryInfoProperties::DeviceState(DeviceState::Discharging),
+            BatteryInfoProperties::Percentage(4.5.into()),
+            BatteryInfoProperties::WarningLevel(WarningLevel::Critical),
+            BatteryInfoProperties::Model("5B10W13975".to_owned().into()),
+            BatteryInfoProperties::EnergyRate(9.5.into()),
+        ]);
+
+        let diff = prev.diff(&next);
+
+        insta::assert_debug_snapshot!(diff.changes, @r###"
+        [
+            PropertyChange {
+                old: Some(
+                    DeviceState(
+                        Charging,
+                    ),
+                ),
+                new: Some(
+                    DeviceState(
+                        Discharging,
+                    ),
+                ),
+            },
+            PropertyChange {
+                old: Some(
+                    Percentage(
+                        Percentage(
+                            22.0,
+                        ),
+                    ),
+                ),
+                new: Some(
+                    Percentage(
+                        Percentage(
+                            4.5,
+                        ),
+                    ),
+                ),
+            },
+            PropertyChange {
+                old: Some(
+                    TimeUntil(
+                        Full(
+                            5400s,
+                        ),
+                    ),
+                ),
+                new: None,
+            },
+            PropertyChange {
+                old: Some(
+                    WarningLevel(
+                        NoWarning,
+                    ),
+                ),
+                new: Some(
+                    WarningLevel(
+                        Critical,
+                    ),
+                ),
+            },
+            PropertyChange {
+                old: None,
+                new: Some(
+                    EnergyRate(
+                        EnergyRate(
+                            9.5,
+                        ),
+                    ),
+                ),
+            },
+        ]
+        "###);
+        insta::assert_debug_snapshot!(
+            diff.events.iter().map(|event| event.to_string()).collect::<Vec<_>>(),
+            @r###"
+        [
+            "state charging -> discharging",
+            "percentage fell to 20%",
+            "percentage fell to 5%",
+            "ac_unplugged",
+            "level_critical",
+        ]
+        "###
+        );
+
+        insta::assert_debug_snapshot!(
+            next.diff(&prev).events.iter().map(|event| event.to_string()).collect::<Vec<_>>(),
+            @r###"
+        [
+            "state discharging -> charging",
+            "percentage rose above 20%",
+            "percentage rose above 5%",
+            "ac_plugged",
+        ]
+        "###
+        );
+
+        assert!(prev.diff(&prev).is_empty());
+        assert!(diff.change("Model").is_none());
+        assert_eq!(
+            diff.change("Percentage")
+                .and_then(|change| change.new.clone()),
+            Some(BatteryInfoProperties::Percentage(4.5.into()))
+        );
+    }
+
+    #[test]
+    fn custom_thresholds() {
+        let reading =
+            |percentage: f64| batt_info(vec![BatteryInfoProperties::Percentage(percentage.into())]);
+
+        let diff = reading(52.0).diff_with_thresholds(&reading(48.0), &[10.0, 50.0]);
+
+        insta::assert_debug_snapshot!(diff.events, @r###"
+        [
+            PercentageCrossed {
+                threshold: 50.0,
+                rising: false,
+            },
+        ]
+        "###);
+    }
+}
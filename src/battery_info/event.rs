@@ -0,0 +1,360 @@
+use std::{
+    fmt,
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use super::{
+    battery_level::BatteryLevel,
+    device_state::DeviceState,
+    device_type::DeviceType,
+    warning_level::WarningLevel,
+    BatteryInfo,
+};
+use crate::battery_interface::Device;
+
+/// Something that happened between two readings of a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub enum BatteryEvent {
+    AcPlugged,
+    AcUnplugged,
+    LevelLow,
+    LevelCritical,
+    FullyCharged,
+    DeviceAdded,
+    /// A UPS lost mains power and runs on its battery
+    UpsOnBattery,
+    /// A UPS has mains power again
+    UpsOnline,
+}
+
+/// Percentages at or below which a discharging device counts as low or critical, on top of
+/// the level UPower reports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelThresholds {
+    pub low: f64,
+    pub critical: f64,
+}
+
+impl BatteryEvent {
+    pub const ALL: [BatteryEvent; 8] = [
+        BatteryEvent::AcPlugged,
+        BatteryEvent::AcUnplugged,
+        BatteryEvent::LevelLow,
+        BatteryEvent::LevelCritical,
+        BatteryEvent::FullyCharged,
+        BatteryEvent::DeviceAdded,
+        BatteryEvent::UpsOnBattery,
+        BatteryEvent::UpsOnline,
+    ];
+
+    /// Events between two readings of the same device.
+    ///
+    /// AC events are derived from the device state, so a reading in an unknown state neither
+    /// plugs nor unplugs. Level events only fire when the level gets worse.
+    pub fn detect(prev: &BatteryInfo, next: &BatteryInfo) -> Vec<Self> {
+        Self::detect_with_thresholds(prev, next, None)
+    }
+
+    /// Like [`BatteryEvent::detect`], with a discharging device also low or critical once its
+    /// percentage reaches `thresholds`, whichever is worse.
+    pub fn detect_with_thresholds(
+        prev: &BatteryInfo,
+        next: &BatteryInfo,
+        thresholds: Option<LevelThresholds>,
+    ) -> Vec<Self> {
+        let mut events = Vec::new();
+
+        match (on_ac(prev), on_ac(next)) {
+            (Some(false), Some(true)) => events.push(Self::AcPlugged),
+            (Some(true), Some(false)) => events.push(Self::AcUnplugged),
+            _ => {}
+        }
+
+        match (level(prev, thresholds), level(next, thresholds)) {
+            (Level::Normal, Level::Low) => events.push(Self::LevelLow),
+            (Level::Normal | Level::Low, Level::Critical) => events.push(Self::LevelCritical),
+            _ => {}
+        }
+
+        if prev.device_state != Some(DeviceState::FullyCharged)
+            && next.device_state == Some(DeviceState::FullyCharged)
+        {
+            events.push(Self::FullyCharged);
+        }
+
+        events
+    }
+
+    /// Events between two listings of devices, matched up by [`Device::id`].
+    ///
+    /// Devices that don't power the system, e.g. a mouse, only ever produce
+    /// [`BatteryEvent::DeviceAdded`]. A UPS produces [`BatteryEvent::UpsOnBattery`] and
+    /// [`BatteryEvent::UpsOnline`] instead of the AC events.
+    pub fn detect_devices<'a>(prev: &[Device], next: &'a [Device]) -> Vec<(Self, &'a Device)> {
+        Self::detect_devices_with_thresholds(prev, next, None)
+    }
+
+    /// Like [`BatteryEvent::detect_devices`], with level events as in
+    /// [`BatteryEvent::detect_with_thresholds`].
+    pub fn detect_devices_with_thresholds<'a>(
+        prev: &[Device],
+        next: &'a [Device],
+        thresholds: Option<LevelThresholds>,
+    ) -> Vec<(Self, &'a Device)> {
+        let detect = |prev: &Device, next: &Device| {
+            Self::detect_with_thresholds(&prev.info, &next.info, thresholds)
+        };
+
+        next.iter()
+            .flat_map(|device| {
+                let events = match prev.iter().find(|p| p.id == device.id) {
+                    None => vec![Self::DeviceAdded],
+                    Some(_) if device.info.power_supply.is_some_and(|p| !*p) => vec![],
+                    Some(p) if device.info.device_type == Some(DeviceType::Ups) => {
+                        detect(p, device).into_iter().map(Self::for_ups).collect()
+                    }
+                    Some(p) => detect(p, device),
+                };
+
+                events.into_iter().map(move |event| (event, device))
+            })
+            .collect()
+    }
+
+    fn for_ups(self) -> Self {
+        match self {
+            Self::AcUnplugged => Self::UpsOnBattery,
+            Self::AcPlugged => Self::UpsOnline,
+            other => other,
+        }
+    }
+}
+
+/// Ordered from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    Normal,
+    Low,
+    Critical,
+}
+
+/// Prefers UPower's [`WarningLevel`] and falls back to the coarse [`BatteryLevel`], made worse
+/// by the percentage crossing `thresholds` while discharging.
+fn level(batt_info: &BatteryInfo, thresholds: Option<LevelThresholds>) -> Level {
+    let by_percentage = match (thresholds, batt_info.percentage, on_ac(batt_info)) {
+        (Some(thresholds), Some(percentage), Some(false)) => match *percentage {
+            p if p <= thresholds.critical => Level::Critical,
+            p if p <= thresholds.low => Level::Low,
+            _ => Level::Normal,
+        },
+        _ => Level::Normal,
+    };
+
+    reported_level(batt_info).max(by_percentage)
+}
+
+/// The level UPower reports.
+fn reported_level(batt_info: &BatteryInfo) -> Level {
+    match (batt_info.warning_level, batt_info.battery_level) {
+        (Some(WarningLevel::Critical | WarningLevel::Action), _) => Level::Critical,
+        (Some(WarningLevel::Low), _) => Level::Low,
+        (Some(WarningLevel::NoWarning | WarningLevel::Discharging), _) => Level::Normal,
+        (_, Some(BatteryLevel::Critical)) => Level::Critical,
+        (_, Some(BatteryLevel::Low)) => Level::Low,
+        _ => Level::Normal,
+    }
+}
+
+fn on_ac(batt_info: &BatteryInfo) -> Option<bool> {
+    match batt_info.device_state? {
+        DeviceState::Charging | DeviceState::FullyCharged | DeviceState::PendingCharge => {
+            Some(true)
+        }
+        DeviceState::Discharging | DeviceState::PendingDischarge | DeviceState::Empty => {
+            Some(false)
+        }
+        DeviceState::Unknown | DeviceState::Unrecognized(_) => None,
+    }
+}
+
+impl fmt::Display for BatteryEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BatteryEvent::AcPlugged => "ac_plugged",
+            BatteryEvent::AcUnplugged => "ac_unplugged",
+            BatteryEvent::LevelLow => "level_low",
+            BatteryEvent::LevelCritical => "level_critical",
+            BatteryEvent::FullyCharged => "fully_charged",
+            BatteryEvent::DeviceAdded => "device_added",
+            BatteryEvent::UpsOnBattery => "ups_on_battery",
+            BatteryEvent::UpsOnline => "ups_online",
+        })
+    }
+}
+
+impl FromStr for BatteryEvent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BatteryEvent::ALL
+            .into_iter()
+            .find(|event| event.to_string() == s)
+            .ok_or_else(|| anyhow!("unknown battery event: {:?}", s))
+    }
+}
+
+impl TryFrom<String> for BatteryEvent {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        battery_info::BatteryInfoProperties,
+        test_utils,
+    };
+
+    fn batt_info(state: DeviceState, warning_level: WarningLevel) -> BatteryInfo {
+        test_utils::batt_info([
+            BatteryInfoProperties::DeviceState(state),
+            BatteryInfoProperties::WarningLevel(warning_level),
+        ])
+    }
+
+    #[test]
+    fn detect() {
+        use DeviceState::*;
+
+        let readings = [
+            batt_info(FullyCharged, WarningLevel::NoWarning),
+            batt_info(Discharging, WarningLevel::NoWarning),
+            batt_info(Discharging, WarningLevel::Low),
+            batt_info(Unknown, WarningLevel::Action),
+            batt_info(Charging, WarningLevel::Low),
+            batt_info(FullyCharged, WarningLevel::NoWarning),
+        ];
+
+        let events = readings
+            .windows(2)
+            .map(|pair| BatteryEvent::detect(&pair[0], &pair[1]))
+            .collect::<Vec<_>>();
+
+        insta::assert_debug_snapshot!(events, @r###"
+        [
+            [
+                AcUnplugged,
+            ],
+            [
+                LevelLow,
+            ],
+            [
+                LevelCritical,
+            ],
+            [],
+            [
+                FullyCharged,
+            ],
+        ]
+        "###);
+    }
+
+    #[test]
+    fn detect_with_thresholds() {
+        let reading = |state, percentage: f64| {
+            let mut info = batt_info(state, WarningLevel::NoWarning);
+            info.set_propertry(BatteryInfoProperties::Percentage(percentage.into()));
+            info
+        };
+
+        let thresholds = Some(LevelThresholds {
+            low: 30.0,
+            critical: 10.0,
+        });
+
+        let readings = [
+            reading(DeviceState::Discharging, 35.0),
+            reading(DeviceState::Discharging, 30.0),
+            reading(DeviceState::Discharging, 9.0),
+            // plugged in at a critical percentage isn't critical
+            reading(DeviceState::Charging, 9.0),
+            reading(DeviceState::Discharging, 9.0),
+        ];
+
+        let events = readings
+            .windows(2)
+            .map(|pair| BatteryEvent::detect_with_thresholds(&pair[0], &pair[1], thresholds))
+            .collect::<Vec<_>>();
+
+        insta::assert_debug_snapshot!(events, @r###"
+        [
+            [
+                LevelLow,
+            ],
+            [
+                LevelCritical,
+            ],
+            [
+                AcPlugged,
+            ],
+            [
+                AcUnplugged,
+                LevelCritical,
+            ],
+        ]
+        "###);
+
+        // without thresholds only the warning level counts
+        assert!(BatteryEvent::detect(&readings[0], &readings[2]).is_empty());
+    }
+
+    #[test]
+    fn detect_devices() {
+        let device = |id: &str, state, power_supply: bool| {
+            let mut info = batt_info(state, WarningLevel::NoWarning);
+            info.set_propertry(BatteryInfoProperties::PowerSupply(power_supply.into()));
+
+            Device {
+                id: id.to_owned(),
+                info,
+            }
+        };
+
+        let prev = [
+            device("BAT0", DeviceState::Discharging, true),
+            device("mouse", DeviceState::Discharging, false),
+        ];
+        let next = [
+            device("BAT0", DeviceState::Charging, true),
+            device("mouse", DeviceState::Charging, false),
+            device("headset", DeviceState::Discharging, false),
+        ];
+
+        let events = BatteryEvent::detect_devices(&prev, &next)
+            .into_iter()
+            .map(|(event, device)| format!("{} {}", event, device.id))
+            .collect::<Vec<_>>();
+
+        insta::assert_debug_snapshot!(events, @r###"
+        [
+            "ac_plugged BAT0",
+            "device_added headset",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn event_names_round_trip() {
+        for event in BatteryEvent::ALL {
+            assert_eq!(event.to_string().parse::<BatteryEvent>().unwrap(), event);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+/// Enums that can be shown to a user as a human readable, translatable label.
+///
+/// Every value has a translation key `<KIND>.<canonical name>`, e.g. `device-state.charging`,
+/// and an English default label used whenever a [`Translator`] has nothing for it.
+pub trait Labeled: std::fmt::Display {
+    const KIND: &'static str;
+
+    fn default_label(&self) -> String;
+
+    fn label_key(&self) -> String {
+        format!("{}.{}", Self::KIND, self)
+    }
+
+    fn label(&self, language: &str, translator: &impl Translator) -> String {
+        translator
+            .translate(language, &self.label_key())
+            .unwrap_or_else(|| self.default_label())
+    }
+}
+
+/// Hook for looking up translated labels, implemented for [`LabelTable`] and for closures taking
+/// a language and a translation key.
+pub trait Translator {
+    fn translate(&self, language: &str, key: &str) -> Option<String>;
+}
+
+impl<F> Translator for F
+where
+    F: Fn(&str, &str) -> Option<String>,
+{
+    fn translate(&self, language: &str, key: &str) -> Option<String> {
+        self(language, key)
+    }
+}
+
+/// Translated labels keyed by language and translation key.
+#[derive(Debug, Clone, Default)]
+pub struct LabelTable {
+    labels: HashMap<String, HashMap<String, String>>,
+}
+
+impl LabelTable {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn insert(
+        &mut self,
+        language: impl Into<String>,
+        key: impl Into<String>,
+        label: impl Into<String>,
+    ) -> &mut Self {
+        self.labels
+            .entry(language.into())
+            .or_default()
+            .insert(key.into(), label.into());
+        self
+    }
+
+    /// Add the labels for one language from `key = label` lines, blank lines and lines starting
+    /// with `#` are ignored.
+    pub fn insert_lines(&mut self, language: &str, lines: &str) -> anyhow::Result<&mut Self> {
+        for (number, line) in lines.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, label)) = line.split_once('=') else {
+                anyhow::bail!(
+                    "line {}: expected `key = label`, got {:?}",
+                    number + 1,
+                    line
+                );
+            };
+
+            self.insert(language, key.trim(), label.trim());
+        }
+
+        Ok(self)
+    }
+}
+
+impl Translator for LabelTable {
+    fn translate(&self, language: &str, key: &str) -> Option<String> {
+        // fall back from e.g. `sv_SE` to `sv`
+        let base_language = language.split(['_', '-', '.']).next().unwrap_or(language);
+
+        [language, base_language]
+            .into_iter()
+            .find_map(|language| self.labels.get(language)?.get(key))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_info::{
+        device_state::DeviceState,
+        device_type::DeviceType,
+    };
+
+    #[test]
+    fn labels() -> anyhow::Result<()> {
+        let mut table = LabelTable::new();
+
+        table.insert_lines(
+            "sv",
+            "
+            # svenska
+            device-state.charging = Laddar
+            device-type.line-power = Nätström
+            ",
+        )?;
+
+        insta::assert_debug_snapshot!(DeviceState::Charging.label("sv_SE.UTF-8", &table), @r###""Laddar""###);
+        insta::assert_debug_snapshot!(DeviceType::LinePower.label("sv", &table), @r###""Nätström""###);
+
+        // missing translations fall back to English
+        insta::assert_debug_snapshot!(DeviceState::PendingCharge.label("sv", &table), @r###""Waiting to charge""###);
+        insta::assert_debug_snapshot!(DeviceState::Charging.label("de", &table), @r###""Charging""###);
+
+        let translator = |language: &str, key: &str| match (language, key) {
+            ("de", "device-state.charging") => Some("Wird geladen".to_owned()),
+            _ => None,
+        };
+
+        insta::assert_debug_snapshot!(DeviceState::Charging.label("de", &translator), @r###""Wird geladen""###);
+
+        insta::assert_debug_snapshot!(table.insert_lines("sv", "no separator").is_err(), @"true");
+
+        Ok(())
+    }
+}
@@ -1,10 +1,17 @@
-use std::{ops::Deref, time::Duration};
+use std::{
+    ops::Deref,
+    time::Duration,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TimeUntil {
     Full(Duration),
     Empty(Duration),
     Unknown(Duration),
+    /// Neither estimate applies, e.g. the battery is fully charged or sitting on AC power
+    NotApplicable,
+    /// The battery is (dis)charging but UPower has not settled on a rate yet
+    Calculating,
 }
 
 impl TimeUntil {
@@ -13,6 +20,8 @@ impl TimeUntil {
             TimeUntil::Full(_) => "TimeToFull",
             TimeUntil::Empty(_) => "TimeToEmpty",
             TimeUntil::Unknown(_) => "Unknown",
+            TimeUntil::NotApplicable => "NotApplicable",
+            TimeUntil::Calculating => "Calculating",
         }
     }
 }
@@ -25,6 +34,7 @@ impl Deref for TimeUntil {
             TimeUntil::Full(d) => d,
             TimeUntil::Empty(d) => d,
             TimeUntil::Unknown(d) => d,
+            TimeUntil::NotApplicable | TimeUntil::Calculating => &Duration::ZERO,
         }
     }
 }
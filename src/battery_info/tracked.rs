@@ -0,0 +1,220 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use super::{
+    BatteryInfo,
+    BatteryInfoProperties,
+};
+
+/// Where an update of a property came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateSource {
+    /// A PropertiesChanged signal from UPower
+    Signal,
+    /// Reading every property of a UPower device
+    Poll,
+    /// The kernel's power supply class
+    Sysfs,
+    /// The `/proc/acpi/battery` files of old kernels
+    Acpi,
+}
+
+impl fmt::Display for UpdateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UpdateSource::Signal => "signal",
+            UpdateSource::Poll => "poll",
+            UpdateSource::Sysfs => "sysfs",
+            UpdateSource::Acpi => "acpi",
+        })
+    }
+}
+
+/// When a property was last updated and from where.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldUpdate {
+    pub at: Instant,
+    pub source: UpdateSource,
+}
+
+/// A [`BatteryInfo`] patched property by property, remembering when and from where each one
+/// was last updated.
+///
+/// Updates older than the one a property already has are dropped, so a slow poll can't undo a
+/// signal that arrived in the meantime.
+#[derive(Debug, Default)]
+pub struct TrackedBatteryInfo {
+    info: BatteryInfo,
+    updates: HashMap<&'static str, FieldUpdate>,
+}
+
+impl TrackedBatteryInfo {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The merged reading.
+    pub fn info(&self) -> &BatteryInfo {
+        &self.info
+    }
+
+    /// Update `prop` as of now.
+    pub fn merge(&mut self, prop: BatteryInfoProperties, source: UpdateSource) -> bool {
+        self.merge_at(prop, source, Instant::now())
+    }
+
+    /// Update `prop` as of `at`, whether it was taken, i.e. `at` isn't older than the last
+    /// update of the property.
+    ///
+    /// An update to the same value still counts, it makes the property fresh again.
+    pub fn merge_at(
+        &mut self,
+        prop: BatteryInfoProperties,
+        source: UpdateSource,
+        at: Instant,
+    ) -> bool {
+        let name = prop.name();
+
+        if self.updates.get(name).is_some_and(|last| last.at > at) {
+            return false;
+        }
+
+        prop.insert_property_by_mut_ref(&mut self.info);
+        self.updates.insert(name, FieldUpdate { at, source });
+
+        true
+    }
+
+    /// Update every property set in `batt_info` as of `at`, e.g. a full poll or a decoded
+    /// PropertiesChanged signal, returning the names of the properties that were taken.
+    pub fn merge_info(
+        &mut self,
+        batt_info: &BatteryInfo,
+        source: UpdateSource,
+        at: Instant,
+    ) -> Vec<&'static str> {
+        batt_info
+            .properties()
+            .into_iter()
+            .filter_map(|prop| {
+                let name = prop.name();
+                self.merge_at(prop, source, at).then_some(name)
+            })
+            .collect()
+    }
+
+    /// The last update of the property named `name`, e.g. `Percentage`.
+    pub fn last_update(&self, name: &str) -> Option<FieldUpdate> {
+        self.updates.get(name).copied()
+    }
+
+    /// How long before `now` the property named `name` was last updated.
+    pub fn age(&self, name: &str, now: Instant) -> Option<Duration> {
+        self.last_update(name)
+            .map(|update| now.saturating_duration_since(update.at))
+    }
+
+    /// Names of the properties last updated more than `max_age` before `now`, in the order of
+    /// [`BatteryInfoProperties`].
+    pub fn stale(&self, max_age: Duration, now: Instant) -> Vec<&'static str> {
+        self.info
+            .properties()
+            .iter()
+            .map(BatteryInfoProperties::name)
+            .filter(|name| self.age(name, now).is_some_and(|age| age > max_age))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use zbus::zvariant::OwnedValue;
+
+    use super::*;
+    use crate::{
+        battery_info::device_state::DeviceState,
+        battery_interface::upower::{
+            decode_changed_properties,
+            ParseMode,
+        },
+        test_utils::batt_info,
+    };
+
+    #[test]
+    fn merge_and_staleness() -> anyhow::Result<()> {
+        let start = Instant::now();
+        let secs = |secs: u64| start + Duration::from_secs(secs);
+
+        let mut tracked = TrackedBatteryInfo::new();
+
+        let poll = batt_info([
+            BatteryInfoProperties::DeviceState(DeviceState::Discharging),
+            BatteryInfoProperties::Percentage(41.0.into()),
+            BatteryInfoProperties::EnergyRate(7.5.into()),
+        ]);
+
+        tracked.merge_info(&poll, UpdateSource::Poll, secs(0));
+
+        // a signal only carries what changed, TimeToEmpty is resolved with the state so far
+        let changed: HashMap<String, OwnedValue> = HashMap::from([
+            ("Percentage".to_owned(), 40.0f64.into()),
+            ("TimeToEmpty".to_owned(), 3600i64.into()),
+        ]);
+        let delta =
+            decode_changed_properties(&changed, tracked.info().device_state(), ParseMode::Strict)?;
+
+        insta::assert_debug_snapshot!(
+            tracked.merge_info(&delta, UpdateSource::Signal, secs(20)),
+            @r###"
+        [
+            "Percentage",
+            "TimeUntil",
+        ]
+        "###
+        );
+
+        // a poll that started before the signal arrived
+        let taken = tracked.merge_at(
+            BatteryInfoProperties::Percentage(41.0.into()),
+            UpdateSource::Poll,
+            secs(10),
+        );
+
+        insta::assert_debug_snapshot!(taken, @"false");
+        insta::assert_debug_snapshot!(tracked.info().percentage(), @r###"
+        Some(
+            Percentage(
+                40.0,
+            ),
+        )
+        "###);
+        insta::assert_debug_snapshot!(
+            tracked.last_update("Percentage").map(|update| (update.source, update.at - start)),
+            @r###"
+        Some(
+            (
+                Signal,
+                20s,
+            ),
+        )
+        "###
+        );
+        insta::assert_debug_snapshot!(tracked.stale(Duration::from_secs(15), secs(30)), @r###"
+        [
+            "State",
+            "EnergyRate",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(tracked.age("Voltage", secs(30)), @"None");
+
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use super::label::Labeled;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WarningLevel {
     Unknown,
@@ -6,6 +8,8 @@ pub enum WarningLevel {
     Low,
     Critical,
     Action,
+    /// A raw value this crate doesn't know about, e.g. one added in a newer UPower
+    Unrecognized(u32),
 }
 
 impl From<u32> for WarningLevel {
@@ -16,7 +20,8 @@ impl From<u32> for WarningLevel {
             3 => WarningLevel::Low,
             4 => WarningLevel::Critical,
             5 => WarningLevel::Action,
-            _ => WarningLevel::Unknown,
+            0 => WarningLevel::Unknown,
+            other => WarningLevel::Unrecognized(other),
         }
     }
 }
@@ -30,6 +35,7 @@ impl From<WarningLevel> for u32 {
             WarningLevel::Critical => 4,
             WarningLevel::Action => 5,
             WarningLevel::Unknown => 0,
+            WarningLevel::Unrecognized(other) => other,
         }
     }
 }
@@ -42,35 +48,91 @@ impl From<WarningLevel> for (u32, WarningLevel) {
 }
 
 pub struct WarningLevelIter {
-    index: u32,
+    index: usize,
 }
 
 impl Iterator for WarningLevelIter {
     type Item = WarningLevel;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index > 6 {
-            return None;
-        }
-        let item: WarningLevel = self.index.into();
+        let item = *WarningLevel::ALL.get(self.index)?;
         self.index += 1;
         Some(item)
     }
 }
 
 impl WarningLevel {
+    /// Every WarningLevel this crate recognizes, ordered by raw value
+    pub const ALL: [WarningLevel; 6] = [
+        WarningLevel::Unknown,
+        WarningLevel::NoWarning,
+        WarningLevel::Discharging,
+        WarningLevel::Low,
+        WarningLevel::Critical,
+        WarningLevel::Action,
+    ];
+
     pub fn iter_levels() -> WarningLevelIter {
         WarningLevelIter { index: 0 }
     }
 }
 
+impl std::fmt::Display for WarningLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            WarningLevel::Unknown => "unknown",
+            WarningLevel::NoWarning => "none",
+            WarningLevel::Discharging => "discharging",
+            WarningLevel::Low => "low",
+            WarningLevel::Critical => "critical",
+            WarningLevel::Action => "action",
+            WarningLevel::Unrecognized(other) => return write!(f, "unrecognized-{}", other),
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for WarningLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(other) = s.strip_prefix("unrecognized-") {
+            return Ok(WarningLevel::from(other.parse::<u32>()?));
+        }
+
+        WarningLevel::ALL
+            .into_iter()
+            .find(|v| v.to_string() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown warning level: {:?}", s))
+    }
+}
+
+impl Labeled for WarningLevel {
+    const KIND: &'static str = "warning-level";
+
+    fn default_label(&self) -> String {
+        let label = match self {
+            WarningLevel::Unknown => "Unknown",
+            WarningLevel::NoWarning => "No warning",
+            WarningLevel::Discharging => "Discharging",
+            WarningLevel::Low => "Low",
+            WarningLevel::Critical => "Critical",
+            WarningLevel::Action => "Action required",
+            WarningLevel::Unrecognized(other) => return format!("Unrecognized ({})", other),
+        };
+
+        label.to_owned()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn verify_warning_level_enum() {
-        assert!(WarningLevel::iter_levels().eq((0u32..=6u32).map(|i| i.into())));
+        assert!(WarningLevel::iter_levels().eq((0u32..=5u32).map(|i| i.into())));
 
         insta::assert_debug_snapshot!(WarningLevel::iter_levels()
             .map(|t| t.into())
@@ -100,11 +162,49 @@ mod tests {
                 5,
                 Action,
             ),
-            (
-                0,
-                Unknown,
-            ),
         ]
         "###)
     }
+
+    #[test]
+    fn unrecognized_warning_level_is_preserved() {
+        let warning_level = WarningLevel::from(6);
+
+        insta::assert_debug_snapshot!(warning_level, @r###"
+        Unrecognized(
+            6,
+        )
+        "###);
+
+        insta::assert_debug_snapshot!(u32::from(warning_level), @"6");
+    }
+
+    #[test]
+    fn warning_level_names_round_trip() -> anyhow::Result<()> {
+        for warning_level in WarningLevel::ALL
+            .into_iter()
+            .chain([WarningLevel::Unrecognized(99)])
+        {
+            assert_eq!(
+                warning_level.to_string().parse::<WarningLevel>()?,
+                warning_level
+            );
+        }
+
+        insta::assert_debug_snapshot!(WarningLevel::ALL.map(|v| v.to_string()), @r###"
+        [
+            "unknown",
+            "none",
+            "discharging",
+            "low",
+            "critical",
+            "action",
+        ]
+        "###);
+
+        insta::assert_debug_snapshot!("not-a-name".parse::<WarningLevel>().is_err(), @"true");
+        insta::assert_debug_snapshot!("unrecognized-0".parse::<WarningLevel>()?, @"Unknown");
+
+        Ok(())
+    }
 }
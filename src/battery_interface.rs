@@ -1,7 +1,170 @@
-use crate::battery_info::BatteryInfo;
+use std::fmt;
 
+use crate::battery_info::{
+    time_until::TimeUntil,
+    BatteryInfo,
+};
+
+pub mod acpi;
+pub mod cached;
+pub mod fallback;
+pub mod replay;
+pub mod simulated;
+pub mod sysfs;
 pub mod upower;
 
+/// A single power source, as opposed to the aggregate a backend reports from `battery_info`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    /// Identifies the device within its backend, e.g. a D-Bus object path
+    pub id: String,
+    pub info: BatteryInfo,
+}
+
+impl fmt::Display for Device {
+    /// One line summary, e.g. `battery_BAT0: 81% discharging, 2h 5m until empty`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = &self.info;
+
+        write!(f, "{}:", self.id.rsplit('/').next().unwrap_or(&self.id))?;
+
+        if let Some(percentage) = info.percentage {
+            write!(f, " {:.0}%", *percentage)?;
+        }
+
+        if let Some(state) = info.device_state {
+            write!(f, " {}", state)?;
+        }
+
+        match info.time_until {
+            Some(TimeUntil::Empty(d)) => write!(f, ", {} until empty", hours_minutes(d)),
+            Some(TimeUntil::Full(d)) => write!(f, ", {} until full", hours_minutes(d)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn hours_minutes(duration: std::time::Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    format!("{}h {}m", minutes / 60, minutes % 60)
+}
+
+/// A power source that was listed but couldn't be read, e.g. a headset unplugged between
+/// listing the devices and reading them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedDevice {
+    pub id: String,
+    pub error: String,
+}
+
+impl fmt::Display for SkippedDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.id, self.error)
+    }
+}
+
+/// The devices a backend could read, and the ones it left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Listing {
+    pub devices: Vec<Device>,
+    pub skipped: Vec<SkippedDevice>,
+}
+
 pub trait BatteryInterface {
     fn battery_info() -> std::result::Result<BatteryInfo, impl Into<Box<dyn std::error::Error + 'static>>>;
+
+    /// Every power source the backend knows about, backends that can only report the aggregate
+    /// don't have to implement it.
+    fn devices() -> std::result::Result<Vec<Device>, impl Into<Box<dyn std::error::Error + 'static>>>
+    {
+        Err::<_, anyhow::Error>(anyhow::anyhow!("this backend can't list devices"))
+    }
+}
+
+/// A backend to read from, unlike [`BatteryInterface`] which reads from the default instance of
+/// a backend type.
+///
+/// Sources are `Send + Sync`, one can be read from several threads at once. The backends in this
+/// module are also `Clone`, clones are handles to the same source: they share UPower's D-Bus
+/// connection, a replay's position, a simulation's clock and a [`CachedReader`]'s reading.
+///
+/// [`CachedReader`]: cached::CachedReader
+pub trait BatterySource: Send + Sync {
+    /// Short name of the backend, e.g. `upower`
+    fn name(&self) -> &str;
+
+    fn battery_info(&self) -> anyhow::Result<BatteryInfo>;
+
+    fn devices(&self) -> anyhow::Result<Vec<Device>>;
+
+    /// [`BatterySource::devices`], along with the devices that were listed but left out because
+    /// they couldn't be read.
+    fn listing(&self) -> anyhow::Result<Listing> {
+        Ok(Listing {
+            devices: self.devices()?,
+            skipped: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        battery_info::{
+            device_state::DeviceState,
+            BatteryInfoProperties,
+        },
+        test_utils::batt_info,
+    };
+
+    #[test]
+    fn display_device() {
+        let info = batt_info([
+            BatteryInfoProperties::Percentage(80.6.into()),
+            BatteryInfoProperties::DeviceState(DeviceState::Discharging),
+            BatteryInfoProperties::TimeUntil(TimeUntil::Empty(Duration::from_secs(7500))),
+        ]);
+
+        let device = Device {
+            id: "/org/freedesktop/UPower/devices/battery_BAT0".to_owned(),
+            info,
+        };
+
+        insta::assert_snapshot!(device.to_string(), @"battery_BAT0: 81% discharging, 2h 5m until empty");
+    }
+
+    #[test]
+    fn devices_unsupported_by_default() {
+        struct AggregateOnly;
+
+        impl BatteryInterface for AggregateOnly {
+            fn battery_info(
+            ) -> std::result::Result<BatteryInfo, impl Into<Box<dyn std::error::Error + 'static>>>
+            {
+                Ok::<_, anyhow::Error>(BatteryInfo::new())
+            }
+        }
+
+        let error = AggregateOnly::devices().map_err(Into::into).unwrap_err();
+
+        insta::assert_snapshot!(error.to_string(), @"this backend can't list devices");
+    }
+
+    #[test]
+    fn shareable_across_threads() {
+        fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+
+        assert_shareable::<upower::UPower>();
+        assert_shareable::<sysfs::Sysfs>();
+        assert_shareable::<acpi::Acpi>();
+        assert_shareable::<fallback::Fallback>();
+        assert_shareable::<replay::Replay>();
+        assert_shareable::<simulated::Simulation>();
+        assert_shareable::<cached::CachedReader<upower::UPower>>();
+        assert_shareable::<BatteryInfo>();
+        assert_shareable::<Device>();
+    }
 }
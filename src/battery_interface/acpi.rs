@@ -0,0 +1,514 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    anyhow,
+    Context,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::{
+    sysfs,
+    BatteryInterface,
+    BatterySource,
+    Device,
+    Listing,
+    SkippedDevice,
+};
+use crate::{
+    aggregate::Aggregator,
+    battery_info::{
+        battery_level::BatteryLevel,
+        device_state::DeviceState,
+        device_type::DeviceType,
+        BatteryInfo,
+        BatteryInfoProperties,
+    },
+};
+
+/// Where kernels before the power supply class list batteries.
+pub const ACPI_BATTERY_ROOT: &str = "/proc/acpi/battery";
+
+/// `key:   value` lines of the `info` and `state` files
+static LINE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^([^:]+?)\s*:\s*(.*?)\s*$").unwrap());
+
+/// A number with its unit, e.g. `4064 mAh`
+static QUANTITY: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\d+)\s*(mWh|mAh|mW|mA|mV)?$").unwrap());
+
+/// Reads the legacy `/proc/acpi/battery/*/{info,state}` files of old kernels.
+///
+/// Like [`Sysfs`](super::sysfs::Sysfs) there's no aggregate, `battery_info` combines the
+/// batteries with an [`Aggregator`].
+#[derive(Debug, Clone)]
+pub struct Acpi {
+    root: PathBuf,
+    aggregator: Aggregator,
+}
+
+impl Default for Acpi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Acpi {
+    pub fn new() -> Self {
+        Self::with_root(ACPI_BATTERY_ROOT)
+    }
+
+    /// Read the batteries under `root` instead, e.g. fixtures in tests.
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            aggregator: Aggregator::default(),
+        }
+    }
+
+    pub fn with_aggregator(mut self, aggregator: Aggregator) -> Self {
+        self.aggregator = aggregator;
+        self
+    }
+
+    /// Every battery under the root that could be read, see [`Acpi::listing`].
+    pub fn devices(&self) -> anyhow::Result<Vec<Device>> {
+        Ok(self.listing()?.devices)
+    }
+
+    /// Every battery under the root, ordered by name. The id is the battery's directory.
+    ///
+    /// A battery whose files can't be read is left out and reported as skipped, like
+    /// [`Sysfs::listing`](super::sysfs::Sysfs::listing) does.
+    pub fn listing(&self) -> anyhow::Result<Listing> {
+        let mut paths = fs::read_dir(&self.root)
+            .with_context(|| format!("reading {}", self.root.display()))?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        paths.sort();
+
+        let mut listing = Listing::default();
+
+        for path in paths {
+            let id = path.display().to_string();
+
+            match self.device_battery_info(&path) {
+                Ok(info) => listing.devices.push(Device { info, id }),
+                Err(e) => listing.skipped.push(SkippedDevice {
+                    id,
+                    error: format!("{:#}", e),
+                }),
+            }
+        }
+
+        Ok(listing)
+    }
+
+    /// The battery in the directory `path`.
+    pub fn device_battery_info(&self, path: &Path) -> anyhow::Result<BatteryInfo> {
+        let read = |name: &str| {
+            let file = path.join(name);
+            fs::read_to_string(&file).with_context(|| format!("reading {}", file.display()))
+        };
+
+        let mut batt_info = decode(&read("info")?, &read("state")?)
+            .with_context(|| format!("decoding {}", path.display()))?;
+
+        if let Some(name) = path.file_name() {
+            batt_info.set_propertry(BatteryInfoProperties::NativePath(
+                name.to_string_lossy().into_owned().into(),
+            ));
+        }
+
+        Ok(batt_info)
+    }
+
+    /// The batteries combined by the aggregator.
+    pub fn battery_info(&self) -> anyhow::Result<BatteryInfo> {
+        self.aggregator
+            .aggregate(self.devices()?)
+            .map(|aggregate| aggregate.info)
+            .ok_or_else(|| anyhow!("no battery in {}", self.root.display()))
+    }
+}
+
+fn parse(text: &str) -> HashMap<&str, &str> {
+    text.lines()
+        .filter_map(|line| {
+            let captures = LINE.captures(line)?;
+            Some((captures.get(1)?.as_str(), captures.get(2)?.as_str()))
+        })
+        .collect()
+}
+
+/// Decodes the contents of a battery's `info` and `state` files.
+///
+/// Capacities are in mWh, or in mAh on batteries that report charge, which are converted to
+/// energy with the design voltage.
+fn decode(info: &str, state: &str) -> anyhow::Result<BatteryInfo> {
+    let mut values = parse(info);
+    values.extend(parse(state));
+
+    // a missing value, or one the firmware reports as "unknown"
+    let quantity = |key: &str| -> anyhow::Result<Option<(f64, &str)>> {
+        match values.get(key) {
+            None | Some(&"unknown") => Ok(None),
+            Some(value) => {
+                let captures = QUANTITY
+                    .captures(value)
+                    .ok_or_else(|| anyhow!("{}: not a quantity: {:?}", key, value))?;
+
+                let number = captures[1]
+                    .parse::<f64>()
+                    .map_err(|e| anyhow!("{}: error: {:?}, value: {:?}", key, e, value))?;
+
+                Ok(Some((
+                    number,
+                    captures.get(2).map_or("", |unit| unit.as_str()),
+                )))
+            }
+        }
+    };
+
+    let mut batt_info = BatteryInfo::new();
+    let mut set = |prop| batt_info.set_propertry(prop);
+
+    set(BatteryInfoProperties::DeviceType(DeviceType::Battery));
+    set(BatteryInfoProperties::PowerSupply(true.into()));
+
+    if values.get("present") == Some(&"no") {
+        return Ok(batt_info);
+    }
+
+    let voltage = quantity("present voltage")?.map(|(mv, _)| mv / 1000.0);
+    let design_voltage = quantity("design voltage")?
+        .map(|(mv, _)| mv / 1000.0)
+        .or(voltage);
+
+    let energy = |key: &str| -> anyhow::Result<Option<f64>> {
+        Ok(match quantity(key)? {
+            Some((mwh, "mWh")) => Some(mwh / 1000.0),
+            Some((mah, "mAh")) => design_voltage.map(|v| mah / 1000.0 * v),
+            _ => None,
+        })
+    };
+
+    let energy_now = energy("remaining capacity")?;
+    let energy_full = energy("last full capacity")?;
+    let energy_rate = match quantity("present rate")? {
+        Some((mw, "mW")) => Some(mw / 1000.0),
+        Some((ma, "mA")) => voltage.or(design_voltage).map(|v| ma / 1000.0 * v),
+        _ => None,
+    };
+
+    let state = values.get("charging state").map(|state| match *state {
+        "charging" => DeviceState::Charging,
+        "discharging" => DeviceState::Discharging,
+        "charged" => DeviceState::FullyCharged,
+        _ => DeviceState::Unknown,
+    });
+    if let Some(state) = state {
+        set(BatteryInfoProperties::DeviceState(state));
+    }
+
+    if let Some((now, full)) = energy_now.zip(energy_full).filter(|(_, full)| *full > 0.0) {
+        set(BatteryInfoProperties::Percentage(
+            (now / full * 100.0).min(100.0).into(),
+        ));
+    }
+    if let Some(energy) = energy_now {
+        set(BatteryInfoProperties::Energy(energy.into()));
+    }
+    if let Some(energy_full) = energy_full {
+        set(BatteryInfoProperties::EnergyFull(energy_full.into()));
+    }
+    if let Some(energy_rate) = energy_rate {
+        set(BatteryInfoProperties::EnergyRate(energy_rate.into()));
+    }
+    if let Some(voltage) = voltage {
+        set(BatteryInfoProperties::Voltage(voltage.into()));
+    }
+    if let Some((cycles, _)) = quantity("cycle count")? {
+        set(BatteryInfoProperties::ChargeCycles((cycles as i32).into()));
+    }
+    if let Some(model) = values.get("model number").filter(|model| !model.is_empty()) {
+        set(BatteryInfoProperties::Model(model.to_string().into()));
+    }
+    if values.get("capacity state") == Some(&"critical") {
+        set(BatteryInfoProperties::BatteryLevel(BatteryLevel::Critical));
+    }
+
+    if let Some(time_until) = sysfs::time_until(state, energy_now, energy_full, energy_rate) {
+        set(BatteryInfoProperties::TimeUntil(time_until));
+    }
+
+    Ok(batt_info)
+}
+
+impl BatteryInterface for Acpi {
+    fn battery_info(
+    ) -> std::result::Result<BatteryInfo, impl Into<Box<dyn std::error::Error + 'static>>> {
+        Acpi::new().battery_info()
+    }
+
+    fn devices() -> std::result::Result<Vec<Device>, impl Into<Box<dyn std::error::Error + 'static>>>
+    {
+        Acpi::new().devices()
+    }
+}
+
+impl BatterySource for Acpi {
+    fn name(&self) -> &str {
+        "acpi"
+    }
+
+    fn battery_info(&self) -> anyhow::Result<BatteryInfo> {
+        Acpi::battery_info(self)
+    }
+
+    fn devices(&self) -> anyhow::Result<Vec<Device>> {
+        Acpi::devices(self)
+    }
+
+    fn listing(&self) -> anyhow::Result<Listing> {
+        Acpi::listing(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INFO: &str = "\
+present:                 yes
+design capacity:         4400 mAh
+last full capacity:      4064 mAh
+battery technology:      rechargeable
+design voltage:          10800 mV
+design capacity warning: 300 mAh
+design capacity low:     163 mAh
+cycle count:             0
+capacity granularity 1:  32 mAh
+capacity granularity 2:  32 mAh
+model number:            DELL T5443
+serial number:           3614
+battery type:            LION
+OEM info:                SMP
+";
+
+    const STATE: &str = "\
+present:                 yes
+capacity state:          ok
+charging state:          discharging
+present rate:            1354 mA
+remaining capacity:      2964 mAh
+present voltage:         11432 mV
+";
+
+    // reports energy, charging at an unknown rate
+    const INFO_MWH: &str = "\
+present:                 yes
+design capacity:         47520 mWh
+last full capacity:      41130 mWh
+design voltage:          10800 mV
+model number:
+";
+
+    const STATE_MWH: &str = "\
+present:                 yes
+capacity state:          critical
+charging state:          charging
+present rate:            unknown
+remaining capacity:      1960 mWh
+present voltage:         10910 mV
+";
+
+    #[test]
+    fn decode_charge() -> anyhow::Result<()> {
+        insta::assert_debug_snapshot!(decode(INFO, STATE)?, @r###"
+        BatteryInfo {
+            device_type: Some(
+                Battery,
+            ),
+            device_state: Some(
+                Discharging,
+            ),
+            percentage: Some(
+                Percentage(
+                    72.93307086614172,
+                ),
+            ),
+            power_supply: Some(
+                PowerSupply(
+                    true,
+                ),
+            ),
+            battery_level: None,
+            icon_name: None,
+            time_until: Some(
+                Empty(
+                    7444.980686001s,
+                ),
+            ),
+            warning_level: None,
+            energy: Some(
+                Energy(
+                    32.0112,
+                ),
+            ),
+            energy_full: Some(
+                Energy(
+                    43.891200000000005,
+                ),
+            ),
+            energy_rate: Some(
+                EnergyRate(
+                    15.478928000000002,
+                ),
+            ),
+            voltage: Some(
+                Voltage(
+                    11.432,
+                ),
+            ),
+            temperature: None,
+            charge_cycles: Some(
+                ChargeCycles(
+                    0,
+                ),
+            ),
+            model: Some(
+                Model(
+                    "DELL T5443",
+                ),
+            ),
+            native_path: None,
+            diagnostics: [],
+        }
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_energy() -> anyhow::Result<()> {
+        let batt_info = decode(INFO_MWH, STATE_MWH)?;
+
+        insta::assert_debug_snapshot!(
+            (
+                batt_info.device_state,
+                batt_info.percentage,
+                batt_info.energy,
+                batt_info.energy_rate,
+                batt_info.battery_level,
+                batt_info.time_until,
+                batt_info.model,
+            ),
+            @r###"
+        (
+            Some(
+                Charging,
+            ),
+            Some(
+                Percentage(
+                    4.765378069535618,
+                ),
+            ),
+            Some(
+                Energy(
+                    1.96,
+                ),
+            ),
+            None,
+            Some(
+                Critical,
+            ),
+            Some(
+                Calculating,
+            ),
+            None,
+        )
+        "###
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn not_present() -> anyhow::Result<()> {
+        let batt_info = decode("present: no\n", "present: no\n")?;
+
+        insta::assert_debug_snapshot!((batt_info.device_type, batt_info.percentage), @r###"
+        (
+            Some(
+                Battery,
+            ),
+            None,
+        )
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn devices() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        for (name, info, state) in [("BAT0", INFO, STATE), ("BAT1", INFO_MWH, STATE_MWH)] {
+            fs::create_dir(dir.path().join(name))?;
+            fs::write(dir.path().join(name).join("info"), info)?;
+            fs::write(dir.path().join(name).join("state"), state)?;
+        }
+
+        // a battery without its state file
+        fs::create_dir(dir.path().join("BAT2"))?;
+        fs::write(dir.path().join("BAT2").join("info"), INFO)?;
+
+        let acpi = Acpi::with_root(dir.path());
+
+        let listing = acpi.listing()?;
+        let devices = listing
+            .devices
+            .iter()
+            .map(|device| device.to_string())
+            .collect::<Vec<_>>();
+
+        insta::assert_debug_snapshot!(devices, @r###"
+        [
+            "BAT0: 73% discharging, 2h 4m until empty",
+            "BAT1: 5% charging",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(
+            listing
+                .skipped
+                .iter()
+                .map(|skipped| skipped.id.rsplit('/').next().unwrap())
+                .collect::<Vec<_>>(),
+            @r###"
+        [
+            "BAT2",
+        ]
+        "###
+        );
+        insta::assert_debug_snapshot!(acpi.battery_info()?.device_state, @r###"
+        Some(
+            Discharging,
+        )
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn malformed() {
+        let error = decode(INFO, "remaining capacity: lots\n").unwrap_err();
+
+        insta::assert_snapshot!(error.to_string(), @r###"remaining capacity: not a quantity: "lots""###);
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    sync::{
+        Arc,
+        Mutex,
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use super::{
+    upower::UPower,
+    BatterySource,
+    Device,
+    Listing,
+};
+use crate::battery_info::BatteryInfo;
+
+/// A reading and when it was read.
+type Reading = (Instant, Arc<BatteryInfo>);
+
+/// Serves one shared reading of a [`BatterySource`] to many callers, reading the source again
+/// only once the reading is older than the max age or was invalidated.
+///
+/// Callers that ask while the source is being read wait for that read instead of starting
+/// their own. Failed reads aren't cached, the next caller tries again.
+///
+/// Clones share the reading, hand one to every worker thread.
+#[derive(Debug, Clone)]
+pub struct CachedReader<S> {
+    source: S,
+    max_age: Duration,
+    cache: Arc<Mutex<Option<Reading>>>,
+}
+
+impl<S: BatterySource> CachedReader<S> {
+    /// A reader keeping readings for a second.
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            max_age: Duration::from_secs(1),
+            cache: Default::default(),
+        }
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    /// The cached reading, read from the source if there is none or it's too old.
+    pub fn battery_info(&self) -> anyhow::Result<Arc<BatteryInfo>> {
+        // held while reading, so concurrent callers wait for the reading
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some((read_at, batt_info)) = &*cache {
+            if read_at.elapsed() <= self.max_age {
+                return Ok(batt_info.clone());
+            }
+        }
+
+        let batt_info = Arc::new(self.source.battery_info()?);
+        *cache = Some((Instant::now(), batt_info.clone()));
+
+        Ok(batt_info)
+    }
+
+    /// Have the next caller read the source.
+    pub fn invalidate(&self) {
+        *self.cache.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Invalidate the reading on every item of `changes`, e.g. [`UPower::display_device_changes`],
+    /// on a thread of its own.
+    ///
+    /// The thread doesn't keep the reading alive, it ends with the first item after the reader
+    /// and its clones were dropped.
+    pub fn invalidate_on<I>(&self, changes: I)
+    where
+        I: IntoIterator,
+        I::IntoIter: Send + 'static,
+    {
+        let cache = Arc::downgrade(&self.cache);
+        let changes = changes.into_iter();
+
+        thread::spawn(move || {
+            for _ in changes {
+                match cache.upgrade() {
+                    Some(cache) => *cache.lock().unwrap_or_else(|e| e.into_inner()) = None,
+                    None => break,
+                }
+            }
+        });
+    }
+}
+
+impl CachedReader<UPower> {
+    /// Invalidate the reading whenever the display device's properties change, when UPower's
+    /// signals can be subscribed to, relying on the max age otherwise.
+    pub fn watch_display_device(self) -> Self {
+        if let Ok(changes) = self.source.display_device_changes() {
+            self.invalidate_on(changes);
+        }
+
+        self
+    }
+}
+
+/// Reads [`BatterySource::battery_info`] through the cache, devices and listings aren't cached.
+impl<S: BatterySource> BatterySource for CachedReader<S> {
+    fn name(&self) -> &str {
+        self.source.name()
+    }
+
+    fn battery_info(&self) -> anyhow::Result<BatteryInfo> {
+        Ok(CachedReader::battery_info(self)?.as_ref().clone())
+    }
+
+    fn devices(&self) -> anyhow::Result<Vec<Device>> {
+        self.source.devices()
+    }
+
+    fn listing(&self) -> anyhow::Result<Listing> {
+        self.source.listing()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    };
+
+    use zbus::block_on;
+
+    use super::*;
+    use crate::{
+        battery_info::{
+            device_state::DeviceState,
+            device_type::DeviceType,
+            BatteryInfoProperties,
+        },
+        battery_interface::{
+            replay::Timing,
+            simulated::Simulation,
+            Device,
+        },
+        test_utils::{
+            p2p_connections,
+            FakeDevice,
+        },
+    };
+
+    const DISPLAY_DEVICE: &str = "/org/freedesktop/UPower/devices/DisplayDevice";
+
+    /// Counts reads, each taking a while.
+    #[derive(Default)]
+    struct Counting {
+        reads: AtomicUsize,
+    }
+
+    impl BatterySource for Counting {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn battery_info(&self) -> anyhow::Result<BatteryInfo> {
+            let reads = self.reads.fetch_add(1, Ordering::SeqCst) + 1;
+            thread::sleep(Duration::from_millis(50));
+
+            let mut batt_info = BatteryInfo::new();
+            batt_info.set_propertry(BatteryInfoProperties::Percentage((reads as f64).into()));
+
+            Ok(batt_info)
+        }
+
+        fn devices(&self) -> anyhow::Result<Vec<Device>> {
+            Ok(vec![])
+        }
+    }
+
+    #[test]
+    fn coalesce_and_expire() -> anyhow::Result<()> {
+        let reader =
+            CachedReader::new(Counting::default()).with_max_age(Duration::from_millis(200));
+
+        let readings = thread::scope(|scope| {
+            let readers = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        reader
+                            .battery_info()
+                            .map(|info| *info.percentage().unwrap())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            readers
+                .into_iter()
+                .map(|reader| reader.join().unwrap())
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?;
+
+        insta::assert_debug_snapshot!(readings, @r###"
+        [
+            1.0,
+            1.0,
+            1.0,
+            1.0,
+        ]
+        "###);
+        insta::assert_debug_snapshot!(reader.source().reads.load(Ordering::SeqCst), @"1");
+
+        reader.invalidate();
+        reader.battery_info()?;
+        insta::assert_debug_snapshot!(reader.source().reads.load(Ordering::SeqCst), @"2");
+
+        thread::sleep(Duration::from_millis(250));
+        reader.battery_info()?;
+        insta::assert_debug_snapshot!(reader.source().reads.load(Ordering::SeqCst), @"3");
+
+        Ok(())
+    }
+
+    #[test]
+    fn clones_share_reading() -> anyhow::Result<()> {
+        let reader = CachedReader::new(Simulation::new().with_timing(Timing::Stepped)?)
+            .with_max_age(Duration::from_secs(3600));
+
+        let readings = (0..4)
+            .map(|_| {
+                let reader = reader.clone();
+                thread::spawn(move || reader.battery_info())
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|reader| reader.join().unwrap())
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        assert!(readings.iter().all(|reading| *reading == readings[0]));
+
+        let first = BatterySource::battery_info(&reader)?;
+        assert_eq!(first, *readings[0]);
+
+        reader.invalidate();
+        assert_ne!(BatterySource::battery_info(&reader.clone())?, first);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalidated_by_signal() -> anyhow::Result<()> {
+        let (server, client) = p2p_connections(|builder| {
+            builder.serve_at(
+                DISPLAY_DEVICE,
+                FakeDevice::new(DeviceType::Battery, DeviceState::Discharging, 40.0),
+            )
+        })?;
+
+        let reader = CachedReader::new(UPower::with_connection(&client)?)
+            .with_max_age(Duration::from_secs(3600))
+            .watch_display_device();
+
+        insta::assert_debug_snapshot!(reader.battery_info()?.percentage(), @r###"
+        Some(
+            Percentage(
+                40.0,
+            ),
+        )
+        "###);
+
+        let iface = server
+            .object_server()
+            .interface::<_, FakeDevice>(DISPLAY_DEVICE)?;
+        iface.get_mut().percentage = 39.0;
+        block_on(iface.get().percentage_changed(iface.signal_context()))?;
+
+        // the signal is handled on another thread
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while reader.battery_info()?.percentage() != Some(39.0.into()) {
+            assert!(
+                Instant::now() < deadline,
+                "the reading was never invalidated"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        Ok(())
+    }
+}
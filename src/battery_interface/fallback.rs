@@ -0,0 +1,331 @@
+use std::{
+    fmt,
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+
+use super::{
+    acpi::Acpi,
+    sysfs::Sysfs,
+    upower::UPower,
+    BatteryInterface,
+    BatterySource,
+    Device,
+    Listing,
+};
+use crate::{
+    aggregate::Aggregator,
+    battery_info::BatteryInfo,
+};
+
+/// Why a source in a [`Fallback`] chain didn't answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFailure {
+    pub source: String,
+    pub error: String,
+}
+
+impl fmt::Display for SourceFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.source, self.error)
+    }
+}
+
+/// A reading from a [`Fallback`] chain, with which source answered and why the ones before it
+/// didn't.
+#[derive(Debug, Clone)]
+pub struct Answered<T> {
+    pub value: T,
+    pub source: String,
+    pub failures: Vec<SourceFailure>,
+}
+
+/// Tries its sources in order and answers with the first one that succeeds, so the same call
+/// works on a desktop running UPower and on a minimal system without it.
+///
+/// Clones share their sources.
+#[derive(Clone)]
+pub struct Fallback {
+    sources: Vec<Arc<dyn BatterySource>>,
+}
+
+impl Default for Fallback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fallback {
+    /// A chain without sources, which never answers.
+    pub fn new() -> Self {
+        Self { sources: vec![] }
+    }
+
+    /// UPower, then sysfs, then ACPI, the latter two combining batteries with `aggregator`.
+    ///
+    /// UPower keeps its connection for the lifetime of the process, if connecting fails here
+    /// the chain skips it with that error from then on.
+    pub fn standard(aggregator: &Aggregator) -> Self {
+        let upower: Arc<dyn BatterySource> = match UPower::new() {
+            Ok(upower) => Arc::new(upower.clone()),
+            Err(e) => Arc::new(Unavailable {
+                name: "upower",
+                error: format!("{:#}", e),
+            }),
+        };
+
+        Self {
+            sources: vec![
+                upower,
+                Arc::new(Sysfs::new().with_aggregator(aggregator.clone())),
+                Arc::new(Acpi::new().with_aggregator(aggregator.clone())),
+            ],
+        }
+    }
+
+    /// Try `source` after the sources already added.
+    pub fn with_source(mut self, source: impl BatterySource + 'static) -> Self {
+        self.sources.push(Arc::new(source));
+        self
+    }
+
+    /// Names of the sources, in the order they are tried.
+    pub fn source_names(&self) -> Vec<&str> {
+        self.sources.iter().map(|source| source.name()).collect()
+    }
+
+    pub fn battery_info(&self) -> anyhow::Result<Answered<BatteryInfo>> {
+        self.first(|source| source.battery_info())
+    }
+
+    /// The devices of the first source that has any, see [`Fallback::listing`].
+    pub fn devices(&self) -> anyhow::Result<Answered<Vec<Device>>> {
+        let answered = self.listing()?;
+
+        Ok(Answered {
+            value: answered.value.devices,
+            source: answered.source,
+            failures: answered.failures,
+        })
+    }
+
+    /// The listing of the first source that has any devices, an empty power supply class
+    /// doesn't stop the chain from reaching ACPI.
+    pub fn listing(&self) -> anyhow::Result<Answered<Listing>> {
+        self.first(|source| match source.listing()? {
+            listing if listing.devices.is_empty() => Err(anyhow!("no devices")),
+            listing => Ok(listing),
+        })
+    }
+
+    fn first<T>(
+        &self,
+        read: impl Fn(&dyn BatterySource) -> anyhow::Result<T>,
+    ) -> anyhow::Result<Answered<T>> {
+        let mut failures = Vec::new();
+
+        for source in &self.sources {
+            match read(source.as_ref()) {
+                Ok(value) => {
+                    return Ok(Answered {
+                        value,
+                        source: source.name().to_owned(),
+                        failures,
+                    })
+                }
+                Err(e) => failures.push(SourceFailure {
+                    source: source.name().to_owned(),
+                    error: format!("{:#}", e),
+                }),
+            }
+        }
+
+        let reasons = failures
+            .iter()
+            .map(|failure| failure.to_string())
+            .collect::<Vec<_>>();
+
+        Err(anyhow!("no backend answered: {}", reasons.join("; ")))
+    }
+}
+
+impl BatterySource for Fallback {
+    fn name(&self) -> &str {
+        "fallback"
+    }
+
+    fn battery_info(&self) -> anyhow::Result<BatteryInfo> {
+        Ok(Fallback::battery_info(self)?.value)
+    }
+
+    fn devices(&self) -> anyhow::Result<Vec<Device>> {
+        Ok(Fallback::devices(self)?.value)
+    }
+
+    fn listing(&self) -> anyhow::Result<Listing> {
+        Ok(Fallback::listing(self)?.value)
+    }
+}
+
+impl BatteryInterface for Fallback {
+    fn battery_info(
+    ) -> std::result::Result<BatteryInfo, impl Into<Box<dyn std::error::Error + 'static>>> {
+        <Fallback as BatterySource>::battery_info(&Fallback::standard(&Aggregator::default()))
+    }
+
+    fn devices() -> std::result::Result<Vec<Device>, impl Into<Box<dyn std::error::Error + 'static>>>
+    {
+        <Fallback as BatterySource>::devices(&Fallback::standard(&Aggregator::default()))
+    }
+}
+
+/// A source that couldn't be set up, failing every read with the reason.
+struct Unavailable {
+    name: &'static str,
+    error: String,
+}
+
+impl BatterySource for Unavailable {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn battery_info(&self) -> anyhow::Result<BatteryInfo> {
+        Err(anyhow!("{}", self.error))
+    }
+
+    fn devices(&self) -> anyhow::Result<Vec<Device>> {
+        Err(anyhow!("{}", self.error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    const INFO: &str = "\
+present:                 yes
+last full capacity:      41130 mWh
+design voltage:          10800 mV
+";
+
+    const STATE: &str = "\
+present:                 yes
+charging state:          charged
+remaining capacity:      41130 mWh
+";
+
+    #[test]
+    fn falls_back() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("BAT0"))?;
+        fs::write(dir.path().join("BAT0").join("info"), INFO)?;
+        fs::write(dir.path().join("BAT0").join("state"), STATE)?;
+
+        let chain = Fallback::new()
+            .with_source(Unavailable {
+                name: "upower",
+                error: "org.freedesktop.DBus.Error.ServiceUnknown".to_owned(),
+            })
+            .with_source(Sysfs::with_root(dir.path().join("power_supply")))
+            .with_source(Acpi::with_root(dir.path()));
+
+        let answered = chain.battery_info()?;
+        let tmp = dir.path().to_string_lossy();
+        let failures = answered
+            .failures
+            .iter()
+            .map(|failure| failure.to_string().replace(&*tmp, "<tmp>"))
+            .collect::<Vec<_>>();
+
+        insta::assert_debug_snapshot!(
+            (
+                answered.source,
+                answered.value.device_state,
+                answered.value.percentage,
+                failures,
+            ),
+            @r###"
+        (
+            "acpi",
+            Some(
+                FullyCharged,
+            ),
+            Some(
+                Percentage(
+                    100.0,
+                ),
+            ),
+            [
+                "upower: org.freedesktop.DBus.Error.ServiceUnknown",
+                "sysfs: reading <tmp>/power_supply: No such file or directory (os error 2)",
+            ],
+        )
+        "###
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn empty_power_supply_class() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("power_supply"))?;
+        fs::create_dir_all(dir.path().join("acpi").join("BAT0"))?;
+        fs::write(dir.path().join("acpi").join("BAT0").join("info"), INFO)?;
+        fs::write(dir.path().join("acpi").join("BAT0").join("state"), STATE)?;
+        // skipped by ACPI, which still answers with BAT0
+        fs::create_dir(dir.path().join("acpi").join("BAT1"))?;
+
+        let chain = Fallback::new()
+            .with_source(Sysfs::with_root(dir.path().join("power_supply")))
+            .with_source(Acpi::with_root(dir.path().join("acpi")));
+
+        let listing = chain.listing()?;
+        let batt_info = chain.battery_info()?;
+
+        insta::assert_debug_snapshot!(
+            (
+                listing.source,
+                listing.value.devices.len(),
+                listing.value.skipped.len(),
+                listing.failures,
+                batt_info.source,
+            ),
+            @r###"
+        (
+            "acpi",
+            1,
+            1,
+            [
+                SourceFailure {
+                    source: "sysfs",
+                    error: "no devices",
+                },
+            ],
+            "acpi",
+        )
+        "###
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn nothing_answers() {
+        let chain = Fallback::new()
+            .with_source(Sysfs::with_root("/nonexistent/power_supply"))
+            .with_source(Acpi::with_root("/nonexistent/acpi/battery"));
+
+        insta::assert_debug_snapshot!(chain.source_names(), @r###"
+        [
+            "sysfs",
+            "acpi",
+        ]
+        "###);
+        insta::assert_snapshot!(chain.devices().unwrap_err().to_string(), @"no backend answered: sysfs: reading /nonexistent/power_supply: No such file or directory (os error 2); acpi: reading /nonexistent/acpi/battery: No such file or directory (os error 2)");
+    }
+}
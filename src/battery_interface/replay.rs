@@ -0,0 +1,639 @@
+use std::{
+    fs::{
+        self,
+        File,
+    },
+    io::Write,
+    path::Path,
+    sync::{
+        atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+        Arc,
+        OnceLock,
+    },
+    thread::sleep,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+use anyhow::{
+    anyhow,
+    bail,
+};
+
+use super::{
+    BatterySource,
+    Device,
+};
+use crate::{
+    battery_info::{
+        battery_level::BatteryLevel,
+        device_state::DeviceState,
+        device_type::DeviceType,
+        time_until::TimeUntil,
+        warning_level::WarningLevel,
+        BatteryInfo,
+        BatteryInfoProperties,
+    },
+    history::{
+        time_until_from_field,
+        time_until_to_field,
+    },
+};
+
+const HEADER: &str = "timestamp_ms,type,state,percentage,power_supply,battery_level,icon_name,\
+                      time_until,warning_level,energy,energy_full,energy_rate,voltage,\
+                      temperature,charge_cycles,model,native_path";
+
+const FIELDS: usize = 17;
+
+/// Every property of a [`BatteryInfo`], together with when it was read.
+///
+/// Unlike a [`history::Snapshot`](crate::history::Snapshot) nothing is left out, replaying a
+/// frame gives back the reading it was recorded from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub timestamp: SystemTime,
+    pub device_type: Option<DeviceType>,
+    pub device_state: Option<DeviceState>,
+    pub percentage: Option<f64>,
+    pub power_supply: Option<bool>,
+    pub battery_level: Option<BatteryLevel>,
+    pub icon_name: Option<String>,
+    pub time_until: Option<TimeUntil>,
+    pub warning_level: Option<WarningLevel>,
+    /// Wh
+    pub energy: Option<f64>,
+    /// Wh
+    pub energy_full: Option<f64>,
+    /// W
+    pub energy_rate: Option<f64>,
+    /// V
+    pub voltage: Option<f64>,
+    /// °C
+    pub temperature: Option<f64>,
+    pub charge_cycles: Option<i32>,
+    pub model: Option<String>,
+    pub native_path: Option<String>,
+}
+
+impl Frame {
+    pub fn new(timestamp: SystemTime, batt_info: &BatteryInfo) -> Self {
+        Self {
+            timestamp,
+            device_type: batt_info.device_type,
+            device_state: batt_info.device_state,
+            percentage: batt_info.percentage.map(|p| *p),
+            power_supply: batt_info.power_supply.map(|p| *p),
+            battery_level: batt_info.battery_level,
+            icon_name: batt_info.icon_name.as_ref().map(|i| i.as_str().to_owned()),
+            time_until: batt_info.time_until,
+            warning_level: batt_info.warning_level,
+            energy: batt_info.energy.map(|e| *e),
+            energy_full: batt_info.energy_full.map(|e| *e),
+            energy_rate: batt_info.energy_rate.map(|e| *e),
+            voltage: batt_info.voltage.map(|v| *v),
+            temperature: batt_info.temperature.map(|t| *t),
+            charge_cycles: batt_info.charge_cycles.map(|c| *c),
+            model: batt_info.model.as_ref().map(|m| m.as_str().to_owned()),
+            native_path: batt_info
+                .native_path
+                .as_ref()
+                .map(|n| n.as_str().to_owned()),
+        }
+    }
+
+    /// Rebuild the [`BatteryInfo`] this frame was recorded from.
+    pub fn battery_info(&self) -> BatteryInfo {
+        let mut batt_info = BatteryInfo::new();
+
+        [
+            self.device_type.map(BatteryInfoProperties::DeviceType),
+            self.device_state.map(BatteryInfoProperties::DeviceState),
+            self.percentage
+                .map(|p| BatteryInfoProperties::Percentage(p.into())),
+            self.power_supply
+                .map(|p| BatteryInfoProperties::PowerSupply(p.into())),
+            self.battery_level.map(BatteryInfoProperties::BatteryLevel),
+            self.icon_name
+                .clone()
+                .map(|i| BatteryInfoProperties::IconName(i.into())),
+            self.time_until.map(BatteryInfoProperties::TimeUntil),
+            self.warning_level.map(BatteryInfoProperties::WarningLevel),
+            self.energy.map(|e| BatteryInfoProperties::Energy(e.into())),
+            self.energy_full
+                .map(|e| BatteryInfoProperties::EnergyFull(e.into())),
+            self.energy_rate
+                .map(|e| BatteryInfoProperties::EnergyRate(e.into())),
+            self.voltage
+                .map(|v| BatteryInfoProperties::Voltage(v.into())),
+            self.temperature
+                .map(|t| BatteryInfoProperties::Temperature(t.into())),
+            self.charge_cycles
+                .map(|c| BatteryInfoProperties::ChargeCycles(c.into())),
+            self.model
+                .clone()
+                .map(|m| BatteryInfoProperties::Model(m.into())),
+            self.native_path
+                .clone()
+                .map(|n| BatteryInfoProperties::NativePath(n.into())),
+        ]
+        .into_iter()
+        .flatten()
+        .for_each(|prop| batt_info.set_propertry(prop));
+
+        batt_info
+    }
+
+    pub fn to_csv(&self) -> String {
+        let opt = |v: Option<String>| v.unwrap_or_default();
+
+        let timestamp = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        [
+            timestamp.to_string(),
+            opt(self.device_type.map(|t| t.to_string())),
+            opt(self.device_state.map(|s| s.to_string())),
+            opt(self.percentage.map(|p| p.to_string())),
+            opt(self.power_supply.map(|p| p.to_string())),
+            opt(self.battery_level.map(|b| b.to_string())),
+            opt(self.icon_name.as_deref().map(escape)),
+            opt(self.time_until.map(time_until_to_field)),
+            opt(self.warning_level.map(|w| w.to_string())),
+            opt(self.energy.map(|e| e.to_string())),
+            opt(self.energy_full.map(|e| e.to_string())),
+            opt(self.energy_rate.map(|e| e.to_string())),
+            opt(self.voltage.map(|v| v.to_string())),
+            opt(self.temperature.map(|t| t.to_string())),
+            opt(self.charge_cycles.map(|c| c.to_string())),
+            opt(self.model.as_deref().map(escape)),
+            opt(self.native_path.as_deref().map(escape)),
+        ]
+        .join(",")
+    }
+
+    pub fn from_csv(line: &str) -> anyhow::Result<Self> {
+        let fields = line.trim_end().split(',').collect::<Vec<_>>();
+
+        if fields.len() != FIELDS {
+            bail!(
+                "expected {} fields, got {}: {:?}",
+                FIELDS,
+                fields.len(),
+                line
+            );
+        }
+
+        fn opt<T>(
+            field: &str,
+            parse: impl FnOnce(&str) -> anyhow::Result<T>,
+        ) -> anyhow::Result<Option<T>> {
+            match field {
+                "" => Ok(None),
+                field => parse(field).map(Some),
+            }
+        }
+
+        let mut fields = fields.into_iter();
+        let mut next = || fields.next().unwrap_or_default();
+
+        Ok(Self {
+            timestamp: UNIX_EPOCH + Duration::from_millis(next().parse()?),
+            device_type: opt(next(), |s| s.parse())?,
+            device_state: opt(next(), |s| s.parse())?,
+            percentage: opt(next(), |s| Ok(s.parse()?))?,
+            power_supply: opt(next(), |s| Ok(s.parse()?))?,
+            battery_level: opt(next(), |s| s.parse())?,
+            icon_name: opt(next(), |s| Ok(unescape(s)))?,
+            time_until: opt(next(), time_until_from_field)?,
+            warning_level: opt(next(), |s| s.parse())?,
+            energy: opt(next(), |s| Ok(s.parse()?))?,
+            energy_full: opt(next(), |s| Ok(s.parse()?))?,
+            energy_rate: opt(next(), |s| Ok(s.parse()?))?,
+            voltage: opt(next(), |s| Ok(s.parse()?))?,
+            temperature: opt(next(), |s| Ok(s.parse()?))?,
+            charge_cycles: opt(next(), |s| Ok(s.parse()?))?,
+            model: opt(next(), |s| Ok(unescape(s)))?,
+            native_path: opt(next(), |s| Ok(unescape(s)))?,
+        })
+    }
+}
+
+/// Keeps commas and line breaks in free text from splitting the record.
+fn escape(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace(',', "%2C")
+        .replace('\n', "%0A")
+}
+
+fn unescape(field: &str) -> String {
+    field
+        .replace("%0A", "\n")
+        .replace("%2C", ",")
+        .replace("%25", "%")
+}
+
+/// Writes readings as CSV, to be played back by a [`Replay`].
+#[derive(Debug)]
+pub struct Recorder {
+    file: File,
+}
+
+impl Recorder {
+    /// Start a new recording at `path`, replacing any file there.
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", HEADER)?;
+
+        Ok(Self { file })
+    }
+
+    pub fn append(&mut self, frame: &Frame) -> anyhow::Result<()> {
+        writeln!(self.file, "{}", frame.to_csv())?;
+        self.file.flush()?;
+
+        Ok(())
+    }
+
+    /// Record `batt_info` as read right now.
+    pub fn record(&mut self, batt_info: &BatteryInfo) -> anyhow::Result<()> {
+        self.append(&Frame::new(SystemTime::now(), batt_info))
+    }
+
+    /// Record a frame every `interval` until writing fails, failed reads are skipped.
+    pub fn poll(
+        &mut self,
+        interval: Duration,
+        mut read: impl FnMut() -> anyhow::Result<BatteryInfo>,
+    ) -> anyhow::Result<()> {
+        loop {
+            if let Ok(batt_info) = read() {
+                self.record(&batt_info)?;
+            }
+
+            sleep(interval);
+        }
+    }
+}
+
+/// How a [`Replay`] moves through its frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timing {
+    /// Frames come as far apart as they were recorded
+    Original,
+    /// The recording plays this many times faster
+    Accelerated(f64),
+    /// Every read moves on to the next frame, without waiting
+    Stepped,
+}
+
+impl Timing {
+    /// Fails for a speed that isn't a positive, finite number.
+    pub(crate) fn check(self) -> anyhow::Result<Self> {
+        if let Timing::Accelerated(speed) = self {
+            if !(speed.is_finite() && speed > 0.0) {
+                bail!("can't play at {} times the original speed", speed);
+            }
+        }
+
+        Ok(self)
+    }
+}
+
+/// Plays back what a [`Recorder`] recorded as a [`BatterySource`].
+///
+/// With [`Timing::Original`] and [`Timing::Accelerated`] the clock starts at the first read,
+/// which gets the first frame, later reads get the frame that was current that long into the
+/// recording. Once the recording runs out every read gets the last frame.
+///
+/// Clones play the same recording from the same position, reads through any of them move it on.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    frames: Arc<[Frame]>,
+    timing: Timing,
+    started: Arc<OnceLock<Instant>>,
+    step: Arc<AtomicUsize>,
+}
+
+impl Replay {
+    pub fn new(mut frames: Vec<Frame>, timing: Timing) -> anyhow::Result<Self> {
+        if frames.is_empty() {
+            bail!("nothing to replay, the recording has no frames");
+        }
+
+        timing.check()?;
+
+        frames.sort_by_key(|frame| frame.timestamp);
+
+        Ok(Self {
+            frames: frames.into(),
+            timing,
+            started: Default::default(),
+            step: Default::default(),
+        })
+    }
+
+    /// Replay the recording at `path`.
+    pub fn open(path: impl AsRef<Path>, timing: Timing) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+
+        let frames = fs::read_to_string(path)
+            .map_err(|e| anyhow!("reading {}: {}", path.display(), e))?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty() && *line != HEADER)
+            .map(|(number, line)| {
+                Frame::from_csv(line)
+                    .map_err(|e| anyhow!("{}:{}: {}", path.display(), number + 1, e))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Self::new(frames, timing)
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// How long the recording took.
+    pub fn duration(&self) -> Duration {
+        self.recorded_since_start(self.frames.len() - 1)
+    }
+
+    /// The frame a read gets right now.
+    pub fn current(&self) -> &Frame {
+        let index = match self.timing {
+            Timing::Stepped => self.step.fetch_add(1, Ordering::Relaxed),
+            Timing::Original | Timing::Accelerated(_) => {
+                let started = *self.started.get_or_init(Instant::now);
+
+                // past what a timestamp can hold is past the end of the recording
+                match self.frames[0]
+                    .timestamp
+                    .checked_add(self.recorded(started.elapsed()))
+                {
+                    Some(target) => {
+                        self.frames
+                            .partition_point(|frame| frame.timestamp <= target)
+                            - 1
+                    }
+                    None => self.frames.len() - 1,
+                }
+            }
+        };
+
+        &self.frames[index.min(self.frames.len() - 1)]
+    }
+
+    /// Whether reads have reached the last frame.
+    pub fn finished(&self) -> bool {
+        match self.timing {
+            Timing::Stepped => self.step.load(Ordering::Relaxed) >= self.frames.len(),
+            Timing::Original | Timing::Accelerated(_) => self
+                .started
+                .get()
+                .is_some_and(|started| self.recorded(started.elapsed()) >= self.duration()),
+        }
+    }
+
+    /// Every frame in order, sleeping in between as the timing asks for, independent of reads.
+    pub fn play(&self) -> impl Iterator<Item = BatteryInfo> + '_ {
+        self.frames.iter().enumerate().map(|(index, frame)| {
+            if index > 0 {
+                let gap = frame
+                    .timestamp
+                    .duration_since(self.frames[index - 1].timestamp)
+                    .unwrap_or_default();
+
+                sleep(self.real(gap));
+            }
+
+            frame.battery_info()
+        })
+    }
+
+    fn recorded_since_start(&self, index: usize) -> Duration {
+        self.frames[index]
+            .timestamp
+            .duration_since(self.frames[0].timestamp)
+            .unwrap_or_default()
+    }
+
+    /// How much of the recording plays in `real` time, saturating at [`Duration::MAX`].
+    fn recorded(&self, real: Duration) -> Duration {
+        match self.timing {
+            Timing::Original => real,
+            Timing::Accelerated(speed) => {
+                Duration::try_from_secs_f64(real.as_secs_f64() * speed).unwrap_or(Duration::MAX)
+            }
+            Timing::Stepped => Duration::ZERO,
+        }
+    }
+
+    /// How long `recorded` time takes to play, saturating at [`Duration::MAX`].
+    fn real(&self, recorded: Duration) -> Duration {
+        match self.timing {
+            Timing::Original => recorded,
+            Timing::Accelerated(speed) => {
+                Duration::try_from_secs_f64(recorded.as_secs_f64() / speed).unwrap_or(Duration::MAX)
+            }
+            Timing::Stepped => Duration::ZERO,
+        }
+    }
+}
+
+impl BatterySource for Replay {
+    fn name(&self) -> &str {
+        "replay"
+    }
+
+    fn battery_info(&self) -> anyhow::Result<BatteryInfo> {
+        Ok(self.current().battery_info())
+    }
+
+    /// The recorded reading as the only device, `display`.
+    fn devices(&self) -> anyhow::Result<Vec<Device>> {
+        Ok(vec![Device {
+            id: "display".to_owned(),
+            info: self.current().battery_info(),
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::batt_info;
+
+    fn frame(secs: u64, percentage: f64) -> Frame {
+        let batt_info = batt_info([
+            BatteryInfoProperties::DeviceType(DeviceType::Battery),
+            BatteryInfoProperties::DeviceState(DeviceState::Discharging),
+            BatteryInfoProperties::Percentage(percentage.into()),
+            BatteryInfoProperties::PowerSupply(true.into()),
+            BatteryInfoProperties::IconName("battery-good-symbolic".to_owned().into()),
+            BatteryInfoProperties::TimeUntil(TimeUntil::Empty(Duration::from_secs(7500))),
+            BatteryInfoProperties::Voltage(11.9.into()),
+            BatteryInfoProperties::ChargeCycles(212.into()),
+            BatteryInfoProperties::Model("5B10W13975, 45Wh%".to_owned().into()),
+        ]);
+
+        Frame::new(UNIX_EPOCH + Duration::from_secs(secs), &batt_info)
+    }
+
+    fn percentages(replay: &Replay, reads: usize) -> anyhow::Result<Vec<f64>> {
+        (0..reads)
+            .map(|_| {
+                Ok(replay
+                    .battery_info()?
+                    .percentage
+                    .map(|p| *p)
+                    .unwrap_or_default())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn csv_round_trip() -> anyhow::Result<()> {
+        let frame = frame(1_700_000_000, 81.5);
+
+        insta::assert_snapshot!(frame.to_csv(), @"1700000000000,battery,discharging,81.5,true,,battery-good-symbolic,empty:7500,,,,,11.9,,212,5B10W13975%2C 45Wh%25,");
+
+        assert_eq!(Frame::from_csv(&frame.to_csv())?, frame);
+        assert_eq!(Frame::new(frame.timestamp, &frame.battery_info()), frame);
+
+        insta::assert_debug_snapshot!(Frame::from_csv("1,battery,charging").is_err(), @"true");
+
+        Ok(())
+    }
+
+    #[test]
+    fn record_and_step() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("session.csv");
+
+        let mut recorder = Recorder::create(&path)?;
+        for (n, percentage) in [80.0, 79.0, 78.0].into_iter().enumerate() {
+            recorder.append(&frame(n as u64 * 60, percentage))?;
+        }
+
+        let replay = Replay::open(&path, Timing::Stepped)?;
+
+        insta::assert_debug_snapshot!(replay.duration(), @"120s");
+        insta::assert_debug_snapshot!(percentages(&replay, 2)?, @r###"
+        [
+            80.0,
+            79.0,
+        ]
+        "###);
+        insta::assert_debug_snapshot!(replay.finished(), @"false");
+        insta::assert_debug_snapshot!(percentages(&replay, 2)?, @r###"
+        [
+            78.0,
+            78.0,
+        ]
+        "###);
+        insta::assert_debug_snapshot!(replay.finished(), @"true");
+
+        let played = Replay::open(&path, Timing::Stepped)?
+            .play()
+            .map(|batt_info| batt_info.percentage.map(|p| *p))
+            .collect::<Vec<_>>();
+        insta::assert_debug_snapshot!(played, @r###"
+        [
+            Some(
+                80.0,
+            ),
+            Some(
+                79.0,
+            ),
+            Some(
+                78.0,
+            ),
+        ]
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn accelerated() -> anyhow::Result<()> {
+        // a second of playing covers 10000s of the recording
+        let replay = Replay::new(
+            vec![frame(0, 80.0), frame(1_000, 79.0), frame(100_000, 50.0)],
+            Timing::Accelerated(10_000.0),
+        )?;
+
+        insta::assert_debug_snapshot!(percentages(&replay, 1)?, @r###"
+        [
+            80.0,
+        ]
+        "###);
+
+        sleep(Duration::from_millis(150));
+
+        insta::assert_debug_snapshot!(percentages(&replay, 1)?, @r###"
+        [
+            79.0,
+        ]
+        "###);
+        insta::assert_debug_snapshot!(replay.finished(), @"false");
+
+        Ok(())
+    }
+
+    #[test]
+    fn extreme_speed() -> anyhow::Result<()> {
+        // any real time at all covers more than a timestamp can hold
+        let replay = Replay::new(
+            vec![frame(0, 80.0), frame(1_000, 79.0)],
+            Timing::Accelerated(1e30),
+        )?;
+
+        percentages(&replay, 1)?;
+        sleep(Duration::from_millis(10));
+
+        insta::assert_debug_snapshot!(percentages(&replay, 1)?, @r###"
+        [
+            79.0,
+        ]
+        "###);
+        insta::assert_debug_snapshot!(replay.finished(), @"true");
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("session.csv");
+        fs::write(
+            &path,
+            format!("{}\n{}\n1,battery\n", HEADER, frame(0, 80.0).to_csv()),
+        )?;
+
+        let error = Replay::open(&path, Timing::Original)
+            .unwrap_err()
+            .to_string()
+            .replace(&*dir.path().to_string_lossy(), "<tmp>");
+        insta::assert_snapshot!(error, @r###"<tmp>/session.csv:3: expected 17 fields, got 2: "1,battery""###);
+
+        insta::assert_snapshot!(Replay::new(vec![], Timing::Original).unwrap_err(), @"nothing to replay, the recording has no frames");
+        insta::assert_snapshot!(
+            Replay::new(vec![frame(0, 80.0)], Timing::Accelerated(0.0)).unwrap_err(),
+            @"can't play at 0 times the original speed"
+        );
+
+        Ok(())
+    }
+}
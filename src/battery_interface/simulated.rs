@@ -0,0 +1,578 @@
+use std::{
+    sync::{
+        atomic::{
+            AtomicU32,
+            Ordering,
+        },
+        Arc,
+        Mutex,
+        OnceLock,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use once_cell::sync::Lazy;
+
+use super::{
+    replay::Timing,
+    sysfs::time_until,
+    BatteryInterface,
+    BatterySource,
+    Device,
+};
+use crate::battery_info::{
+    battery_level::BatteryLevel,
+    device_state::DeviceState,
+    device_type::DeviceType,
+    warning_level::WarningLevel,
+    BatteryInfo,
+    BatteryInfoProperties,
+    IconName,
+};
+
+/// The simulation moves on in steps of a second.
+const TICK: Duration = Duration::from_secs(1);
+
+/// The simulation ends after a year, later times read the battery as it was then.
+const END: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Percentages at which UPower warns by default.
+const LOW: f64 = 20.0;
+const CRITICAL: f64 = 5.0;
+const ACTION: f64 = 2.0;
+
+/// Power drawn from the battery over time, repeating once it runs out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadProfile {
+    phases: Vec<(Duration, f64)>,
+}
+
+impl LoadProfile {
+    /// Always `watts`.
+    pub fn constant(watts: f64) -> Self {
+        Self {
+            phases: vec![(Duration::ZERO, watts)],
+        }
+    }
+
+    /// Each phase draws its watts for its duration, then the next phase starts, and after the
+    /// last one the first again.
+    pub fn cycle(phases: impl IntoIterator<Item = (Duration, f64)>) -> Self {
+        Self {
+            phases: phases.into_iter().collect(),
+        }
+    }
+
+    /// W drawn `elapsed` into the simulation.
+    pub fn watts_at(&self, elapsed: Duration) -> f64 {
+        let period = self.phases.iter().map(|(d, _)| *d).sum::<Duration>();
+
+        if period.is_zero() {
+            return self.phases.first().map_or(0.0, |(_, watts)| *watts);
+        }
+
+        let mut into = Duration::from_nanos((elapsed.as_nanos() % period.as_nanos()) as u64);
+
+        for (duration, watts) in &self.phases {
+            if into < *duration {
+                return *watts;
+            }
+
+            into -= *duration;
+        }
+
+        0.0
+    }
+}
+
+/// How far the simulation got, the energy left after `tick` seconds.
+#[derive(Debug, Clone, Copy)]
+struct State {
+    tick: u64,
+    energy: f64,
+}
+
+/// A made up battery, discharging under a load and charging while the charger is plugged in.
+///
+/// Readings only depend on how much simulated time passed, [`Simulation::at`] gives the same
+/// reading for the same time on every run. As a [`BatterySource`] simulated time passes with
+/// the [`Timing`]: in real time, faster, or by [`Simulation::with_step`] on every read.
+///
+/// The battery charges at a constant rate up to 80% and slower after that. Warning levels are
+/// UPower's defaults, low at 20%, critical at 5% and action at 2%.
+///
+/// Clones share the simulated clock and read the same battery, until the settings of one of
+/// them are changed, which makes it a different battery on the same clock.
+#[derive(Debug)]
+pub struct Simulation {
+    /// Wh
+    capacity: f64,
+    start_percentage: f64,
+    load: LoadProfile,
+    /// When the charger was plugged in or out, in order
+    charger: Vec<(Duration, bool)>,
+    /// W
+    charge_rate: f64,
+    /// W
+    noise: f64,
+    seed: u64,
+    timing: Timing,
+    step: Duration,
+    started: Arc<OnceLock<Instant>>,
+    reads: Arc<AtomicU32>,
+    state: Mutex<Option<State>>,
+}
+
+impl Clone for Simulation {
+    /// Shares the clock, see [`Simulation`]. The clone integrates from the start again, as its
+    /// settings may still be changed.
+    fn clone(&self) -> Self {
+        Self {
+            load: self.load.clone(),
+            charger: self.charger.clone(),
+            started: self.started.clone(),
+            reads: self.reads.clone(),
+            state: Mutex::new(None),
+            ..*self
+        }
+    }
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulation {
+    /// A full 50Wh battery under an 8W load, with the charger unplugged and no noise, in real
+    /// time.
+    pub fn new() -> Self {
+        Self {
+            capacity: 50.0,
+            start_percentage: 100.0,
+            load: LoadProfile::constant(8.0),
+            charger: vec![],
+            charge_rate: 30.0,
+            noise: 0.0,
+            seed: 0,
+            timing: Timing::Original,
+            step: Duration::from_secs(60),
+            started: Default::default(),
+            reads: Default::default(),
+            state: Mutex::new(None),
+        }
+    }
+
+    /// How much energy the battery holds when full, in Wh.
+    pub fn with_capacity(mut self, wh: f64) -> Self {
+        self.capacity = wh.max(f64::EPSILON);
+        self
+    }
+
+    pub fn with_start_percentage(mut self, percentage: f64) -> Self {
+        self.start_percentage = percentage.clamp(0.0, 100.0);
+        self
+    }
+
+    pub fn with_load(mut self, load: LoadProfile) -> Self {
+        self.load = load;
+        self
+    }
+
+    /// Plug the charger in `at` into the simulation.
+    pub fn with_plugged_in(self, at: Duration) -> Self {
+        self.with_charger_change(at, true)
+    }
+
+    /// Unplug the charger `at` into the simulation.
+    pub fn with_unplugged(self, at: Duration) -> Self {
+        self.with_charger_change(at, false)
+    }
+
+    /// W going into the battery while it charges.
+    pub fn with_charge_rate(mut self, watts: f64) -> Self {
+        self.charge_rate = watts.max(0.0);
+        self
+    }
+
+    /// Vary the load by up to `watts` either way, differently every second but the same for
+    /// the same `seed`.
+    pub fn with_noise(mut self, watts: f64, seed: u64) -> Self {
+        self.noise = watts.abs();
+        self.seed = seed;
+        self
+    }
+
+    /// Fails for an accelerated timing that isn't a positive, finite speed.
+    pub fn with_timing(mut self, timing: Timing) -> anyhow::Result<Self> {
+        self.timing = timing.check()?;
+        Ok(self)
+    }
+
+    /// Simulated time between reads with [`Timing::Stepped`], a minute by default.
+    pub fn with_step(mut self, step: Duration) -> Self {
+        self.step = step;
+        self
+    }
+
+    fn with_charger_change(mut self, at: Duration, plugged: bool) -> Self {
+        self.charger.push((at, plugged));
+        self.charger.sort_by_key(|(at, _)| *at);
+        self
+    }
+
+    /// The reading `elapsed` into the simulation, at most a year in.
+    pub fn at(&self, elapsed: Duration) -> BatteryInfo {
+        let target = elapsed.min(END).as_secs();
+
+        let mut guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        // going back in time starts over
+        let mut state = guard.filter(|state| state.tick <= target).unwrap_or(State {
+            tick: 0,
+            energy: self.capacity * self.start_percentage / 100.0,
+        });
+
+        while state.tick < target {
+            if self.settled(state) {
+                state.tick = target;
+                break;
+            }
+
+            let (_, power) = self.power(state);
+            state.energy =
+                (state.energy + power * TICK.as_secs_f64() / 3600.0).clamp(0.0, self.capacity);
+            state.tick += 1;
+        }
+
+        *guard = Some(state);
+        drop(guard);
+
+        self.reading(state)
+    }
+
+    /// Simulated time that passed for a read now.
+    pub fn elapsed(&self) -> Duration {
+        match self.timing {
+            Timing::Stepped => self
+                .step
+                .checked_mul(self.reads.fetch_add(1, Ordering::Relaxed))
+                .unwrap_or(Duration::MAX),
+            Timing::Original => self.started.get_or_init(Instant::now).elapsed(),
+            Timing::Accelerated(speed) => {
+                let real = self.started.get_or_init(Instant::now).elapsed();
+                Duration::try_from_secs_f64(real.as_secs_f64() * speed).unwrap_or(Duration::MAX)
+            }
+        }
+    }
+
+    fn plugged_in(&self, elapsed: Duration) -> bool {
+        self.charger
+            .iter()
+            .rev()
+            .find(|(at, _)| *at <= elapsed)
+            .is_some_and(|(_, plugged)| *plugged)
+    }
+
+    /// Whether the battery stays as it is for the rest of the simulation, empty without a charger
+    /// or full with one, and the charger isn't plugged in or out anymore.
+    fn settled(&self, state: State) -> bool {
+        let elapsed = Duration::from_secs(state.tick);
+
+        let stays = match self.plugged_in(elapsed) {
+            true => state.energy >= self.capacity,
+            false => state.energy <= 0.0,
+        };
+
+        stays && self.charger.iter().all(|(at, _)| *at <= elapsed)
+    }
+
+    /// Whether the charger is plugged in, and the W going into the battery, negative while
+    /// discharging.
+    fn power(&self, state: State) -> (bool, f64) {
+        let elapsed = Duration::from_secs(state.tick);
+
+        if self.plugged_in(elapsed) {
+            if state.energy >= self.capacity {
+                return (true, 0.0);
+            }
+
+            let percentage = state.energy / self.capacity * 100.0;
+            let taper = ((100.0 - percentage) / 20.0).clamp(0.1, 1.0);
+
+            return (true, self.charge_rate * taper);
+        }
+
+        if state.energy <= 0.0 {
+            return (false, 0.0);
+        }
+
+        let load = self.load.watts_at(elapsed) + self.noise * noise(self.seed, state.tick);
+
+        (false, -load.max(0.0))
+    }
+
+    fn reading(&self, state: State) -> BatteryInfo {
+        let (plugged, power) = self.power(state);
+        let percentage = state.energy / self.capacity * 100.0;
+
+        let device_state = match (plugged, power) {
+            (true, power) if power > 0.0 => DeviceState::Charging,
+            (true, _) => DeviceState::FullyCharged,
+            (false, _) if state.energy <= 0.0 => DeviceState::Empty,
+            (false, _) => DeviceState::Discharging,
+        };
+
+        let warning_level = match percentage {
+            _ if plugged => WarningLevel::NoWarning,
+            p if p <= ACTION => WarningLevel::Action,
+            p if p <= CRITICAL => WarningLevel::Critical,
+            p if p <= LOW => WarningLevel::Low,
+            _ => WarningLevel::NoWarning,
+        };
+
+        let battery_level = match percentage {
+            p if p <= CRITICAL => BatteryLevel::Critical,
+            p if p <= LOW => BatteryLevel::Low,
+            p if p < 80.0 => BatteryLevel::Normal,
+            p if p < 100.0 => BatteryLevel::High,
+            _ => BatteryLevel::Full,
+        };
+
+        let mut batt_info = BatteryInfo::new();
+
+        [
+            Some(BatteryInfoProperties::DeviceType(DeviceType::Battery)),
+            Some(BatteryInfoProperties::DeviceState(device_state)),
+            Some(BatteryInfoProperties::Percentage(percentage.into())),
+            Some(BatteryInfoProperties::PowerSupply(true.into())),
+            Some(BatteryInfoProperties::BatteryLevel(battery_level)),
+            Some(BatteryInfoProperties::IconName(IconName::synthesize(
+                percentage.into(),
+                device_state,
+            ))),
+            time_until(
+                Some(device_state),
+                Some(state.energy),
+                Some(self.capacity),
+                Some(power.abs()),
+            )
+            .map(BatteryInfoProperties::TimeUntil),
+            Some(BatteryInfoProperties::WarningLevel(warning_level)),
+            Some(BatteryInfoProperties::Energy(state.energy.into())),
+            Some(BatteryInfoProperties::EnergyFull(self.capacity.into())),
+            Some(BatteryInfoProperties::EnergyRate(power.abs().into())),
+            Some(BatteryInfoProperties::Model(
+                "Simulated battery".to_owned().into(),
+            )),
+            Some(BatteryInfoProperties::NativePath("SIM0".to_owned().into())),
+        ]
+        .into_iter()
+        .flatten()
+        .for_each(|prop| batt_info.set_propertry(prop));
+
+        batt_info
+    }
+}
+
+/// Between -1 and 1, the same for the same `seed` and `tick`.
+fn noise(seed: u64, tick: u64) -> f64 {
+    // splitmix64
+    let mut z = seed
+        .wrapping_add(tick.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+        .wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+
+    (z >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+}
+
+impl BatterySource for Simulation {
+    fn name(&self) -> &str {
+        "simulated"
+    }
+
+    fn battery_info(&self) -> anyhow::Result<BatteryInfo> {
+        Ok(self.at(self.elapsed()))
+    }
+
+    /// The simulated battery as the only device, `SIM0`.
+    fn devices(&self) -> anyhow::Result<Vec<Device>> {
+        Ok(vec![Device {
+            id: "SIM0".to_owned(),
+            info: self.at(self.elapsed()),
+        }])
+    }
+}
+
+/// The default simulation shared by the whole process, in real time from the first read.
+static DEFAULT_SIMULATION: Lazy<Simulation> = Lazy::new(Simulation::new);
+
+/// Reads the process wide [`Simulation::new`], which drains as the process runs.
+impl BatteryInterface for Simulation {
+    fn battery_info(
+    ) -> std::result::Result<BatteryInfo, impl Into<Box<dyn std::error::Error + 'static>>> {
+        <Simulation as BatterySource>::battery_info(&DEFAULT_SIMULATION)
+    }
+
+    fn devices() -> std::result::Result<Vec<Device>, impl Into<Box<dyn std::error::Error + 'static>>>
+    {
+        <Simulation as BatterySource>::devices(&DEFAULT_SIMULATION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery_info::time_until::TimeUntil;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn summary(batt_info: &BatteryInfo) -> String {
+        let time_until = match batt_info.time_until {
+            Some(TimeUntil::Empty(d)) => format!("{}m until empty", d.as_secs() / 60),
+            Some(TimeUntil::Full(d)) => format!("{}m until full", d.as_secs() / 60),
+            other => format!("{:?}", other),
+        };
+
+        format!(
+            "{:.1}% {} {:.1}W, {}, {:?} {:?}",
+            batt_info.percentage.map(|p| *p).unwrap_or_default(),
+            batt_info.device_state.unwrap(),
+            batt_info.energy_rate.map(|r| *r).unwrap_or_default(),
+            time_until,
+            batt_info.warning_level.unwrap(),
+            batt_info.battery_level.unwrap(),
+        )
+    }
+
+    #[test]
+    fn discharge_and_charge() -> anyhow::Result<()> {
+        let simulation = Simulation::new()
+            .with_capacity(40.0)
+            .with_start_percentage(90.0)
+            .with_load(LoadProfile::constant(10.0))
+            .with_charge_rate(20.0)
+            .with_plugged_in(HOUR * 7 / 2)
+            .with_timing(Timing::Stepped)?
+            .with_step(HOUR / 2);
+
+        let readings = (0..12)
+            .map(|_| Ok(summary(&simulation.battery_info()?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        insta::assert_debug_snapshot!(readings, @r###"
+        [
+            "90.0% discharging 10.0W, 216m until empty, NoWarning High",
+            "77.5% discharging 10.0W, 185m until empty, NoWarning Normal",
+            "65.0% discharging 10.0W, 156m until empty, NoWarning Normal",
+            "52.5% discharging 10.0W, 126m until empty, NoWarning Normal",
+            "40.0% discharging 10.0W, 96m until empty, NoWarning Normal",
+            "27.5% discharging 10.0W, 66m until empty, NoWarning Normal",
+            "15.0% discharging 10.0W, 36m until empty, Low Low",
+            "2.5% charging 20.0W, 117m until full, NoWarning Critical",
+            "27.5% charging 20.0W, 86m until full, NoWarning Normal",
+            "52.5% charging 20.0W, 56m until full, NoWarning Normal",
+            "77.5% charging 20.0W, 26m until full, NoWarning Normal",
+            "93.5% charging 6.5W, 24m until full, NoWarning High",
+        ]
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_speed() {
+        let errors = [f64::INFINITY, f64::NAN, -2.0].map(|speed| {
+            Simulation::new()
+                .with_timing(Timing::Accelerated(speed))
+                .unwrap_err()
+                .to_string()
+        });
+
+        insta::assert_debug_snapshot!(errors, @r###"
+        [
+            "can't play at inf times the original speed",
+            "can't play at NaN times the original speed",
+            "can't play at -2 times the original speed",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn default_drains() -> anyhow::Result<()> {
+        let simulation = Simulation::new()
+            .with_timing(Timing::Stepped)?
+            .with_step(HOUR);
+
+        let readings = (0..3)
+            .map(|_| Ok(summary(&simulation.battery_info()?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        insta::assert_debug_snapshot!(readings, @r###"
+        [
+            "100.0% discharging 8.0W, 375m until empty, NoWarning Full",
+            "84.0% discharging 8.0W, 314m until empty, NoWarning High",
+            "68.0% discharging 8.0W, 254m until empty, NoWarning Normal",
+        ]
+        "###);
+
+        assert!(<Simulation as BatteryInterface>::battery_info().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ends_after_a_year() {
+        let simulation = Simulation::new().with_plugged_in(HOUR);
+
+        let last = simulation.at(Duration::MAX);
+
+        assert_eq!(last, simulation.at(END + HOUR));
+        insta::assert_debug_snapshot!(summary(&last), @r###""100.0% fully-charged 0.0W, Some(NotApplicable), NoWarning Full""###);
+    }
+
+    #[test]
+    fn load_and_noise() {
+        let load = LoadProfile::cycle([(HOUR, 5.0), (HOUR / 2, 20.0)]);
+
+        insta::assert_debug_snapshot!(
+            [0, 59, 60, 89, 90].map(|minutes| load.watts_at(Duration::from_secs(minutes * 60))),
+            @r###"
+        [
+            5.0,
+            5.0,
+            20.0,
+            20.0,
+            5.0,
+        ]
+        "###
+        );
+
+        let noisy = || Simulation::new().with_load(load.clone()).with_noise(2.0, 7);
+
+        let rates = (0..4)
+            .map(|n| *noisy().at(HOUR / 2 + TICK * n).energy_rate.unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(rates.iter().all(|rate| (3.0..=7.0).contains(rate)));
+        assert!(rates.windows(2).any(|pair| pair[0] != pair[1]));
+
+        // the same seed gives the same battery, no matter in which steps time passes
+        let stepwise = noisy();
+        stepwise.at(HOUR / 3);
+        assert_eq!(
+            stepwise.at(HOUR).energy.map(|e| *e),
+            noisy().at(HOUR).energy.map(|e| *e),
+        );
+        assert_ne!(
+            noisy().with_noise(2.0, 8).at(HOUR).energy.map(|e| *e),
+            noisy().at(HOUR).energy.map(|e| *e),
+        );
+    }
+}
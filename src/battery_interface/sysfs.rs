@@ -0,0 +1,568 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::Duration,
+};
+
+use anyhow::{
+    anyhow,
+    Context,
+};
+
+use super::{
+    BatteryInterface,
+    BatterySource,
+    Device,
+    Listing,
+    SkippedDevice,
+};
+use crate::{
+    aggregate::Aggregator,
+    battery_info::{
+        battery_level::BatteryLevel,
+        device_state::DeviceState,
+        device_type::DeviceType,
+        time_until::TimeUntil,
+        BatteryInfo,
+        BatteryInfoProperties,
+    },
+};
+
+/// Where the kernel lists power supplies.
+pub const POWER_SUPPLY_ROOT: &str = "/sys/class/power_supply";
+
+/// Reads the kernel's power supply class directly, for systems without UPower.
+///
+/// The kernel has no aggregate like UPower's display device, `battery_info` combines the
+/// batteries with an [`Aggregator`] instead.
+#[derive(Debug, Clone)]
+pub struct Sysfs {
+    root: PathBuf,
+    aggregator: Aggregator,
+}
+
+impl Default for Sysfs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sysfs {
+    pub fn new() -> Self {
+        Self::with_root(POWER_SUPPLY_ROOT)
+    }
+
+    /// Read the power supplies under `root` instead, e.g. a copy of the tree in tests.
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            aggregator: Aggregator::default(),
+        }
+    }
+
+    pub fn with_aggregator(mut self, aggregator: Aggregator) -> Self {
+        self.aggregator = aggregator;
+        self
+    }
+
+    /// Every power supply under the root that could be read, see [`Sysfs::listing`].
+    pub fn devices(&self) -> anyhow::Result<Vec<Device>> {
+        Ok(self.listing()?.devices)
+    }
+
+    /// Every power supply under the root, ordered by name. The id is the supply's directory.
+    ///
+    /// A supply that can't be read, e.g. one unplugged while listing, is left out and reported
+    /// as skipped.
+    pub fn listing(&self) -> anyhow::Result<Listing> {
+        let mut paths = fs::read_dir(&self.root)
+            .with_context(|| format!("reading {}", self.root.display()))?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        paths.sort();
+
+        let mut listing = Listing::default();
+
+        for path in paths {
+            let id = path.display().to_string();
+
+            match self.device_battery_info(&path) {
+                Ok(info) => listing.devices.push(Device { info, id }),
+                Err(e) => listing.skipped.push(SkippedDevice {
+                    id,
+                    error: format!("{:#}", e),
+                }),
+            }
+        }
+
+        Ok(listing)
+    }
+
+    /// The power supply in the directory `path`.
+    pub fn device_battery_info(&self, path: &Path) -> anyhow::Result<BatteryInfo> {
+        let uevent = path.join("uevent");
+        let text =
+            fs::read_to_string(&uevent).with_context(|| format!("reading {}", uevent.display()))?;
+
+        let mut batt_info =
+            decode_uevent(&text).with_context(|| format!("decoding {}", uevent.display()))?;
+
+        if let Some(name) = path.file_name() {
+            batt_info.set_propertry(BatteryInfoProperties::NativePath(
+                name.to_string_lossy().into_owned().into(),
+            ));
+        }
+
+        Ok(batt_info)
+    }
+
+    /// The batteries combined by the aggregator.
+    pub fn battery_info(&self) -> anyhow::Result<BatteryInfo> {
+        self.aggregator
+            .aggregate(self.devices()?)
+            .map(|aggregate| aggregate.info)
+            .ok_or_else(|| anyhow!("no battery in {}", self.root.display()))
+    }
+}
+
+/// Decodes the `POWER_SUPPLY_*` lines of a `uevent` file.
+///
+/// The kernel reports micro units, e.g. µWh and µV, which are converted to the units UPower
+/// uses. Charge in µAh is converted to energy with the design voltage when there's no energy.
+fn decode_uevent(text: &str) -> anyhow::Result<BatteryInfo> {
+    let values = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("POWER_SUPPLY_")?.split_once('='))
+        .collect::<HashMap<_, _>>();
+
+    let number = |key: &str| -> anyhow::Result<Option<f64>> {
+        values
+            .get(key)
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|e| anyhow!("{}: error: {:?}, value: {:?}", key, e, value))
+            })
+            .transpose()
+    };
+    let micro =
+        |key: &str| -> anyhow::Result<Option<f64>> { Ok(number(key)?.map(|value| value / 1e6)) };
+
+    let mut batt_info = BatteryInfo::new();
+    let mut set = |prop| batt_info.set_propertry(prop);
+
+    let device_type = match values.get("TYPE").copied() {
+        Some("Battery") => DeviceType::Battery,
+        Some("Mains") => DeviceType::LinePower,
+        Some("UPS") => DeviceType::Ups,
+        _ => DeviceType::Unknown,
+    };
+    set(BatteryInfoProperties::DeviceType(device_type));
+
+    // peripherals, e.g. a wireless mouse, report a scope of "Device"
+    set(BatteryInfoProperties::PowerSupply(
+        (values.get("SCOPE").copied() != Some("Device")).into(),
+    ));
+
+    let state = values.get("STATUS").map(|status| match *status {
+        "Charging" => DeviceState::Charging,
+        "Discharging" => DeviceState::Discharging,
+        "Full" => DeviceState::FullyCharged,
+        "Not charging" => DeviceState::PendingCharge,
+        _ => DeviceState::Unknown,
+    });
+    if let Some(state) = state {
+        set(BatteryInfoProperties::DeviceState(state));
+    }
+
+    let voltage = micro("VOLTAGE_NOW")?;
+    let design_voltage = micro("VOLTAGE_MIN_DESIGN")?.or(voltage);
+
+    let from_charge = |key: &str| -> anyhow::Result<Option<f64>> {
+        Ok(micro(key)?.zip(design_voltage).map(|(ah, v)| ah * v))
+    };
+
+    let energy = micro("ENERGY_NOW")?.map_or_else(|| from_charge("CHARGE_NOW"), |e| Ok(Some(e)))?;
+    let energy_full =
+        micro("ENERGY_FULL")?.map_or_else(|| from_charge("CHARGE_FULL"), |e| Ok(Some(e)))?;
+    // the sign of the current differs between drivers
+    let energy_rate = match micro("POWER_NOW")? {
+        Some(power) => Some(power.abs()),
+        None => micro("CURRENT_NOW")?
+            .zip(voltage)
+            .map(|(a, v)| (a * v).abs()),
+    };
+
+    let percentage = number("CAPACITY")?.or_else(|| {
+        energy
+            .zip(energy_full)
+            .filter(|(_, full)| *full > 0.0)
+            .map(|(now, full)| (now / full * 100.0).min(100.0))
+    });
+
+    if let Some(percentage) = percentage {
+        set(BatteryInfoProperties::Percentage(percentage.into()));
+    }
+    if let Some(energy) = energy {
+        set(BatteryInfoProperties::Energy(energy.into()));
+    }
+    if let Some(energy_full) = energy_full {
+        set(BatteryInfoProperties::EnergyFull(energy_full.into()));
+    }
+    if let Some(energy_rate) = energy_rate {
+        set(BatteryInfoProperties::EnergyRate(energy_rate.into()));
+    }
+    if let Some(voltage) = voltage {
+        set(BatteryInfoProperties::Voltage(voltage.into()));
+    }
+    if let Some(temperature) = number("TEMP")? {
+        set(BatteryInfoProperties::Temperature(
+            (temperature / 10.0).into(),
+        ));
+    }
+    if let Some(cycles) = number("CYCLE_COUNT")? {
+        set(BatteryInfoProperties::ChargeCycles((cycles as i32).into()));
+    }
+    if let Some(model) = values.get("MODEL_NAME") {
+        set(BatteryInfoProperties::Model(model.trim().to_owned().into()));
+    }
+
+    let battery_level = values.get("CAPACITY_LEVEL").map(|level| match *level {
+        "Critical" => BatteryLevel::Critical,
+        "Low" => BatteryLevel::Low,
+        "Normal" => BatteryLevel::Normal,
+        "High" => BatteryLevel::High,
+        "Full" => BatteryLevel::Full,
+        _ => BatteryLevel::Unknown,
+    });
+    if let Some(battery_level) = battery_level {
+        set(BatteryInfoProperties::BatteryLevel(battery_level));
+    }
+
+    if let Some(time_until) = time_until(state, energy, energy_full, energy_rate) {
+        set(BatteryInfoProperties::TimeUntil(time_until));
+    }
+
+    Ok(batt_info)
+}
+
+/// Estimates from the energy and the rate, as the kernel doesn't report any. `None` if there's
+/// nothing to estimate, or the estimate is too far out to be one.
+pub(crate) fn time_until(
+    state: Option<DeviceState>,
+    energy: Option<f64>,
+    energy_full: Option<f64>,
+    energy_rate: Option<f64>,
+) -> Option<TimeUntil> {
+    // a tiny rate, or an infinite energy, doesn't fit a Duration
+    let hours =
+        |energy: f64, rate: f64| Duration::try_from_secs_f64(energy.max(0.0) / rate * 3600.0).ok();
+
+    match state? {
+        DeviceState::Discharging => match (energy, energy_rate) {
+            (Some(energy), Some(rate)) if rate > 0.0 => hours(energy, rate).map(TimeUntil::Empty),
+            _ => Some(TimeUntil::Calculating),
+        },
+        DeviceState::Charging => match (energy, energy_full, energy_rate) {
+            (Some(energy), Some(full), Some(rate)) if rate > 0.0 => {
+                hours(full - energy, rate).map(TimeUntil::Full)
+            }
+            _ => Some(TimeUntil::Calculating),
+        },
+        DeviceState::FullyCharged | DeviceState::PendingCharge => Some(TimeUntil::NotApplicable),
+        _ => None,
+    }
+}
+
+impl BatteryInterface for Sysfs {
+    fn battery_info(
+    ) -> std::result::Result<BatteryInfo, impl Into<Box<dyn std::error::Error + 'static>>> {
+        Sysfs::new().battery_info()
+    }
+
+    fn devices() -> std::result::Result<Vec<Device>, impl Into<Box<dyn std::error::Error + 'static>>>
+    {
+        Sysfs::new().devices()
+    }
+}
+
+impl BatterySource for Sysfs {
+    fn name(&self) -> &str {
+        "sysfs"
+    }
+
+    fn battery_info(&self) -> anyhow::Result<BatteryInfo> {
+        Sysfs::battery_info(self)
+    }
+
+    fn devices(&self) -> anyhow::Result<Vec<Device>> {
+        Sysfs::devices(self)
+    }
+
+    fn listing(&self) -> anyhow::Result<Listing> {
+        Sysfs::listing(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BAT0: &str = "\
+POWER_SUPPLY_NAME=BAT0
+POWER_SUPPLY_TYPE=Battery
+POWER_SUPPLY_STATUS=Discharging
+POWER_SUPPLY_PRESENT=1
+POWER_SUPPLY_CYCLE_COUNT=212
+POWER_SUPPLY_VOLTAGE_MIN_DESIGN=11400000
+POWER_SUPPLY_VOLTAGE_NOW=11820000
+POWER_SUPPLY_POWER_NOW=7600000
+POWER_SUPPLY_ENERGY_FULL_DESIGN=24000000
+POWER_SUPPLY_ENERGY_FULL=20000000
+POWER_SUPPLY_ENERGY_NOW=15200000
+POWER_SUPPLY_CAPACITY=76
+POWER_SUPPLY_CAPACITY_LEVEL=Normal
+POWER_SUPPLY_MODEL_NAME=01AV421
+";
+
+    // reports charge instead of energy, and no capacity
+    const BAT1: &str = "\
+POWER_SUPPLY_NAME=BAT1
+POWER_SUPPLY_TYPE=Battery
+POWER_SUPPLY_STATUS=Discharging
+POWER_SUPPLY_VOLTAGE_MIN_DESIGN=10000000
+POWER_SUPPLY_VOLTAGE_NOW=10500000
+POWER_SUPPLY_CURRENT_NOW=-200000
+POWER_SUPPLY_CHARGE_FULL=2000000
+POWER_SUPPLY_CHARGE_NOW=60000
+POWER_SUPPLY_TEMP=312
+";
+
+    const AC: &str = "\
+POWER_SUPPLY_NAME=AC
+POWER_SUPPLY_TYPE=Mains
+POWER_SUPPLY_ONLINE=0
+";
+
+    fn fixture() -> anyhow::Result<tempfile::TempDir> {
+        let dir = tempfile::tempdir()?;
+
+        for (name, uevent) in [("AC", AC), ("BAT0", BAT0), ("BAT1", BAT1)] {
+            fs::create_dir(dir.path().join(name))?;
+            fs::write(dir.path().join(name).join("uevent"), uevent)?;
+        }
+
+        Ok(dir)
+    }
+
+    #[test]
+    fn decode_energy() -> anyhow::Result<()> {
+        insta::assert_debug_snapshot!(decode_uevent(BAT0)?, @r###"
+        BatteryInfo {
+            device_type: Some(
+                Battery,
+            ),
+            device_state: Some(
+                Discharging,
+            ),
+            percentage: Some(
+                Percentage(
+                    76.0,
+                ),
+            ),
+            power_supply: Some(
+                PowerSupply(
+                    true,
+                ),
+            ),
+            battery_level: Some(
+                Normal,
+            ),
+            icon_name: None,
+            time_until: Some(
+                Empty(
+                    7200s,
+                ),
+            ),
+            warning_level: None,
+            energy: Some(
+                Energy(
+                    15.2,
+                ),
+            ),
+            energy_full: Some(
+                Energy(
+                    20.0,
+                ),
+            ),
+            energy_rate: Some(
+                EnergyRate(
+                    7.6,
+                ),
+            ),
+            voltage: Some(
+                Voltage(
+                    11.82,
+                ),
+            ),
+            temperature: None,
+            charge_cycles: Some(
+                ChargeCycles(
+                    212,
+                ),
+            ),
+            model: Some(
+                Model(
+                    "01AV421",
+                ),
+            ),
+            native_path: None,
+            diagnostics: [],
+        }
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_charge() -> anyhow::Result<()> {
+        let batt_info = decode_uevent(BAT1)?;
+
+        insta::assert_debug_snapshot!(
+            (
+                batt_info.percentage,
+                batt_info.energy,
+                batt_info.energy_full,
+                batt_info.energy_rate,
+                batt_info.temperature,
+                batt_info.time_until,
+            ),
+            @r###"
+        (
+            Some(
+                Percentage(
+                    3.0,
+                ),
+            ),
+            Some(
+                Energy(
+                    0.6,
+                ),
+            ),
+            Some(
+                Energy(
+                    20.0,
+                ),
+            ),
+            Some(
+                EnergyRate(
+                    2.1,
+                ),
+            ),
+            Some(
+                Temperature(
+                    31.2,
+                ),
+            ),
+            Some(
+                Empty(
+                    1028.571428571s,
+                ),
+            ),
+        )
+        "###
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn devices() -> anyhow::Result<()> {
+        let dir = fixture()?;
+        let sysfs = Sysfs::with_root(dir.path());
+
+        // removed between listing the directory and reading its uevent
+        fs::create_dir(dir.path().join("BAT2"))?;
+
+        let listing = sysfs.listing()?;
+        let devices = listing
+            .devices
+            .iter()
+            .map(|device| device.to_string())
+            .collect::<Vec<_>>();
+
+        insta::assert_debug_snapshot!(devices, @r###"
+        [
+            "AC:",
+            "BAT0: 76% discharging, 2h 0m until empty",
+            "BAT1: 3% discharging, 0h 17m until empty",
+        ]
+        "###);
+
+        insta::assert_debug_snapshot!(
+            listing
+                .skipped
+                .iter()
+                .map(|skipped| skipped.id.rsplit('/').next().unwrap())
+                .collect::<Vec<_>>(),
+            @r###"
+        [
+            "BAT2",
+        ]
+        "###
+        );
+
+        // the energy of both packs over their combined capacity
+        insta::assert_debug_snapshot!(sysfs.battery_info()?.percentage, @r###"
+        Some(
+            Percentage(
+                39.49999999999999,
+            ),
+        )
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn time_until_out_of_range() {
+        let estimates = [
+            time_until(
+                Some(DeviceState::Discharging),
+                Some(50.0),
+                None,
+                Some(1e-300),
+            ),
+            time_until(
+                Some(DeviceState::Charging),
+                Some(0.0),
+                Some(f64::INFINITY),
+                Some(5.0),
+            ),
+            time_until(Some(DeviceState::Discharging), Some(50.0), None, Some(0.0)),
+        ];
+
+        insta::assert_debug_snapshot!(estimates, @r###"
+        [
+            None,
+            None,
+            Some(
+                Calculating,
+            ),
+        ]
+        "###);
+    }
+
+    #[test]
+    fn malformed() {
+        let error =
+            decode_uevent("POWER_SUPPLY_TYPE=Battery\nPOWER_SUPPLY_CAPACITY=lots\n").unwrap_err();
+
+        insta::assert_snapshot!(error.to_string(), @r###"CAPACITY: error: ParseFloatError { kind: Invalid }, value: "lots""###);
+    }
+}
@@ -19,6 +19,8 @@ use zbus::{
 
 use crate::battery_info::*;
 
+pub mod changes;
+pub mod hotplug;
 mod utils;
 use utils::*;
 
@@ -34,19 +36,50 @@ type DBusConnection = zbus::blocking::Connection;
 )]
 trait UPower {
     fn get_display_device(&self) -> zbus::Result<zvariant::OwnedObjectPath>;
+
+    fn enumerate_devices(&self) -> zbus::Result<Vec<zvariant::OwnedObjectPath>>;
+
+    #[zbus(signal)]
+    fn device_added(&self, device: zvariant::ObjectPath<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn device_removed(&self, device: zvariant::ObjectPath<'_>) -> zbus::Result<()>;
 }
 
+/// A handle on UPower over D-Bus.
+///
+/// Handles are cheap to clone and `Send + Sync`. Clones share the connection and its proxies,
+/// calls from several threads go over that one connection at the same time.
+#[derive(Clone)]
 pub struct UPower {
     proxy: UPowerProxy<'static>,
     properties_proxy: PropertiesProxy<'static>,
+    parse_mode: ParseMode,
 }
 
 impl UPower {
+    /// The handle on the system bus shared by the whole process, clone it for an owned one.
     pub fn new() -> anyhow::Result<&'static Self> {
         let clj = || DBUS_UPOWER.deref().as_ref();
         retry_result_with_delay::<'static, UPower, 100>(clj)
     }
 
+    /// A handle on `connection` instead of the shared system bus connection.
+    pub fn with_connection(connection: &DBusConnection) -> anyhow::Result<Self> {
+        let proxy = UPowerProxy::new(connection)?;
+
+        let properties_proxy = device_properties_proxy(
+            connection,
+            zvariant::ObjectPath::from_static_str("/org/freedesktop/UPower/devices/DisplayDevice")?,
+        )?;
+
+        Ok(UPower {
+            proxy,
+            properties_proxy,
+            parse_mode: ParseMode::default(),
+        })
+    }
+
     pub fn get_display_device(&self) -> anyhow::Result<zvariant::OwnedObjectPath> {
         Ok(self.proxy.get_display_device()?)
     }
@@ -64,124 +97,264 @@ impl UPower {
     pub fn battery_info(&self) -> anyhow::Result<BatteryInfo> {
         let disp_dev_props = self.get_all_display_device_properties()?;
 
-        let mut batt_info = BatteryInfo::default();
-
-        disp_dev_props.iter().try_for_each(|(k, v)| {
-            anyhow::Ok(match k.as_str() {
-                "BatteryLevel" => {
-                    let prop = BatteryInfoProperties::BatteryLevel(
-                        v.downcast_ref::<u32>()
-                            .map_err(|e| {
-                                anyhow!(
-                                    "BatteryLevel: error: {:?}, value: {:?}, key: {:?}",
-                                    e,
-                                    v,
-                                    k
-                                )
-                            })?
-                            .into(),
-                    );
-
-                    batt_info.set_propertry(prop);
-                }
-
-                "IconName" => {
-                    let prop = BatteryInfoProperties::IconName(
-                        v.try_to_owned()
-                            .map_err(|e| {
-                                anyhow!("IconName: error: {:?}, value: {:?}, key: {:?}", e, v, k)
-                            })?
-                            .to_string()
-                            .into(),
-                    );
-
-                    batt_info.set_propertry(prop);
-                }
-
-                "Type" => {
-                    let prop = BatteryInfoProperties::DeviceType(
-                        v.downcast_ref::<u32>()
-                            .map_err(|e| {
-                                anyhow!("Type: error: {:?}, value: {:?}, key: {:?}", e, v, k)
-                            })?
-                            .into(),
-                    );
-
-                    batt_info.set_propertry(prop);
-                }
-
-                "TimeToEmpty" | "TimeToFull" => {
-                    let value = v.downcast_ref().map_err(|e| {
-                        anyhow!(
-                            "'TimeToEmpty' | 'TimeToFull': error: {:?}, value: {:?}, key: {:?}",
-                            e,
-                            v,
-                            k
-                        )
-                    })?;
-
-                    handle_time(k.as_str(), value, &mut batt_info)?;
-                }
-
-                "Percentage" => {
-                    let prop = BatteryInfoProperties::Percentage(
-                        v.downcast_ref::<f64>()
-                            .map_err(|e| {
-                                anyhow!("Percentage: error: {:?}, value: {:?}, key: {:?}", e, v, k)
-                            })?
-                            .into(),
-                    );
-
-                    batt_info.set_propertry(prop);
-                }
-
-                "WarningLevel" => {
-                    let prop = BatteryInfoProperties::WarningLevel(
-                        v.downcast_ref::<u32>()
-                            .map_err(|e| {
-                                anyhow!(
-                                    "WarningLevel: error: {:?}, value: {:?}, key: {:?}",
-                                    e,
-                                    v,
-                                    k
-                                )
-                            })?
-                            .into(),
-                    );
-
-                    batt_info.set_propertry(prop);
-                }
-
-                "State" => {
-                    let prop = BatteryInfoProperties::DeviceState(
-                        v.downcast_ref::<u32>()
-                            .map_err(|e| {
-                                anyhow!("State: error: {:?}, value: {:?}, key: {:?}", e, v, k)
-                            })?
-                            .into(),
-                    );
-
-                    batt_info.set_propertry(prop);
-                }
-
-                "PowerSupply" => {
-                    let prop = BatteryInfoProperties::PowerSupply(
-                        v.downcast_ref::<bool>()
-                            .map_err(|e| {
-                                anyhow!("PowerSupply: error: {:?}, value: {:?}, key: {:?}", e, v, k)
-                            })?
-                            .into()
-                    );
-
-                    batt_info.set_propertry(prop);
-                }
-
-                &_ => {}
-            })
-        })?;
+        decode_properties(&disp_dev_props, self.parse_mode)
+    }
+
+    /// Object paths of every device UPower knows about, excluding the display device.
+    pub fn enumerate_devices(&self) -> anyhow::Result<Vec<zvariant::OwnedObjectPath>> {
+        Ok(self.proxy.enumerate_devices()?)
+    }
+
+    pub fn get_all_device_properties(
+        &self,
+        path: &zvariant::ObjectPath<'_>,
+    ) -> anyhow::Result<HashMap<String, zvariant::OwnedValue>> {
+        let properties_proxy =
+            device_properties_proxy(self.proxy.inner().connection(), path.to_owned())?;
+
+        Ok(
+            properties_proxy.get_all(Optional::<InterfaceName<'static>>::from(
+                InterfaceName::from_static_str("org.freedesktop.UPower.Device").ok(),
+            ))?,
+        )
+    }
+
+    pub fn device_battery_info(
+        &self,
+        path: &zvariant::ObjectPath<'_>,
+    ) -> anyhow::Result<BatteryInfo> {
+        let dev_props = self.get_all_device_properties(path)?;
+
+        decode_properties(&dev_props, self.parse_mode)
+    }
+
+    /// Every device UPower could be read for, see [`UPower::listing`].
+    pub fn devices(&self) -> anyhow::Result<Vec<Device>> {
+        Ok(self.listing()?.devices)
+    }
+
+    /// Every device UPower knows about. A device that goes away between enumerating and reading
+    /// it, e.g. an unplugged headset, is left out and reported as skipped.
+    pub fn listing(&self) -> anyhow::Result<Listing> {
+        let mut listing = Listing::default();
+
+        for path in self.enumerate_devices()? {
+            match self.device_battery_info(&path) {
+                Ok(info) => listing.devices.push(Device {
+                    info,
+                    id: path.to_string(),
+                }),
+                Err(e) => listing.skipped.push(SkippedDevice {
+                    id: path.to_string(),
+                    error: format!("{:#}", e),
+                }),
+            }
+        }
+
+        Ok(listing)
+    }
+
+    pub fn parse_mode(&self) -> ParseMode {
+        self.parse_mode
+    }
+
+    /// A handle sharing the same D-Bus proxies that decodes properties using `parse_mode`.
+    pub fn with_parse_mode(&self, parse_mode: ParseMode) -> Self {
+        Self {
+            parse_mode,
+            ..self.clone()
+        }
+    }
+}
 
-        Ok(batt_info)
+/// How to react to property values that can't be decoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Fail the whole [`BatteryInfo`] on the first property that can't be decoded
+    #[default]
+    Strict,
+    /// Skip properties that can't be decoded and record them in [`BatteryInfo::diagnostics`]
+    Lenient,
+}
+
+enum DecodedProperty {
+    Property(BatteryInfoProperties),
+    TimeToEmpty(i64),
+    TimeToFull(i64),
+}
+
+fn decode_properties(
+    props: &HashMap<String, zvariant::OwnedValue>,
+    parse_mode: ParseMode,
+) -> anyhow::Result<BatteryInfo> {
+    decode_changed_properties(props, None, parse_mode)
+}
+
+/// Decode the properties a PropertiesChanged signal of a device carries.
+///
+/// TimeToEmpty and TimeToFull depend on the state, `state` is the one known so far for when the
+/// signal doesn't carry the State as well.
+pub fn decode_changed_properties(
+    props: &HashMap<String, zvariant::OwnedValue>,
+    state: Option<device_state::DeviceState>,
+    parse_mode: ParseMode,
+) -> anyhow::Result<BatteryInfo> {
+    let mut batt_info = BatteryInfo::default();
+
+    let report = |key: &str, error: anyhow::Error, batt_info: &mut BatteryInfo| match parse_mode {
+        ParseMode::Strict => Err(error),
+        ParseMode::Lenient => {
+            batt_info
+                .diagnostics
+                .push(PropertyDiagnostic::new(key, &error));
+            Ok(())
+        }
+    };
+
+    // resolved once all properties are known, these depend on each other and on the state
+    let mut time_to_empty = None;
+    let mut time_to_full = None;
+
+    for (k, v) in props.iter() {
+        match decode_property(k, v) {
+            Ok(Some(DecodedProperty::Property(prop))) => batt_info.set_propertry(prop),
+            Ok(Some(DecodedProperty::TimeToEmpty(value))) => time_to_empty = Some(value),
+            Ok(Some(DecodedProperty::TimeToFull(value))) => time_to_full = Some(value),
+            Ok(None) => {}
+            Err(e) => report(k, e, &mut batt_info)?,
+        }
     }
+
+    match resolve_time(time_to_empty, time_to_full, batt_info.device_state.or(state)) {
+        Ok(Some(time_until)) => {
+            batt_info.set_propertry(BatteryInfoProperties::TimeUntil(time_until))
+        }
+        Ok(None) => {}
+        Err(e) => report("TimeToEmpty/TimeToFull", e, &mut batt_info)?,
+    }
+
+    Ok(batt_info)
+}
+
+fn decode_property(k: &str, v: &zvariant::OwnedValue) -> anyhow::Result<Option<DecodedProperty>> {
+    let prop = match k {
+        "BatteryLevel" => BatteryInfoProperties::BatteryLevel(
+            v.downcast_ref::<u32>()
+                .map_err(|e| anyhow!("BatteryLevel: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .into(),
+        ),
+
+        "IconName" => BatteryInfoProperties::IconName(
+            v.downcast_ref::<&str>()
+                .map_err(|e| anyhow!("IconName: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .to_owned()
+                .into(),
+        ),
+
+        "Type" => BatteryInfoProperties::DeviceType(
+            v.downcast_ref::<u32>()
+                .map_err(|e| anyhow!("Type: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .into(),
+        ),
+
+        "TimeToEmpty" | "TimeToFull" => {
+            let value = v.downcast_ref::<i64>().map_err(|e| {
+                anyhow!(
+                    "'TimeToEmpty' | 'TimeToFull': error: {:?}, value: {:?}, key: {:?}",
+                    e,
+                    v,
+                    k
+                )
+            })?;
+
+            return Ok(Some(match k {
+                "TimeToEmpty" => DecodedProperty::TimeToEmpty(value),
+                _ => DecodedProperty::TimeToFull(value),
+            }));
+        }
+
+        "Percentage" => BatteryInfoProperties::Percentage(
+            v.downcast_ref::<f64>()
+                .map_err(|e| anyhow!("Percentage: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .into(),
+        ),
+
+        "WarningLevel" => BatteryInfoProperties::WarningLevel(
+            v.downcast_ref::<u32>()
+                .map_err(|e| anyhow!("WarningLevel: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .into(),
+        ),
+
+        "State" => BatteryInfoProperties::DeviceState(
+            v.downcast_ref::<u32>()
+                .map_err(|e| anyhow!("State: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .into(),
+        ),
+
+        "PowerSupply" => BatteryInfoProperties::PowerSupply(
+            v.downcast_ref::<bool>()
+                .map_err(|e| anyhow!("PowerSupply: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .into(),
+        ),
+
+        "Energy" | "EnergyFull" => {
+            let value = v.downcast_ref::<f64>().map_err(|e| {
+                anyhow!(
+                    "'Energy' | 'EnergyFull': error: {:?}, value: {:?}, key: {:?}",
+                    e,
+                    v,
+                    k
+                )
+            })?;
+
+            match k {
+                "Energy" => BatteryInfoProperties::Energy(value.into()),
+                _ => BatteryInfoProperties::EnergyFull(value.into()),
+            }
+        }
+
+        "EnergyRate" => BatteryInfoProperties::EnergyRate(
+            v.downcast_ref::<f64>()
+                .map_err(|e| anyhow!("EnergyRate: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .into(),
+        ),
+
+        "Voltage" => BatteryInfoProperties::Voltage(
+            v.downcast_ref::<f64>()
+                .map_err(|e| anyhow!("Voltage: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .into(),
+        ),
+
+        "Temperature" => BatteryInfoProperties::Temperature(
+            v.downcast_ref::<f64>()
+                .map_err(|e| anyhow!("Temperature: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .into(),
+        ),
+
+        "ChargeCycles" => BatteryInfoProperties::ChargeCycles(
+            v.downcast_ref::<i32>()
+                .map_err(|e| anyhow!("ChargeCycles: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .into(),
+        ),
+
+        "Model" => BatteryInfoProperties::Model(
+            v.downcast_ref::<&str>()
+                .map_err(|e| anyhow!("Model: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .to_owned()
+                .into(),
+        ),
+
+        "NativePath" => BatteryInfoProperties::NativePath(
+            v.downcast_ref::<&str>()
+                .map_err(|e| anyhow!("NativePath: error: {:?}, value: {:?}, key: {:?}", e, v, k))?
+                .to_owned()
+                .into(),
+        ),
+
+        &_ => return Ok(None),
+    };
+
+    Ok(Some(DecodedProperty::Property(prop)))
 }
 
 impl BatteryInterface for UPower {
@@ -190,6 +363,42 @@ impl BatteryInterface for UPower {
         let upower = UPower::new()?;
         upower.battery_info()
     }
+
+    fn devices() -> std::result::Result<Vec<Device>, impl Into<Box<dyn std::error::Error + 'static>>>
+    {
+        let upower = UPower::new()?;
+        upower.devices()
+    }
+}
+
+impl BatterySource for UPower {
+    fn name(&self) -> &str {
+        "upower"
+    }
+
+    fn battery_info(&self) -> anyhow::Result<BatteryInfo> {
+        UPower::battery_info(self)
+    }
+
+    fn devices(&self) -> anyhow::Result<Vec<Device>> {
+        UPower::devices(self)
+    }
+
+    fn listing(&self) -> anyhow::Result<Listing> {
+        UPower::listing(self)
+    }
+}
+
+fn device_properties_proxy(
+    connection: &DBusConnection,
+    path: zvariant::ObjectPath<'static>,
+) -> anyhow::Result<PropertiesProxy<'static>> {
+    Ok(PropertiesProxy::builder(connection)
+        .destination("org.freedesktop.UPower")?
+        .path(path)?
+        .interface("org.freedesktop.DBus.Properties")?
+        .cache_properties(CacheProperties::No)
+        .build()?)
 }
 
 
@@ -211,24 +420,220 @@ static DBUS_UPOWER: Lazy<anyhow::Result<UPower>> = Lazy::new(|| {
         Err(e) => bail!(e),
     };
 
-    let proxy = UPowerProxy::new(connection)?;
-
-    let properties_proxy: PropertiesProxy<'static> = PropertiesProxy::builder(connection)
-        .destination("org.freedesktop.UPower")?
-        .path("/org/freedesktop/UPower/devices/DisplayDevice")?
-        .interface("org.freedesktop.DBus.Properties")?
-        .cache_properties(CacheProperties::No)
-        .build()?;
-
-    Ok(UPower {
-        proxy,
-        properties_proxy,
-    })
+    UPower::with_connection(connection)
 });
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        battery_info::{
+            device_state::DeviceState,
+            device_type::DeviceType,
+        },
+        test_utils::{
+            p2p_connections,
+            FakeDevice,
+            FakeUPower,
+            UPOWER_PATH,
+        },
+    };
+
+    fn fixture_properties() -> HashMap<String, zvariant::OwnedValue> {
+        HashMap::from([
+            ("Type".to_owned(), 2u32.into()),
+            ("State".to_owned(), 2u32.into()),
+            ("Percentage".to_owned(), 40.0f64.into()),
+            ("PowerSupply".to_owned(), true.into()),
+            ("TimeToEmpty".to_owned(), 3600i64.into()),
+            ("TimeToFull".to_owned(), 0i64.into()),
+            ("WarningLevel".to_owned(), 1u32.into()),
+            (
+                "IconName".to_owned(),
+                zvariant::Str::from_static("battery-level-40-symbolic").into(),
+            ),
+            ("Energy".to_owned(), 20.0f64.into()),
+            ("EnergyFull".to_owned(), 50.0f64.into()),
+            ("EnergyRate".to_owned(), 7.5f64.into()),
+            ("Voltage".to_owned(), 11.4f64.into()),
+            ("Temperature".to_owned(), 31.5f64.into()),
+            ("ChargeCycles".to_owned(), 120i32.into()),
+            (
+                "Model".to_owned(),
+                zvariant::Str::from_static("5B10W13930").into(),
+            ),
+            (
+                "NativePath".to_owned(),
+                zvariant::Str::from_static("BAT0").into(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn vanished_device() -> anyhow::Result<()> {
+        const BATTERY: &str = "/org/freedesktop/UPower/devices/battery_BAT0";
+        const HEADSET: &str = "/org/freedesktop/UPower/devices/headset_dev_00_1B_66";
+
+        // the headset is enumerated but gone by the time its properties are read
+        let (_server, client) = p2p_connections(|builder| {
+            builder
+                .serve_at(
+                    UPOWER_PATH,
+                    FakeUPower {
+                        devices: vec![HEADSET.try_into().unwrap(), BATTERY.try_into().unwrap()],
+                    },
+                )?
+                .serve_at(
+                    BATTERY,
+                    FakeDevice::new(DeviceType::Battery, DeviceState::Discharging, 80.0),
+                )
+        })?;
+
+        let listing = UPower::with_connection(&client)?.listing()?;
+
+        insta::assert_debug_snapshot!(
+            (
+                listing.devices.iter().map(|device| device.to_string()).collect::<Vec<_>>(),
+                listing.skipped.iter().map(|skipped| skipped.id.as_str()).collect::<Vec<_>>(),
+            ),
+            @r###"
+        (
+            [
+                "battery_BAT0: 80% discharging",
+            ],
+            [
+                "/org/freedesktop/UPower/devices/headset_dev_00_1B_66",
+            ],
+        )
+        "###
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_strict() -> anyhow::Result<()> {
+        let batt_info = decode_properties(&fixture_properties(), ParseMode::Strict)?;
+
+        insta::assert_debug_snapshot!(batt_info, @r###"
+        BatteryInfo {
+            device_type: Some(
+                Battery,
+            ),
+            device_state: Some(
+                Discharging,
+            ),
+            percentage: Some(
+                Percentage(
+                    40.0,
+                ),
+            ),
+            power_supply: Some(
+                PowerSupply(
+                    true,
+                ),
+            ),
+            battery_level: None,
+            icon_name: Some(
+                IconName(
+                    "battery-level-40-symbolic",
+                ),
+            ),
+            time_until: Some(
+                Empty(
+                    3600s,
+                ),
+            ),
+            warning_level: Some(
+                NoWarning,
+            ),
+            energy: Some(
+                Energy(
+                    20.0,
+                ),
+            ),
+            energy_full: Some(
+                Energy(
+                    50.0,
+                ),
+            ),
+            energy_rate: Some(
+                EnergyRate(
+                    7.5,
+                ),
+            ),
+            voltage: Some(
+                Voltage(
+                    11.4,
+                ),
+            ),
+            temperature: Some(
+                Temperature(
+                    31.5,
+                ),
+            ),
+            charge_cycles: Some(
+                ChargeCycles(
+                    120,
+                ),
+            ),
+            model: Some(
+                Model(
+                    "5B10W13930",
+                ),
+            ),
+            native_path: Some(
+                NativePath(
+                    "BAT0",
+                ),
+            ),
+            diagnostics: [],
+        }
+        "###);
+
+        let mut props = fixture_properties();
+        props.insert("Percentage".to_owned(), 40u32.into());
+
+        let this_should_be_error = decode_properties(&props, ParseMode::Strict);
+
+        insta::assert_debug_snapshot!(this_should_be_error.is_err(), @"true");
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_lenient() -> anyhow::Result<()> {
+        let mut props = fixture_properties();
+        props.insert("Percentage".to_owned(), 40u32.into());
+        props.insert("TimeToEmpty".to_owned(), (-1i64).into());
+
+        let batt_info = decode_properties(&props, ParseMode::Lenient)?;
+
+        insta::assert_debug_snapshot!(batt_info.percentage, @"None");
+        insta::assert_debug_snapshot!(batt_info.time_until, @"None");
+        insta::assert_debug_snapshot!(batt_info.device_state, @r###"
+        Some(
+            Discharging,
+        )
+        "###);
+
+        let mut diagnostics = batt_info
+            .diagnostics()
+            .iter()
+            .map(|d| d.key())
+            .collect::<Vec<_>>();
+
+        diagnostics.sort();
+
+        insta::assert_debug_snapshot!(diagnostics, @r###"
+        [
+            "Percentage",
+            "TimeToEmpty/TimeToFull",
+        ]
+        "###);
+
+        Ok(())
+    }
 
     #[test]
     fn percentage() -> anyhow::Result<()> {
@@ -300,8 +705,7 @@ mod tests {
 
         let mut disp_dev_props: Vec<String> = upower
             .get_all_display_device_properties()?
-            .into_iter()
-            .map(|(k, _)| k)
+            .into_keys()
             .collect::<Vec<String>>();
 
         disp_dev_props.sort();
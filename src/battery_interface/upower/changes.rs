@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use zbus::{
+    blocking::MessageIterator,
+    fdo::PropertiesChanged,
+    message::Type,
+    zvariant::{
+        ObjectPath,
+        OwnedValue,
+    },
+    MatchRule,
+};
+
+use super::UPower;
+
+/// The properties of a device that changed, as a PropertiesChanged signal carries them.
+///
+/// Decode them with [`decode_changed_properties`](super::decode_changed_properties).
+#[derive(Debug)]
+pub struct PropertiesChange {
+    pub changed: HashMap<String, OwnedValue>,
+    /// Properties that changed without the signal carrying their new value
+    pub invalidated: Vec<String>,
+}
+
+/// Blocks for the next [`PropertiesChange`], see [`UPower::device_changes`].
+pub struct PropertyChanges {
+    messages: MessageIterator,
+}
+
+impl UPower {
+    /// Changes to the properties of the device at `path` from now on.
+    pub fn device_changes(&self, path: &ObjectPath<'_>) -> anyhow::Result<PropertyChanges> {
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .sender("org.freedesktop.UPower")?
+            .path(path.to_owned())?
+            .interface("org.freedesktop.DBus.Properties")?
+            .member("PropertiesChanged")?
+            .arg(0, "org.freedesktop.UPower.Device")?
+            .build();
+
+        Ok(PropertyChanges {
+            messages: MessageIterator::for_match_rule(rule, self.proxy.inner().connection(), None)?,
+        })
+    }
+
+    /// Changes to the properties of the display device from now on.
+    pub fn display_device_changes(&self) -> anyhow::Result<PropertyChanges> {
+        self.device_changes(self.properties_proxy.inner().path())
+    }
+}
+
+impl Iterator for PropertyChanges {
+    type Item = anyhow::Result<PropertiesChange>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let message = match self.messages.next()? {
+                Ok(message) => message,
+                Err(e) => return Some(Err(e.into())),
+            };
+
+            let Some(signal) = PropertiesChanged::from_message(message) else {
+                continue;
+            };
+
+            return Some(signal.args().map_err(Into::into).and_then(|args| {
+                Ok(PropertiesChange {
+                    changed: args
+                        .changed_properties()
+                        .iter()
+                        .map(|(name, value)| Ok((name.to_string(), value.try_to_owned()?)))
+                        .collect::<anyhow::Result<_>>()?,
+                    invalidated: args
+                        .invalidated_properties()
+                        .iter()
+                        .map(|name| name.to_string())
+                        .collect(),
+                })
+            }));
+        }
+    }
+}
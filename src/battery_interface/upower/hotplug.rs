@@ -0,0 +1,183 @@
+use zbus::{
+    blocking::MessageIterator,
+    message::Type,
+    zvariant::OwnedObjectPath,
+    MatchRule,
+};
+
+use super::{
+    DeviceAdded,
+    DeviceRemoved,
+    UPower,
+};
+use crate::battery_info::BatteryInfo;
+
+/// A device UPower started or stopped tracking, e.g. a Bluetooth headset connecting.
+#[derive(Debug)]
+pub enum HotplugEvent {
+    /// With a first reading of the new device
+    Added {
+        path: OwnedObjectPath,
+        info: Box<BatteryInfo>,
+    },
+    Removed {
+        path: OwnedObjectPath,
+    },
+}
+
+impl HotplugEvent {
+    pub fn path(&self) -> &OwnedObjectPath {
+        match self {
+            HotplugEvent::Added { path, .. } | HotplugEvent::Removed { path } => path,
+        }
+    }
+}
+
+/// Blocks for the next [`HotplugEvent`], see [`UPower::hotplug`].
+pub struct Hotplug<'a> {
+    upower: &'a UPower,
+    messages: MessageIterator,
+}
+
+impl UPower {
+    /// Devices added or removed from now on.
+    ///
+    /// An added device is read right away, which fails if it's already gone again. That error
+    /// is an item of its own, the iterator carries on after it.
+    pub fn hotplug(&self) -> anyhow::Result<Hotplug<'_>> {
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .sender("org.freedesktop.UPower")?
+            .path("/org/freedesktop/UPower")?
+            .interface("org.freedesktop.UPower")?
+            .build();
+
+        Ok(Hotplug {
+            upower: self,
+            messages: MessageIterator::for_match_rule(rule, self.proxy.inner().connection(), None)?,
+        })
+    }
+}
+
+impl Iterator for Hotplug<'_> {
+    type Item = anyhow::Result<HotplugEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let message = match self.messages.next()? {
+                Ok(message) => message,
+                Err(e) => return Some(Err(e.into())),
+            };
+
+            if let Some(added) = DeviceAdded::from_message(message.clone()) {
+                return Some(added.args().map_err(Into::into).and_then(|args| {
+                    let path = OwnedObjectPath::from(args.device);
+
+                    Ok(HotplugEvent::Added {
+                        info: Box::new(self.upower.device_battery_info(&path)?),
+                        path,
+                    })
+                }));
+            }
+
+            if let Some(removed) = DeviceRemoved::from_message(message) {
+                return Some(
+                    removed
+                        .args()
+                        .map(|args| HotplugEvent::Removed {
+                            path: args.device.into(),
+                        })
+                        .map_err(Into::into),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::{
+        block_on,
+        zvariant::ObjectPath,
+    };
+
+    use super::*;
+    use crate::{
+        battery_info::{
+            device_state::DeviceState,
+            device_type::DeviceType,
+        },
+        test_utils::{
+            p2p_connections,
+            FakeDevice,
+            FakeUPower,
+            UPOWER_PATH,
+        },
+    };
+
+    const HEADSET: &str = "/org/freedesktop/UPower/devices/headset_dev_00_1B_66";
+
+    #[test]
+    fn added_and_removed() -> anyhow::Result<()> {
+        let mut headset = FakeDevice::new(DeviceType::Headset, DeviceState::Discharging, 10.0);
+        headset.model = "WH-1000XM4".to_owned();
+
+        let (server, client) = p2p_connections(|builder| {
+            builder
+                .serve_at(UPOWER_PATH, FakeUPower { devices: vec![] })?
+                .serve_at(HEADSET, headset)
+        })?;
+
+        let upower = UPower::with_connection(&client)?;
+        let mut hotplug = upower.hotplug()?;
+
+        let iface = server
+            .object_server()
+            .interface::<_, FakeUPower>(UPOWER_PATH)?;
+        let path = ObjectPath::from_static_str(HEADSET)?;
+        block_on(FakeUPower::device_added(
+            iface.signal_context(),
+            path.clone(),
+        ))?;
+        block_on(FakeUPower::device_removed(iface.signal_context(), path))?;
+
+        let added = hotplug.next().expect("an added event")?;
+        let removed = hotplug.next().expect("a removed event")?;
+
+        let HotplugEvent::Added { path, info } = added else {
+            panic!("expected an added event, got {:?}", added);
+        };
+        insta::assert_debug_snapshot!(
+            (path.as_str(), info.device_type, info.percentage, info.model),
+            @r###"
+        (
+            "/org/freedesktop/UPower/devices/headset_dev_00_1B_66",
+            Some(
+                Headset,
+            ),
+            Some(
+                Percentage(
+                    10.0,
+                ),
+            ),
+            Some(
+                Model(
+                    "WH-1000XM4",
+                ),
+            ),
+        )
+        "###
+        );
+        insta::assert_debug_snapshot!(removed, @r###"
+        Removed {
+            path: OwnedObjectPath(
+                ObjectPath(
+                    "/org/freedesktop/UPower/devices/headset_dev_00_1B_66",
+                ),
+            ),
+        }
+        "###);
+
+        Ok(())
+    }
+}
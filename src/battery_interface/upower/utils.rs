@@ -5,54 +5,48 @@ use anyhow::bail;
 use seq_macro::seq;
 
 use super::*;
-
-pub(super) fn handle_time(
-    key: &str,
-    value: i64,
-    batt_info: &mut BatteryInfo,
-) -> anyhow::Result<()> {
-    if let Some(t) = &batt_info.time_until {
-        let duration: Duration = **t;
-
-        let current_dur_is_zero = duration == Duration::from_secs(0);
-        let key_check = key == t.as_str();
-
-        // should never happen we are never mutating a BatteryInfo state that has been
-        // initialized with real values, and keys should always be different from what's
-        // already there
-        if key_check {
-            bail!(
-                "encountered a time key that's already been encountered, this should never happen"
-            );
-        }
-
-        // this state is weird - both 'TimeToFull' and 'TimeToEmpty' is zero
-        if current_dur_is_zero && value == 0 {
-            bail!("both 'TimeToFull' and 'TimeToEmpty' is zero, this should never happen");
-        }
-
-        // this state is weird - both 'TimeToFull' and 'TimeToEmpty' is n0n-zero
-        if !current_dur_is_zero && value != 0 {
-            bail!("both 'TimeToFull' and 'TimeToEmpty' is non-zero, this should never happen")
-        }
-
-        // dont update value, already correct
-        if !current_dur_is_zero && value == 0 {
-            return Ok(());
-        }
-
-        batt_info.set_propertry(BatteryInfoProperties::TimeUntil(
-            (key, Duration::from_secs(value.try_into()?)).into(),
-        ));
-
-        return Ok(());
+use crate::battery_info::{
+    device_state::DeviceState,
+    time_until::TimeUntil,
+};
+
+/// Combine UPower's `TimeToEmpty` and `TimeToFull` into a single [`TimeUntil`].
+///
+/// Both values have to be known up front, together with the device state, since UPower reports
+/// zero for both when fully charged or while the (dis)charge rate is still being computed.
+pub(super) fn resolve_time(
+    time_to_empty: Option<i64>,
+    time_to_full: Option<i64>,
+    device_state: Option<DeviceState>,
+) -> anyhow::Result<Option<TimeUntil>> {
+    if time_to_empty.is_none() && time_to_full.is_none() {
+        return Ok(None);
     }
 
-    batt_info.set_propertry(BatteryInfoProperties::TimeUntil(
-        (key, Duration::from_secs(value.try_into()?)).into(),
-    ));
+    let to_duration = |secs: i64| -> anyhow::Result<Duration> {
+        if secs < 0 {
+            bail!("encountered a negative time estimate: {}s", secs);
+        }
 
-    Ok(())
+        Ok(Duration::from_secs(secs as u64))
+    };
+
+    let time_until = match (time_to_empty.unwrap_or(0), time_to_full.unwrap_or(0)) {
+        (0, 0) => match device_state {
+            Some(DeviceState::Charging | DeviceState::Discharging) => TimeUntil::Calculating,
+            _ => TimeUntil::NotApplicable,
+        },
+        (empty, 0) => TimeUntil::Empty(to_duration(empty)?),
+        (0, full) => TimeUntil::Full(to_duration(full)?),
+        // both estimates are non-zero, let the device state decide which one is relevant
+        (empty, full) => match device_state {
+            Some(DeviceState::Charging) => TimeUntil::Full(to_duration(full)?),
+            Some(DeviceState::Discharging) => TimeUntil::Empty(to_duration(empty)?),
+            _ => TimeUntil::Calculating,
+        },
+    };
+
+    Ok(Some(time_until))
 }
 
 pub(super) fn retry_result_with_delay<'a, T, const DURATION_MS: u64>(
@@ -86,12 +80,10 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_handle_time() -> anyhow::Result<()> {
-        let mut batt_info = BatteryInfo::default();
-
-        handle_time("TimeToEmpty", 1000, &mut batt_info)?;
+    fn test_resolve_time() -> anyhow::Result<()> {
+        let time_until = resolve_time(Some(1000), Some(0), Some(DeviceState::Discharging))?;
 
-        insta::assert_debug_snapshot!(batt_info.time_until, @r###"
+        insta::assert_debug_snapshot!(time_until, @r###"
         Some(
             Empty(
                 1000s,
@@ -99,21 +91,20 @@ mod tests {
         )
         "###);
 
-        handle_time("TimeToFull", 0, &mut batt_info)?;
+        let time_until = resolve_time(Some(0), Some(1000), Some(DeviceState::Charging))?;
 
-        insta::assert_debug_snapshot!(batt_info.time_until, @r###"
+        insta::assert_debug_snapshot!(time_until, @r###"
         Some(
-            Empty(
+            Full(
                 1000s,
             ),
         )
         "###);
 
-        let mut batt_info = BatteryInfo::default();
-
-        handle_time("TimeToFull", 1000, &mut batt_info)?;
+        // only one of the keys reported
+        let time_until = resolve_time(None, Some(1000), None)?;
 
-        insta::assert_debug_snapshot!(batt_info.time_until, @r###"
+        insta::assert_debug_snapshot!(time_until, @r###"
         Some(
             Full(
                 1000s,
@@ -121,55 +112,79 @@ mod tests {
         )
         "###);
 
-        handle_time("TimeToEmpty", 0, &mut batt_info)?;
+        let time_until = resolve_time(None, None, Some(DeviceState::Charging))?;
 
-        insta::assert_debug_snapshot!(batt_info.time_until, @r###"
+        insta::assert_debug_snapshot!(time_until, @"None");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_time_both_zero() -> anyhow::Result<()> {
+        // fully charged, or on AC with a full battery
+        let time_until = resolve_time(Some(0), Some(0), Some(DeviceState::FullyCharged))?;
+
+        insta::assert_debug_snapshot!(time_until, @r###"
         Some(
-            Full(
-                1000s,
-            ),
+            NotApplicable,
         )
         "###);
 
-        // weird case - have both time to empty and time to full
-        let this_should_be_error = handle_time("TimeToEmpty", 999, &mut batt_info);
+        let time_until = resolve_time(Some(0), Some(0), Some(DeviceState::PendingCharge))?;
 
-        insta::assert_debug_snapshot!(this_should_be_error, @r###"
-        Err(
-            "both 'TimeToFull' and 'TimeToEmpty' is non-zero, this should never happen",
+        insta::assert_debug_snapshot!(time_until, @r###"
+        Some(
+            NotApplicable,
         )
         "###);
 
-        let mut batt_info = BatteryInfo::default();
-
-        handle_time("TimeToEmpty", 0, &mut batt_info)?;
-        let this_should_be_error = handle_time("TimeToFull", 0, &mut batt_info);
+        // rates are still being computed
+        let time_until = resolve_time(Some(0), Some(0), Some(DeviceState::Discharging))?;
 
-        insta::assert_debug_snapshot!(this_should_be_error, @r###"
-        Err(
-            "both 'TimeToFull' and 'TimeToEmpty' is zero, this should never happen",
+        insta::assert_debug_snapshot!(time_until, @r###"
+        Some(
+            Calculating,
         )
         "###);
 
-        let mut batt_info = BatteryInfo::default();
+        Ok(())
+    }
 
-        handle_time("TimeToEmpty", 1000, &mut batt_info)?;
-        let this_should_be_error = handle_time("TimeToEmpty", 1893, &mut batt_info);
+    #[test]
+    fn test_resolve_time_both_non_zero() -> anyhow::Result<()> {
+        let time_until = resolve_time(Some(999), Some(1000), Some(DeviceState::Discharging))?;
 
-        insta::assert_debug_snapshot!(this_should_be_error, @r###"
-        Err(
-            "encountered a time key that's already been encountered, this should never happen",
+        insta::assert_debug_snapshot!(time_until, @r###"
+        Some(
+            Empty(
+                999s,
+            ),
         )
         "###);
 
-        let mut batt_info = BatteryInfo::default();
+        let time_until = resolve_time(Some(999), Some(1000), Some(DeviceState::Charging))?;
+
+        insta::assert_debug_snapshot!(time_until, @r###"
+        Some(
+            Full(
+                1000s,
+            ),
+        )
+        "###);
+
+        let time_until = resolve_time(Some(999), Some(1000), None)?;
+
+        insta::assert_debug_snapshot!(time_until, @r###"
+        Some(
+            Calculating,
+        )
+        "###);
 
-        handle_time("TimeToFull", 1000, &mut batt_info)?;
-        let this_should_be_error = handle_time("TimeToFull", 1893, &mut batt_info);
+        let this_should_be_error = resolve_time(Some(-1), Some(0), None);
 
         insta::assert_debug_snapshot!(this_should_be_error, @r###"
         Err(
-            "encountered a time key that's already been encountered, this should never happen",
+            "encountered a negative time estimate: -1s",
         )
         "###);
 
@@ -0,0 +1,821 @@
+use std::{
+    collections::HashMap,
+    env,
+    fs,
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{
+    anyhow,
+    bail,
+    Context,
+};
+use serde::Deserialize;
+use toml::{
+    Table,
+    Value,
+};
+use zbus::blocking::Connection;
+
+use crate::{
+    aggregate::{
+        AggregationPolicy,
+        Aggregator,
+    },
+    battery_info::event::{
+        BatteryEvent,
+        LevelThresholds,
+    },
+    glyph::{
+        ColorMode,
+        GlyphRenderer,
+        GlyphSet,
+    },
+    hooks::HookRunner,
+    logind::{
+        CriticalAction,
+        CriticalWatcher,
+    },
+};
+
+/// Prefix of the environment variables that override configuration keys, nested keys are
+/// separated by a double underscore, e.g. `LOW_VOLTAGE_THRESHOLDS__LOW=15`.
+pub const ENV_PREFIX: &str = "LOW_VOLTAGE_";
+
+const FILE_NAME: &str = "low-voltage/config.toml";
+
+/// Every key of [`Config`], below `hooks.on` any [`BatteryEvent`] is a key as well.
+const KEYS: &[&str] = &[
+    "backend",
+    "device",
+    "format",
+    "aggregation.policy",
+    "aggregation.primary",
+    "glyph.style",
+    "glyph.color",
+    "glyph.width",
+    "thresholds.low",
+    "thresholds.critical",
+    "critical.actions",
+    "critical.grace_period",
+    "critical.max_inhibitor_delay",
+    "critical.dry_run",
+    "hooks.timeout",
+    "hooks.debounce",
+    "hooks.on",
+];
+
+/// Settings for `low-voltage`, every key is optional.
+///
+/// ```toml
+/// # "upower", "sysfs", "acpi", or "auto" for the first of them that answers
+/// backend = "auto"
+/// # "display" for the aggregate of the batteries, "all", or a device id such as "battery_BAT0"
+/// device = "display"
+/// # "text", "glyph" or "openmetrics"
+/// format = "text"
+///
+/// [aggregation]
+/// # how "display" combines several batteries, "energy-weighted", "min", "max" or
+/// # "primary-only", unset to use UPower's display device
+/// policy = "min"
+/// # the battery "primary-only" reports, the first one by default
+/// primary = "BAT0"
+///
+/// [glyph]
+/// # "nerd-font", "unicode" or "ascii"
+/// style = "unicode"
+/// # "none", "ansi256" or "truecolor"
+/// color = "none"
+/// # cells in the ascii bar
+/// width = 10
+///
+/// [thresholds]
+/// # percentages at which level events fire and hooks run while discharging, on top of
+/// # UPower's warning level, critical must be below low and also triggers [critical]
+/// low = 20.0
+/// critical = 5.0
+///
+/// [critical]
+/// # tried in order, "suspend", "hibernate", "hybrid-sleep" or "poweroff"
+/// actions = ["hibernate", "suspend"]
+/// # seconds
+/// grace_period = 60
+/// max_inhibitor_delay = 300
+/// dry_run = false
+///
+/// [hooks]
+/// # seconds
+/// timeout = 30
+/// debounce = 5
+///
+/// # commands per event, see `BatteryEvent` for the names
+/// [hooks.on]
+/// ac_unplugged = ["brightnessctl set 40%"]
+/// level_critical = ["notify-send -u critical 'Battery critical'"]
+/// ups_on_battery = ["wall 'Running on UPS battery'"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub backend: Backend,
+    pub device: DeviceSelection,
+    pub aggregation: AggregationConfig,
+    pub format: OutputFormat,
+    pub glyph: GlyphConfig,
+    pub thresholds: Thresholds,
+    pub critical: CriticalConfig,
+    pub hooks: HooksConfig,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// UPower, falling back to sysfs and then ACPI when it doesn't answer
+    #[default]
+    Auto,
+    UPower,
+    /// The kernel's power supply class, without UPower
+    Sysfs,
+    /// The `/proc/acpi/battery` files of old kernels
+    Acpi,
+}
+
+/// Which devices to report on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub enum DeviceSelection {
+    /// The aggregate of every battery powering the system
+    #[default]
+    Display,
+    All,
+    /// A device id, or its last path segment, e.g. `battery_BAT0`
+    Id(String),
+}
+
+impl DeviceSelection {
+    /// Whether the device `id` is selected, always false for [`DeviceSelection::Display`].
+    pub fn matches(&self, id: &str) -> bool {
+        match self {
+            DeviceSelection::Display => false,
+            DeviceSelection::All => true,
+            DeviceSelection::Id(selected) => {
+                id == selected || id.rsplit('/').next() == Some(selected.as_str())
+            }
+        }
+    }
+}
+
+impl From<String> for DeviceSelection {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "display" => DeviceSelection::Display,
+            "all" => DeviceSelection::All,
+            _ => DeviceSelection::Id(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AggregationConfig {
+    /// `None` to use the display device of the backend, if it has one
+    pub policy: Option<AggregationPolicy>,
+    pub primary: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Glyph,
+    OpenMetrics,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GlyphStyle {
+    NerdFont,
+    #[default]
+    Unicode,
+    Ascii,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GlyphConfig {
+    pub style: GlyphStyle,
+    pub color: ColorMode,
+    pub width: usize,
+}
+
+impl Default for GlyphConfig {
+    fn default() -> Self {
+        Self {
+            style: GlyphStyle::default(),
+            color: ColorMode::None,
+            width: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Thresholds {
+    pub low: f64,
+    pub critical: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            low: 20.0,
+            critical: 5.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CriticalConfig {
+    pub actions: Vec<CriticalAction>,
+    /// Seconds
+    pub grace_period: u64,
+    /// Seconds
+    pub max_inhibitor_delay: u64,
+    pub dry_run: bool,
+}
+
+impl Default for CriticalConfig {
+    fn default() -> Self {
+        Self {
+            actions: vec![CriticalAction::Hibernate, CriticalAction::Suspend],
+            grace_period: 60,
+            max_inhibitor_delay: 300,
+            dry_run: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Seconds
+    pub timeout: u64,
+    /// Seconds
+    pub debounce: u64,
+    pub on: HashMap<BatteryEvent, Vec<String>>,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            timeout: 30,
+            debounce: 5,
+            on: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// The configuration from the standard locations, see [`ConfigLoader::standard`].
+    pub fn load() -> anyhow::Result<Self> {
+        ConfigLoader::standard().load()
+    }
+
+    /// Energy-weighted unless another policy is configured.
+    pub fn aggregator(&self) -> Aggregator {
+        let aggregator = Aggregator::new(self.aggregation.policy.unwrap_or_default());
+
+        match &self.aggregation.primary {
+            Some(primary) => aggregator.with_primary(primary),
+            None => aggregator,
+        }
+    }
+
+    pub fn glyph_renderer(&self) -> GlyphRenderer {
+        let glyphs = match self.glyph.style {
+            GlyphStyle::NerdFont => GlyphSet::nerd_font(),
+            GlyphStyle::Unicode => GlyphSet::unicode(),
+            GlyphStyle::Ascii => GlyphSet::ascii(self.glyph.width),
+        };
+
+        GlyphRenderer::new(glyphs).with_color_mode(self.glyph.color)
+    }
+
+    pub fn level_thresholds(&self) -> LevelThresholds {
+        LevelThresholds {
+            low: self.thresholds.low,
+            critical: self.thresholds.critical,
+        }
+    }
+
+    pub fn critical_watcher(&self, connection: &Connection) -> anyhow::Result<CriticalWatcher> {
+        Ok(CriticalWatcher::new(connection)?
+            .with_actions(self.critical.actions.clone())
+            .with_percentage(self.thresholds.critical)
+            .with_grace_period(Duration::from_secs(self.critical.grace_period))
+            .with_max_inhibitor_delay(Duration::from_secs(self.critical.max_inhibitor_delay))
+            .with_dry_run(self.critical.dry_run))
+    }
+
+    pub fn hook_runner(&self) -> HookRunner {
+        let mut runner = HookRunner::new()
+            .with_timeout(Duration::from_secs(self.hooks.timeout))
+            .with_debounce(Duration::from_secs(self.hooks.debounce))
+            .with_level_thresholds(self.level_thresholds());
+
+        for event in BatteryEvent::ALL {
+            for command in self.hooks.on.get(&event).into_iter().flatten() {
+                runner = runner.with_hook(event, command);
+            }
+        }
+
+        runner
+    }
+
+    /// Checks that can't be expressed in the types, `origin` names where a key was set.
+    fn validate(&self, origin: impl Fn(&str) -> String) -> anyhow::Result<()> {
+        let invalid =
+            |key: &str, message: String| anyhow!("{} ({}): {}", key, origin(key), message);
+
+        for (key, value) in [
+            ("thresholds.low", self.thresholds.low),
+            ("thresholds.critical", self.thresholds.critical),
+        ] {
+            if !(0.0..=100.0).contains(&value) {
+                return Err(invalid(key, format!("{} is not between 0 and 100", value)));
+            }
+        }
+
+        if self.thresholds.critical >= self.thresholds.low {
+            return Err(invalid(
+                "thresholds.critical",
+                format!(
+                    "{} must be below thresholds.low, {}",
+                    self.thresholds.critical, self.thresholds.low
+                ),
+            ));
+        }
+
+        if self.critical.actions.is_empty() {
+            return Err(invalid(
+                "critical.actions",
+                "at least one action is needed".into(),
+            ));
+        }
+
+        if self.glyph.width == 0 {
+            return Err(invalid("glyph.width", "must be at least 1".into()));
+        }
+
+        if self.hooks.timeout == 0 {
+            return Err(invalid("hooks.timeout", "must be at least 1 second".into()));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Layer {
+    File(PathBuf),
+    Text {
+        origin: String,
+        text: String,
+    },
+    Value {
+        origin: String,
+        key: String,
+        value: String,
+    },
+}
+
+/// Builds a [`Config`] from layers, each overriding the keys set by those before it.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLoader {
+    layers: Vec<Layer>,
+    ignored: Vec<String>,
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defaults, then the system files, then the user file, then the environment.
+    pub fn standard() -> Self {
+        let mut loader = Self::new();
+
+        for path in system_config_paths() {
+            loader = loader.with_file(path);
+        }
+
+        if let Some(path) = user_config_path() {
+            loader = loader.with_file(path);
+        }
+
+        loader.with_env(env::vars())
+    }
+
+    /// Add a TOML file, skipped if it doesn't exist.
+    pub fn with_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.layers.push(Layer::File(path.into()));
+        self
+    }
+
+    /// Add TOML `text`, `origin` names it in errors.
+    pub fn with_str(mut self, origin: impl Into<String>, text: impl Into<String>) -> Self {
+        self.layers.push(Layer::Text {
+            origin: origin.into(),
+            text: text.into(),
+        });
+        self
+    }
+
+    /// Add every variable starting with [`ENV_PREFIX`]. Variables that don't name a key are
+    /// skipped, e.g. one meant for another version, see [`ConfigLoader::ignored`].
+    pub fn with_env(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut vars = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect::<Vec<_>>();
+        vars.sort();
+
+        for (name, value) in vars {
+            let key = name[ENV_PREFIX.len()..].to_lowercase().replace("__", ".");
+
+            if !known_key(&key) {
+                self.ignored.push(name);
+                continue;
+            }
+
+            self.layers.push(Layer::Value {
+                origin: name,
+                key,
+                value,
+            });
+        }
+
+        self
+    }
+
+    /// The environment variables [`ConfigLoader::with_env`] skipped as they don't name a key.
+    pub fn ignored(&self) -> &[String] {
+        &self.ignored
+    }
+
+    /// Set the dotted `key`, e.g. from a command line flag. `value` is read as a TOML value,
+    /// falling back to a plain string.
+    pub fn with_override(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.layers.push(Layer::Value {
+            origin: "command line".to_owned(),
+            key: key.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn load(&self) -> anyhow::Result<Config> {
+        let mut merged = Table::new();
+        let mut origins = HashMap::new();
+
+        for layer in &self.layers {
+            if let Some((origin, table)) = layer.table()? {
+                merge(&mut merged, table, "", &origin, &mut origins);
+            }
+        }
+
+        let config = Config::deserialize(Value::Table(merged))?;
+
+        config.validate(|key| {
+            // the closest enclosing key that was set, e.g. `hooks.on` for `hooks.on.ac_plugged`
+            let mut key = key;
+            loop {
+                if let Some(origin) = origins.get(key) {
+                    return origin.clone();
+                }
+
+                match key.rsplit_once('.') {
+                    Some((parent, _)) => key = parent,
+                    None => return "default".to_owned(),
+                }
+            }
+        })?;
+
+        Ok(config)
+    }
+}
+
+impl Layer {
+    /// The keys this layer sets, validated against the schema on their own so errors point at
+    /// the layer they came from.
+    fn table(&self) -> anyhow::Result<Option<(String, Table)>> {
+        match self {
+            Layer::File(path) => {
+                if !path.exists() {
+                    return Ok(None);
+                }
+
+                let text = fs::read_to_string(path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+
+                Ok(Some((
+                    path.display().to_string(),
+                    parse_text(&text).with_context(|| format!("invalid {}", path.display()))?,
+                )))
+            }
+            Layer::Text { origin, text } => Ok(Some((
+                origin.clone(),
+                parse_text(text).with_context(|| format!("invalid {}", origin))?,
+            ))),
+            Layer::Value { origin, key, value } => {
+                let value = format!("value = {}", value)
+                    .parse::<Table>()
+                    .ok()
+                    .and_then(|mut table| table.remove("value"))
+                    .unwrap_or_else(|| Value::String(value.clone()));
+
+                let table = key.rsplit('.').fold(value, |value, part| {
+                    Value::Table(Table::from_iter([(part.to_owned(), value)]))
+                });
+
+                let Value::Table(table) = table else {
+                    unreachable!("folding over a key always produces a table")
+                };
+
+                if let Err(e) = Config::deserialize(Value::Table(table.clone())) {
+                    bail!("{} ({}): {}", key, origin, e.message());
+                }
+
+                Ok(Some((origin.clone(), table)))
+            }
+        }
+    }
+}
+
+/// Whether `key` names a key of [`Config`] or a table of them, e.g. `thresholds`.
+fn known_key(key: &str) -> bool {
+    if let Some(event) = key.strip_prefix("hooks.on.") {
+        return event.parse::<BatteryEvent>().is_ok();
+    }
+
+    KEYS.iter().any(|known| {
+        *known == key
+            || known
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+fn parse_text(text: &str) -> anyhow::Result<Table> {
+    // parsing straight into `Config` gives errors with the line and column of the offending key
+    toml::from_str::<Config>(text)?;
+
+    Ok(text.parse::<Table>()?)
+}
+
+fn merge(
+    into: &mut Table,
+    from: Table,
+    prefix: &str,
+    origin: &str,
+    origins: &mut HashMap<String, String>,
+) {
+    for (key, value) in from {
+        let path = match prefix {
+            "" => key.clone(),
+            prefix => format!("{}.{}", prefix, key),
+        };
+
+        match (into.get_mut(&key), value) {
+            (Some(Value::Table(into)), Value::Table(from)) => {
+                merge(into, from, &path, origin, origins)
+            }
+            (_, value) => {
+                origins.insert(path, origin.to_owned());
+                into.insert(key, value);
+            }
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/low-voltage/config.toml`, defaulting to `~/.config`.
+pub fn user_config_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join(FILE_NAME))
+}
+
+/// `low-voltage/config.toml` in each of `$XDG_CONFIG_DIRS`, defaulting to `/etc/xdg`, least
+/// important first.
+pub fn system_config_paths() -> Vec<PathBuf> {
+    let dirs = env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_owned());
+
+    let mut paths = env::split_paths(&dirs)
+        .map(|dir| dir.join(FILE_NAME))
+        .collect::<Vec<_>>();
+    paths.reverse();
+
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYSTEM: &str = r#"
+        backend = "upower"
+
+        [thresholds]
+        low = 25
+        critical = 8
+
+        [hooks.on]
+        ac_plugged = ["echo plugged"]
+    "#;
+
+    const USER: &str = r#"
+        device = "battery_BAT0"
+
+        [thresholds]
+        low = 15.5
+
+        [critical]
+        actions = ["suspend"]
+
+        [hooks.on]
+        level_low = ["echo low"]
+    "#;
+
+    #[test]
+    fn layering() -> anyhow::Result<()> {
+        let loader = ConfigLoader::new()
+            .with_str("system", SYSTEM)
+            .with_str("user", USER)
+            .with_env([
+                ("LOW_VOLTAGE_FORMAT".to_owned(), "glyph".to_owned()),
+                (
+                    "LOW_VOLTAGE_THRESHOLDS__CRITICAL".to_owned(),
+                    "3".to_owned(),
+                ),
+                ("HOME".to_owned(), "/home/user".to_owned()),
+                // for some other version, skipped
+                ("LOW_VOLTAGE_LOG_LEVEL".to_owned(), "debug".to_owned()),
+                ("LOW_VOLTAGE_THRESHOLDS__FULL".to_owned(), "80".to_owned()),
+            ])
+            .with_override("critical.dry_run", "true")
+            .with_override("format", "openmetrics");
+
+        insta::assert_debug_snapshot!(loader.ignored(), @r###"
+        [
+            "LOW_VOLTAGE_LOG_LEVEL",
+            "LOW_VOLTAGE_THRESHOLDS__FULL",
+        ]
+        "###);
+
+        let config = loader.load()?;
+
+        let mut hooks = config.hooks.on.iter().collect::<Vec<_>>();
+        hooks.sort_by_key(|(event, _)| event.to_string());
+
+        insta::assert_debug_snapshot!(hooks, @r###"
+        [
+            (
+                AcPlugged,
+                [
+                    "echo plugged",
+                ],
+            ),
+            (
+                LevelLow,
+                [
+                    "echo low",
+                ],
+            ),
+        ]
+        "###);
+
+        insta::assert_debug_snapshot!(
+            (
+                config.backend,
+                config.device,
+                config.format,
+                config.thresholds,
+                config.critical,
+            ),
+            @r###"
+        (
+            UPower,
+            Id(
+                "battery_BAT0",
+            ),
+            OpenMetrics,
+            Thresholds {
+                low: 15.5,
+                critical: 3.0,
+            },
+            CriticalConfig {
+                actions: [
+                    Suspend,
+                ],
+                grace_period: 60,
+                max_inhibitor_delay: 300,
+                dry_run: true,
+            },
+        )
+        "###
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn defaults() -> anyhow::Result<()> {
+        let config = ConfigLoader::new()
+            .with_file("/nonexistent/low-voltage/config.toml")
+            .load()?;
+
+        assert_eq!(config, Config::default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let error = |loader: ConfigLoader| format!("{:#}", loader.load().unwrap_err());
+
+        insta::assert_snapshot!(
+            error(ConfigLoader::new().with_str("user", "[thresholds]\nlow = \"high\"\n")),
+            @r###"
+        invalid user: TOML parse error at line 2, column 7
+          |
+        2 | low = "high"
+          |       ^^^^^^
+        invalid type: string "high", expected f64
+        "###
+        );
+
+        insta::assert_snapshot!(
+            error(ConfigLoader::new().with_str("user", "[hooks.on]\nac_pluged = []\n")),
+            @r###"
+        invalid user: TOML parse error at line 2, column 1
+          |
+        2 | ac_pluged = []
+          | ^^^^^^^^^
+        unknown battery event: "ac_pluged"
+        "###
+        );
+
+        insta::assert_snapshot!(
+            error(ConfigLoader::new().with_str("user", "batery = \"upower\"\n")),
+            @r###"
+        invalid user: TOML parse error at line 1, column 1
+          |
+        1 | batery = "upower"
+          | ^^^^^^
+        unknown field `batery`, expected one of `backend`, `device`, `aggregation`, `format`, `glyph`, `thresholds`, `critical`, `hooks`
+        "###
+        );
+
+        insta::assert_snapshot!(
+            error(ConfigLoader::new().with_override("critical.actions", "[\"nap\"]")),
+            @r###"critical.actions (command line): unknown critical action: "nap""###
+        );
+
+        insta::assert_snapshot!(
+            error(ConfigLoader::new().with_override("thresholds.full", "80")),
+            @"thresholds.full (command line): unknown field `full`, expected `low` or `critical`"
+        );
+
+        // a known key from the environment still has to be valid
+        insta::assert_snapshot!(
+            error(ConfigLoader::new().with_env([(
+                "LOW_VOLTAGE_HOOKS__ON__LEVEL_LOW".to_owned(),
+                "notify-send low".to_owned(),
+            )])),
+            @r###"hooks.on.level_low (LOW_VOLTAGE_HOOKS__ON__LEVEL_LOW): invalid type: string "notify-send low", expected a sequence"###
+        );
+
+        insta::assert_snapshot!(
+            error(ConfigLoader::new().with_override("aggregation.policy", "average")),
+            @r###"aggregation.policy (command line): unknown aggregation policy: "average""###
+        );
+
+        insta::assert_snapshot!(
+            error(
+                ConfigLoader::new()
+                    .with_str("system", SYSTEM)
+                    .with_env([("LOW_VOLTAGE_THRESHOLDS__LOW".to_owned(), "5".to_owned())])
+            ),
+            @"thresholds.critical (system): 8 must be below thresholds.low, 5"
+        );
+    }
+}
@@ -0,0 +1,395 @@
+use std::{
+    thread::sleep,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use zbus::{
+    block_on,
+    blocking::{
+        object_server::InterfaceRef,
+        Connection,
+    },
+    interface,
+    SignalContext,
+};
+
+use crate::battery_info::{
+    device_state::DeviceState,
+    event::{
+        BatteryEvent,
+        LevelThresholds,
+    },
+    time_until::TimeUntil,
+    BatteryInfo,
+};
+
+/// Well-known name the daemon owns on the session bus.
+pub const NAME: &str = "io.github.JoakimPaulsson.LowVoltage";
+
+pub const PATH: &str = "/io/github/JoakimPaulsson/LowVoltage";
+
+pub const INTERFACE: &str = "io.github.JoakimPaulsson.LowVoltage.Battery";
+
+/// Client side of the published state, properties are cached and kept up to date by signals.
+#[zbus::proxy(
+    interface = "io.github.JoakimPaulsson.LowVoltage.Battery",
+    default_service = "io.github.JoakimPaulsson.LowVoltage",
+    default_path = "/io/github/JoakimPaulsson/LowVoltage",
+    gen_async = false
+)]
+pub trait Battery {
+    #[zbus(property)]
+    fn percentage(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn state(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn warning_level(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn on_battery(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn time_to_empty(&self) -> zbus::Result<i64>;
+
+    #[zbus(property)]
+    fn time_to_full(&self) -> zbus::Result<i64>;
+
+    #[zbus(property)]
+    fn energy_rate(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn icon_name(&self) -> zbus::Result<String>;
+
+    /// A [`BatteryEvent`] by name, e.g. `ac_plugged`
+    #[zbus(signal)]
+    fn event(&self, name: &str) -> zbus::Result<()>;
+}
+
+/// What is published, unknown values are zero or empty like UPower does it.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Published {
+    percentage: f64,
+    state: String,
+    warning_level: String,
+    on_battery: bool,
+    time_to_empty: i64,
+    time_to_full: i64,
+    energy_rate: f64,
+    icon_name: String,
+}
+
+impl From<&BatteryInfo> for Published {
+    fn from(batt_info: &BatteryInfo) -> Self {
+        let (time_to_empty, time_to_full) = match batt_info.time_until {
+            Some(TimeUntil::Empty(d)) => (d.as_secs() as i64, 0),
+            Some(TimeUntil::Full(d)) => (0, d.as_secs() as i64),
+            _ => (0, 0),
+        };
+
+        Self {
+            percentage: batt_info.percentage.map(|p| *p).unwrap_or_default(),
+            state: batt_info
+                .device_state
+                .unwrap_or(DeviceState::Unknown)
+                .to_string(),
+            warning_level: batt_info
+                .warning_level
+                .map(|w| w.to_string())
+                .unwrap_or_default(),
+            on_battery: matches!(
+                batt_info.device_state,
+                Some(DeviceState::Discharging | DeviceState::PendingDischarge | DeviceState::Empty)
+            ),
+            time_to_empty,
+            time_to_full,
+            energy_rate: batt_info.energy_rate.map(|e| *e).unwrap_or_default(),
+            icon_name: batt_info
+                .icon_name_or_synthesized()
+                .map(|i| i.to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[interface(name = "io.github.JoakimPaulsson.LowVoltage.Battery")]
+impl Published {
+    #[zbus(property)]
+    fn percentage(&self) -> f64 {
+        self.percentage
+    }
+
+    #[zbus(property)]
+    fn state(&self) -> &str {
+        &self.state
+    }
+
+    #[zbus(property)]
+    fn warning_level(&self) -> &str {
+        &self.warning_level
+    }
+
+    #[zbus(property)]
+    fn on_battery(&self) -> bool {
+        self.on_battery
+    }
+
+    #[zbus(property)]
+    fn time_to_empty(&self) -> i64 {
+        self.time_to_empty
+    }
+
+    #[zbus(property)]
+    fn time_to_full(&self) -> i64 {
+        self.time_to_full
+    }
+
+    #[zbus(property)]
+    fn energy_rate(&self) -> f64 {
+        self.energy_rate
+    }
+
+    #[zbus(property)]
+    fn icon_name(&self) -> &str {
+        &self.icon_name
+    }
+
+    #[zbus(signal)]
+    async fn event(ctxt: &SignalContext<'_>, name: &str) -> zbus::Result<()>;
+}
+
+/// Publishes one battery reading at a time on D-Bus, so clients don't each have to poll.
+///
+/// Changes to the state or an event are published right away, anything else, like the
+/// percentage or time estimates, at most once per debounce period.
+pub struct Daemon {
+    iface: InterfaceRef<Published>,
+    debounce: Duration,
+    thresholds: Option<LevelThresholds>,
+    last_published: Option<Instant>,
+    prev: Option<BatteryInfo>,
+}
+
+impl Daemon {
+    /// Serve at [`PATH`] on `connection`, without requesting [`NAME`]. An interface already
+    /// served there is taken over.
+    pub fn new(connection: &Connection) -> anyhow::Result<Self> {
+        let object_server = connection.object_server();
+        object_server.at(PATH, Published::default())?;
+
+        Ok(Self {
+            iface: object_server.interface::<_, Published>(PATH)?,
+            debounce: Duration::from_secs(5),
+            thresholds: None,
+            last_published: None,
+            prev: None,
+        })
+    }
+
+    /// Serve on the session bus under [`NAME`].
+    pub fn session() -> anyhow::Result<Self> {
+        let connection = Connection::session()?;
+        let daemon = Self::new(&connection)?;
+        connection.request_name(NAME)?;
+
+        Ok(daemon)
+    }
+
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Publish level events once the percentage crosses `thresholds`, not only when UPower's
+    /// warning level changes.
+    pub fn with_level_thresholds(mut self, thresholds: LevelThresholds) -> Self {
+        self.thresholds = Some(thresholds);
+        self
+    }
+
+    /// Publish `batt_info` if it changed enough, returns whether anything was published.
+    pub fn update(&mut self, batt_info: BatteryInfo, now: Instant) -> anyhow::Result<bool> {
+        let next = Published::from(&batt_info);
+
+        let events = match &self.prev {
+            Some(prev) => BatteryEvent::detect_with_thresholds(prev, &batt_info, self.thresholds),
+            None => vec![],
+        };
+        self.prev = Some(batt_info);
+
+        let current = self.iface.get().clone();
+
+        let urgent = self.last_published.is_none()
+            || !events.is_empty()
+            || next.state != current.state
+            || next.on_battery != current.on_battery;
+
+        let due = self
+            .last_published
+            .is_some_and(|last| now.saturating_duration_since(last) >= self.debounce);
+
+        if next == current || !(urgent || due) {
+            return Ok(false);
+        }
+
+        *self.iface.get_mut() = next.clone();
+        self.last_published = Some(now);
+
+        self.notify(&current, &next)?;
+
+        let ctxt = self.iface.signal_context();
+        for event in events {
+            block_on(Published::event(ctxt, &event.to_string()))?;
+        }
+
+        Ok(true)
+    }
+
+    /// Read every `interval` and publish, until publishing fails.
+    pub fn run(
+        &mut self,
+        interval: Duration,
+        mut read: impl FnMut() -> anyhow::Result<BatteryInfo>,
+    ) -> anyhow::Result<()> {
+        loop {
+            if let Ok(batt_info) = read() {
+                self.update(batt_info, Instant::now())?;
+            }
+
+            sleep(interval);
+        }
+    }
+
+    /// Emit `PropertiesChanged` for what differs between `old` and `new`.
+    fn notify(&self, old: &Published, new: &Published) -> zbus::Result<()> {
+        let iface = self.iface.get();
+        let ctxt = self.iface.signal_context();
+
+        block_on(async {
+            if old.percentage != new.percentage {
+                iface.percentage_changed(ctxt).await?;
+            }
+            if old.state != new.state {
+                iface.state_changed(ctxt).await?;
+            }
+            if old.warning_level != new.warning_level {
+                iface.warning_level_changed(ctxt).await?;
+            }
+            if old.on_battery != new.on_battery {
+                iface.on_battery_changed(ctxt).await?;
+            }
+            if old.time_to_empty != new.time_to_empty {
+                iface.time_to_empty_changed(ctxt).await?;
+            }
+            if old.time_to_full != new.time_to_full {
+                iface.time_to_full_changed(ctxt).await?;
+            }
+            if old.energy_rate != new.energy_rate {
+                iface.energy_rate_changed(ctxt).await?;
+            }
+            if old.icon_name != new.icon_name {
+                iface.icon_name_changed(ctxt).await?;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        battery_info::{
+            warning_level::WarningLevel,
+            BatteryInfoProperties,
+        },
+        test_utils::{
+            self,
+            p2p_connections,
+        },
+    };
+
+    fn batt_info(percentage: f64, state: DeviceState) -> BatteryInfo {
+        let mut batt_info = test_utils::reading(percentage, state, WarningLevel::NoWarning);
+
+        batt_info.set_propertry(BatteryInfoProperties::TimeUntil(match state {
+            DeviceState::Charging => TimeUntil::Full(Duration::from_secs(1800)),
+            _ => TimeUntil::Empty(Duration::from_secs(3600)),
+        }));
+
+        batt_info
+    }
+
+    #[test]
+    fn publish() -> anyhow::Result<()> {
+        // serving from the start, an object server started after the handshake can miss the
+        // first calls, `Daemon::new` picks the existing interface up
+        let (server, client) =
+            p2p_connections(|builder| builder.serve_at(PATH, Published::default()))?;
+
+        let mut daemon = Daemon::new(&server)?.with_debounce(Duration::from_secs(10));
+        let proxy = BatteryProxy::builder(&client)
+            .cache_properties(zbus::CacheProperties::No)
+            .build()?;
+        // a proxy's signal stream asks the bus who owns the name, there is no bus here
+        let rule = zbus::MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(INTERFACE)?
+            .member("Event")?
+            .build();
+        let mut events = zbus::blocking::MessageIterator::for_match_rule(rule, &client, None)?;
+
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let published = [
+            daemon.update(batt_info(80.0, DeviceState::Discharging), at(0))?,
+            // within the debounce period
+            daemon.update(batt_info(79.0, DeviceState::Discharging), at(2))?,
+            daemon.update(batt_info(78.0, DeviceState::Discharging), at(12))?,
+            // unchanged
+            daemon.update(batt_info(78.0, DeviceState::Discharging), at(30))?,
+            // plugged in, published right away
+            daemon.update(batt_info(78.0, DeviceState::Charging), at(31))?,
+        ];
+
+        insta::assert_debug_snapshot!(published, @r###"
+        [
+            true,
+            false,
+            true,
+            false,
+            true,
+        ]
+        "###);
+
+        insta::assert_debug_snapshot!(
+            (
+                proxy.percentage()?,
+                proxy.state()?,
+                proxy.on_battery()?,
+                proxy.time_to_empty()?,
+                proxy.icon_name()?,
+            ),
+            @r###"
+        (
+            78.0,
+            "charging",
+            false,
+            0,
+            "battery-level-80-charging-symbolic",
+        )
+        "###
+        );
+
+        let event = events.next().expect("an event signal")?;
+        insta::assert_debug_snapshot!(event.body().deserialize::<&str>()?, @r###""ac_plugged""###);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+use serde::Deserialize;
+
+use crate::battery_info::{
+    battery_level::BatteryLevel,
+    device_state::DeviceState,
+    warning_level::WarningLevel,
+    BatteryInfo,
+};
+
+/// How the charge is drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GlyphSet {
+    /// One glyph per charge step, the percentage picks a glyph evenly across each list
+    Icons {
+        discharging: Vec<String>,
+        charging: Vec<String>,
+        unknown: String,
+    },
+    /// A bar of `width` cells, e.g. `[####      ]`
+    Bar {
+        width: usize,
+        filled: char,
+        empty: char,
+        left: String,
+        right: String,
+    },
+}
+
+impl GlyphSet {
+    /// Nerd Font Material Design battery icons in steps of ten percent.
+    pub fn nerd_font() -> Self {
+        let glyphs = |codepoints: [u32; 11]| {
+            codepoints
+                .iter()
+                .filter_map(|c| char::from_u32(*c))
+                .map(String::from)
+                .collect()
+        };
+
+        Self::Icons {
+            discharging: glyphs([
+                0xF008E, 0xF007A, 0xF007B, 0xF007C, 0xF007D, 0xF007E, 0xF007F, 0xF0080, 0xF0081,
+                0xF0082, 0xF0079,
+            ]),
+            charging: glyphs([
+                0xF089F, 0xF089C, 0xF0086, 0xF0087, 0xF0088, 0xF089D, 0xF0089, 0xF089E, 0xF008A,
+                0xF008B, 0xF0085,
+            ]),
+            unknown: "\u{F0091}".to_owned(),
+        }
+    }
+
+    /// Unicode block elements, `▁` to `█`, with a lightning bolt appended while charging.
+    pub fn unicode() -> Self {
+        let blocks = ["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+
+        Self::Icons {
+            discharging: blocks.map(String::from).to_vec(),
+            charging: blocks.map(|b| format!("{}⚡", b)).to_vec(),
+            unknown: "?".to_owned(),
+        }
+    }
+
+    /// A plain ASCII bar, `[#####     ]`.
+    pub fn ascii(width: usize) -> Self {
+        Self::Bar {
+            width,
+            filled: '#',
+            empty: ' ',
+            left: "[".to_owned(),
+            right: "]".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    None,
+    Ansi256,
+    TrueColor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Closest color in the 6x6x6 cube of the 256 color palette.
+    pub fn to_ansi256(self) -> u8 {
+        let step = |c: u8| (c as u16 * 5 + 127) / 255;
+        (16 + 36 * step(self.0) + 6 * step(self.1) + step(self.2)) as u8
+    }
+}
+
+/// Colors for each [`Severity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub normal: Rgb,
+    pub charging: Rgb,
+    pub low: Rgb,
+    pub critical: Rgb,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            normal: Rgb(0x5f, 0xd7, 0x00),
+            charging: Rgb(0x00, 0xaf, 0xff),
+            low: Rgb(0xff, 0xaf, 0x00),
+            critical: Rgb(0xff, 0x00, 0x00),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Normal,
+    Charging,
+    Low,
+    Critical,
+}
+
+impl Severity {
+    /// Prefers UPower's [`WarningLevel`] and falls back to the coarse [`BatteryLevel`].
+    pub fn of(batt_info: &BatteryInfo) -> Self {
+        let warning = match batt_info.warning_level {
+            Some(WarningLevel::Critical | WarningLevel::Action) => Some(Self::Critical),
+            Some(WarningLevel::Low) => Some(Self::Low),
+            _ => None,
+        };
+
+        let level = match batt_info.battery_level {
+            Some(BatteryLevel::Critical) => Some(Self::Critical),
+            Some(BatteryLevel::Low) => Some(Self::Low),
+            _ => None,
+        };
+
+        let charging = match batt_info.device_state {
+            Some(DeviceState::Charging | DeviceState::FullyCharged) => Some(Self::Charging),
+            _ => None,
+        };
+
+        warning.or(level).or(charging).unwrap_or(Self::Normal)
+    }
+}
+
+/// Renders a [`BatteryInfo`] as a short, optionally colored, string for prompts and status lines.
+#[derive(Debug, Clone)]
+pub struct GlyphRenderer {
+    glyphs: GlyphSet,
+    color_mode: ColorMode,
+    palette: Palette,
+    show_percentage: bool,
+}
+
+impl GlyphRenderer {
+    pub fn new(glyphs: GlyphSet) -> Self {
+        Self {
+            glyphs,
+            color_mode: ColorMode::None,
+            palette: Palette::default(),
+            show_percentage: true,
+        }
+    }
+
+    pub fn with_color_mode(mut self, color_mode: ColorMode) -> Self {
+        self.color_mode = color_mode;
+        self
+    }
+
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    pub fn with_percentage(mut self, show_percentage: bool) -> Self {
+        self.show_percentage = show_percentage;
+        self
+    }
+
+    pub fn render(&self, batt_info: &BatteryInfo) -> String {
+        let percentage = batt_info.percentage.map(|p| (*p).clamp(0.0, 100.0));
+
+        let charging = matches!(
+            batt_info.device_state,
+            Some(DeviceState::Charging | DeviceState::PendingCharge)
+        );
+
+        let mut output = self.glyph(percentage, charging);
+
+        if let (true, Some(percentage)) = (self.show_percentage, percentage) {
+            output.push_str(&format!(" {:.0}%", percentage));
+        }
+
+        let color = match Severity::of(batt_info) {
+            Severity::Normal => self.palette.normal,
+            Severity::Charging => self.palette.charging,
+            Severity::Low => self.palette.low,
+            Severity::Critical => self.palette.critical,
+        };
+
+        match self.color_mode {
+            ColorMode::None => output,
+            ColorMode::Ansi256 => format!("\x1b[38;5;{}m{}\x1b[0m", color.to_ansi256(), output),
+            ColorMode::TrueColor => format!(
+                "\x1b[38;2;{};{};{}m{}\x1b[0m",
+                color.0, color.1, color.2, output
+            ),
+        }
+    }
+
+    fn glyph(&self, percentage: Option<f64>, charging: bool) -> String {
+        match &self.glyphs {
+            GlyphSet::Icons {
+                discharging,
+                charging: charging_glyphs,
+                unknown,
+            } => {
+                let glyphs = if charging {
+                    charging_glyphs
+                } else {
+                    discharging
+                };
+
+                match (percentage, glyphs.len()) {
+                    (Some(percentage), len) if len > 0 => {
+                        let index = (percentage / 100.0 * (len - 1) as f64).round() as usize;
+                        glyphs[index.min(len - 1)].clone()
+                    }
+                    _ => unknown.clone(),
+                }
+            }
+            GlyphSet::Bar {
+                width,
+                filled,
+                empty,
+                left,
+                right,
+            } => {
+                let cells = (percentage.unwrap_or(0.0) / 100.0 * *width as f64).round() as usize;
+                let cells = cells.min(*width);
+
+                format!(
+                    "{}{}{}{}",
+                    left,
+                    filled.to_string().repeat(cells),
+                    empty.to_string().repeat(width - cells),
+                    right
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::reading;
+
+    #[test]
+    fn render_glyphs() {
+        let discharging = reading(42.0, DeviceState::Discharging, WarningLevel::NoWarning);
+        let charging = reading(97.0, DeviceState::Charging, WarningLevel::NoWarning);
+
+        let rendered = [
+            GlyphRenderer::new(GlyphSet::ascii(10)).render(&discharging),
+            GlyphRenderer::new(GlyphSet::ascii(10))
+                .with_percentage(false)
+                .render(&charging),
+            GlyphRenderer::new(GlyphSet::unicode()).render(&discharging),
+            GlyphRenderer::new(GlyphSet::unicode()).render(&charging),
+            GlyphRenderer::new(GlyphSet::nerd_font()).render(&discharging),
+            GlyphRenderer::new(GlyphSet::nerd_font()).render(&BatteryInfo::new()),
+        ];
+
+        insta::assert_debug_snapshot!(rendered, @r###"
+        [
+            "[####      ] 42%",
+            "[##########]",
+            "▄ 42%",
+            "█⚡ 97%",
+            "\u{f007d} 42%",
+            "\u{f0091}",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn render_colors() {
+        let low = reading(12.0, DeviceState::Discharging, WarningLevel::Low);
+        let critical = reading(3.0, DeviceState::Discharging, WarningLevel::Action);
+        let charging = reading(60.0, DeviceState::Charging, WarningLevel::NoWarning);
+
+        let renderer = GlyphRenderer::new(GlyphSet::ascii(4)).with_color_mode(ColorMode::Ansi256);
+
+        let rendered = [
+            renderer.render(&low),
+            renderer.render(&critical),
+            renderer
+                .clone()
+                .with_color_mode(ColorMode::TrueColor)
+                .render(&charging),
+        ];
+
+        insta::assert_debug_snapshot!(rendered, @r###"
+        [
+            "\u{1b}[38;5;214m[    ] 12%\u{1b}[0m",
+            "\u{1b}[38;5;196m[    ] 3%\u{1b}[0m",
+            "\u{1b}[38;2;0;175;255m[##  ] 60%\u{1b}[0m",
+        ]
+        "###);
+    }
+}
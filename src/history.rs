@@ -0,0 +1,581 @@
+use std::{
+    fmt,
+    fs::{
+        self,
+        File,
+        OpenOptions,
+    },
+    io::{
+        BufRead,
+        BufReader,
+        Read,
+        Seek,
+        SeekFrom,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    thread::sleep,
+    time::{
+        Duration,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+use anyhow::bail;
+
+use crate::battery_info::{
+    battery_level::BatteryLevel,
+    device_state::DeviceState,
+    time_until::TimeUntil,
+    warning_level::WarningLevel,
+    BatteryInfo,
+    BatteryInfoProperties,
+};
+
+pub mod session;
+
+const HEADER: &str = "timestamp_ms,state,percentage,energy,energy_full,energy_rate,time_until,\
+                      warning_level,battery_level";
+
+/// The part of a [`BatteryInfo`] that is recorded, together with when it was read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub timestamp: SystemTime,
+    pub device_state: Option<DeviceState>,
+    pub percentage: Option<f64>,
+    /// Wh
+    pub energy: Option<f64>,
+    /// Wh
+    pub energy_full: Option<f64>,
+    /// W
+    pub energy_rate: Option<f64>,
+    pub time_until: Option<TimeUntil>,
+    pub warning_level: Option<WarningLevel>,
+    pub battery_level: Option<BatteryLevel>,
+}
+
+impl Snapshot {
+    pub fn new(timestamp: SystemTime, batt_info: &BatteryInfo) -> Self {
+        Self {
+            timestamp,
+            device_state: batt_info.device_state,
+            percentage: batt_info.percentage.map(|p| *p),
+            energy: batt_info.energy.map(|e| *e),
+            energy_full: batt_info.energy_full.map(|e| *e),
+            energy_rate: batt_info.energy_rate.map(|e| *e),
+            time_until: batt_info.time_until,
+            warning_level: batt_info.warning_level,
+            battery_level: batt_info.battery_level,
+        }
+    }
+
+    /// Rebuild the recorded part of the [`BatteryInfo`] this snapshot was taken from.
+    pub fn battery_info(&self) -> BatteryInfo {
+        let mut batt_info = BatteryInfo::new();
+
+        [
+            self.device_state.map(BatteryInfoProperties::DeviceState),
+            self.percentage
+                .map(|p| BatteryInfoProperties::Percentage(p.into())),
+            self.energy.map(|e| BatteryInfoProperties::Energy(e.into())),
+            self.energy_full
+                .map(|e| BatteryInfoProperties::EnergyFull(e.into())),
+            self.energy_rate
+                .map(|e| BatteryInfoProperties::EnergyRate(e.into())),
+            self.time_until.map(BatteryInfoProperties::TimeUntil),
+            self.warning_level.map(BatteryInfoProperties::WarningLevel),
+            self.battery_level.map(BatteryInfoProperties::BatteryLevel),
+        ]
+        .into_iter()
+        .flatten()
+        .for_each(|prop| batt_info.set_propertry(prop));
+
+        batt_info
+    }
+
+    pub fn to_csv(&self) -> String {
+        let opt = |v: Option<String>| v.unwrap_or_default();
+
+        let timestamp = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        [
+            timestamp.to_string(),
+            opt(self.device_state.map(|s| s.to_string())),
+            opt(self.percentage.map(|p| p.to_string())),
+            opt(self.energy.map(|e| e.to_string())),
+            opt(self.energy_full.map(|e| e.to_string())),
+            opt(self.energy_rate.map(|e| e.to_string())),
+            opt(self.time_until.map(time_until_to_field)),
+            opt(self.warning_level.map(|w| w.to_string())),
+            opt(self.battery_level.map(|b| b.to_string())),
+        ]
+        .join(",")
+    }
+
+    pub fn from_csv(line: &str) -> anyhow::Result<Self> {
+        let fields = line.trim_end().split(',').collect::<Vec<_>>();
+
+        let [timestamp, state, percentage, energy, energy_full, energy_rate, time_until, warning_level, battery_level] =
+            fields[..]
+        else {
+            bail!("expected 9 fields, got {}: {:?}", fields.len(), line);
+        };
+
+        fn opt<T>(
+            field: &str,
+            parse: impl FnOnce(&str) -> anyhow::Result<T>,
+        ) -> anyhow::Result<Option<T>> {
+            match field {
+                "" => Ok(None),
+                field => parse(field).map(Some),
+            }
+        }
+
+        Ok(Self {
+            timestamp: UNIX_EPOCH + Duration::from_millis(timestamp.parse()?),
+            device_state: opt(state, |s| s.parse())?,
+            percentage: opt(percentage, |s| Ok(s.parse()?))?,
+            energy: opt(energy, |s| Ok(s.parse()?))?,
+            energy_full: opt(energy_full, |s| Ok(s.parse()?))?,
+            energy_rate: opt(energy_rate, |s| Ok(s.parse()?))?,
+            time_until: opt(time_until, time_until_from_field)?,
+            warning_level: opt(warning_level, |s| s.parse())?,
+            battery_level: opt(battery_level, |s| s.parse())?,
+        })
+    }
+}
+
+pub(crate) fn time_until_to_field(time_until: TimeUntil) -> String {
+    match time_until {
+        TimeUntil::Full(d) => format!("full:{}", d.as_secs()),
+        TimeUntil::Empty(d) => format!("empty:{}", d.as_secs()),
+        TimeUntil::Unknown(d) => format!("unknown:{}", d.as_secs()),
+        TimeUntil::NotApplicable => "not-applicable".to_owned(),
+        TimeUntil::Calculating => "calculating".to_owned(),
+    }
+}
+
+pub(crate) fn time_until_from_field(field: &str) -> anyhow::Result<TimeUntil> {
+    let secs = |s: &str| -> anyhow::Result<Duration> { Ok(Duration::from_secs(s.parse()?)) };
+
+    Ok(match field.split_once(':') {
+        Some(("full", s)) => TimeUntil::Full(secs(s)?),
+        Some(("empty", s)) => TimeUntil::Empty(secs(s)?),
+        Some(("unknown", s)) => TimeUntil::Unknown(secs(s)?),
+        None if field == "not-applicable" => TimeUntil::NotApplicable,
+        None if field == "calculating" => TimeUntil::Calculating,
+        _ => bail!("unknown time until: {:?}", field),
+    })
+}
+
+/// When the log file is rotated and how many rotated files are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rotation {
+    pub max_file_bytes: u64,
+    pub max_files: usize,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// Appends snapshots as CSV to `<dir>/battery.csv`, rotating it to `battery.csv.1`,
+/// `battery.csv.2`, ... once it grows past [`Rotation::max_file_bytes`].
+#[derive(Debug)]
+pub struct HistoryWriter {
+    dir: PathBuf,
+    rotation: Rotation,
+    file: File,
+    size: u64,
+}
+
+impl HistoryWriter {
+    pub fn open(dir: impl Into<PathBuf>, rotation: Rotation) -> anyhow::Result<Self> {
+        let dir = dir.into();
+
+        if rotation.max_files == 0 {
+            bail!("rotation needs to keep at least one file");
+        }
+
+        fs::create_dir_all(&dir)?;
+
+        let (file, size) = open_current(&dir)?;
+
+        Ok(Self {
+            dir,
+            rotation,
+            file,
+            size,
+        })
+    }
+
+    pub fn append(&mut self, snapshot: &Snapshot) -> anyhow::Result<()> {
+        let line = format!("{}\n", snapshot.to_csv());
+
+        if self.size > HEADER.len() as u64 + 1
+            && self.size + line.len() as u64 > self.rotation.max_file_bytes
+        {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.file.flush()?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+
+    /// Record `batt_info` as read right now.
+    pub fn record(&mut self, batt_info: &BatteryInfo) -> anyhow::Result<()> {
+        self.append(&Snapshot::new(SystemTime::now(), batt_info))
+    }
+
+    /// Record a snapshot every `interval` until writing fails, failed reads are skipped.
+    pub fn poll(
+        &mut self,
+        interval: Duration,
+        mut read: impl FnMut() -> anyhow::Result<BatteryInfo>,
+    ) -> anyhow::Result<()> {
+        loop {
+            if let Ok(batt_info) = read() {
+                self.record(&batt_info)?;
+            }
+
+            sleep(interval);
+        }
+    }
+
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        let oldest = rotated_path(&self.dir, self.rotation.max_files - 1);
+        if self.rotation.max_files > 1 && oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+
+        for n in (0..self.rotation.max_files - 1).rev() {
+            let from = rotated_path(&self.dir, n);
+            if from.exists() {
+                fs::rename(from, rotated_path(&self.dir, n + 1))?;
+            }
+        }
+
+        if self.rotation.max_files == 1 {
+            fs::remove_file(rotated_path(&self.dir, 0))?;
+        }
+
+        (self.file, self.size) = open_current(&self.dir)?;
+
+        Ok(())
+    }
+}
+
+fn rotated_path(dir: &Path, n: usize) -> PathBuf {
+    match n {
+        0 => dir.join("battery.csv"),
+        n => dir.join(format!("battery.csv.{}", n)),
+    }
+}
+
+fn open_current(dir: &Path) -> anyhow::Result<(File, u64)> {
+    let path = rotated_path(dir, 0);
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(&path)?;
+    let mut size = file.metadata()?.len();
+
+    if size == 0 {
+        writeln!(file, "{}", HEADER)?;
+        size = HEADER.len() as u64 + 1;
+    } else {
+        // end a line cut off by the machine going down, so the next snapshot gets its own
+        let mut last = [0];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+
+        if last != *b"\n" {
+            writeln!(file)?;
+            size += 1;
+        }
+    }
+
+    Ok((file, size))
+}
+
+/// A line of a history file that couldn't be read back, e.g. one cut off when the battery died.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedLine {
+    pub path: PathBuf,
+    /// Counting from 1
+    pub line: usize,
+    pub error: String,
+}
+
+impl fmt::Display for SkippedLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.error)
+    }
+}
+
+/// Snapshots a [`HistoryReader`] read back, with the lines it had to skip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct History {
+    /// Oldest first
+    pub snapshots: Vec<Snapshot>,
+    pub skipped: Vec<SkippedLine>,
+}
+
+/// Reads back what a [`HistoryWriter`] recorded, across rotated files.
+#[derive(Debug, Clone)]
+pub struct HistoryReader {
+    dir: PathBuf,
+}
+
+impl HistoryReader {
+    pub fn open(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Every snapshot taken within `from..to`.
+    ///
+    /// Lines that can't be read back are skipped rather than failing the whole query, they
+    /// come back as [`History::skipped`].
+    pub fn range(&self, from: SystemTime, to: SystemTime) -> anyhow::Result<History> {
+        let mut snapshots = Vec::new();
+        let mut skipped = Vec::new();
+
+        let mut files = (0..)
+            .map(|n| rotated_path(&self.dir, n))
+            .take_while(|path| path.exists())
+            .collect::<Vec<_>>();
+        files.reverse();
+
+        for path in files {
+            let reader = BufReader::new(File::open(&path)?);
+
+            for (number, line) in reader.split(b'\n').enumerate() {
+                let line = line?;
+
+                if line.is_empty() || line == HEADER.as_bytes() {
+                    continue;
+                }
+
+                let snapshot = std::str::from_utf8(&line)
+                    .map_err(Into::into)
+                    .and_then(Snapshot::from_csv);
+
+                match snapshot {
+                    Ok(snapshot) if (from..to).contains(&snapshot.timestamp) => {
+                        snapshots.push(snapshot)
+                    }
+                    Ok(_) => {}
+                    Err(e) => skipped.push(SkippedLine {
+                        path: path.clone(),
+                        line: number + 1,
+                        error: format!("{:#}", e),
+                    }),
+                }
+            }
+        }
+
+        snapshots.sort_by_key(|s| s.timestamp);
+
+        Ok(History { snapshots, skipped })
+    }
+
+    /// Every snapshot taken within the last `duration`, [`Duration::MAX`] for all of them.
+    pub fn last(&self, duration: Duration) -> anyhow::Result<History> {
+        let now = SystemTime::now();
+        let start = now.checked_sub(duration).unwrap_or(UNIX_EPOCH);
+
+        self.range(start, now + Duration::from_millis(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(secs: u64, percentage: f64) -> Snapshot {
+        Snapshot {
+            timestamp: UNIX_EPOCH + Duration::from_secs(secs),
+            device_state: Some(DeviceState::Discharging),
+            percentage: Some(percentage),
+            energy: Some(percentage / 2.0),
+            energy_full: Some(50.0),
+            energy_rate: Some(8.25),
+            time_until: Some(TimeUntil::Empty(Duration::from_secs(3600))),
+            warning_level: Some(WarningLevel::NoWarning),
+            battery_level: None,
+        }
+    }
+
+    #[test]
+    fn csv_round_trip() -> anyhow::Result<()> {
+        let snapshot = snapshot(1_700_000_000, 81.5);
+
+        insta::assert_snapshot!(snapshot.to_csv(), @"1700000000000,discharging,81.5,40.75,50,8.25,empty:3600,none,");
+
+        assert_eq!(Snapshot::from_csv(&snapshot.to_csv())?, snapshot);
+
+        let snapshot = Snapshot {
+            time_until: Some(TimeUntil::NotApplicable),
+            ..Snapshot::new(UNIX_EPOCH, &BatteryInfo::new())
+        };
+
+        assert_eq!(Snapshot::from_csv(&snapshot.to_csv())?, snapshot);
+
+        insta::assert_debug_snapshot!(Snapshot::from_csv("1,charging").is_err(), @"true");
+
+        Ok(())
+    }
+
+    #[test]
+    fn rotation() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let rotation = Rotation {
+            max_file_bytes: 400,
+            max_files: 3,
+        };
+
+        let mut writer = HistoryWriter::open(dir.path(), rotation)?;
+
+        for n in 0..20 {
+            writer.append(&snapshot(n * 60, 100.0 - n as f64))?;
+        }
+
+        let mut files = fs::read_dir(dir.path())?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        files.sort();
+
+        insta::assert_debug_snapshot!(files, @r###"
+        [
+            "battery.csv",
+            "battery.csv.1",
+            "battery.csv.2",
+        ]
+        "###);
+
+        for file in &files {
+            assert!(fs::metadata(dir.path().join(file))?.len() <= rotation.max_file_bytes);
+        }
+
+        // the oldest snapshots were rotated away, the rest read back in order
+        let reader = HistoryReader::open(dir.path());
+        let all = reader
+            .range(UNIX_EPOCH, UNIX_EPOCH + Duration::from_secs(3600))?
+            .snapshots;
+
+        insta::assert_debug_snapshot!(all.iter().map(|s| s.percentage.unwrap()).collect::<Vec<_>>(), @r###"
+        [
+            95.0,
+            94.0,
+            93.0,
+            92.0,
+            91.0,
+            90.0,
+            89.0,
+            88.0,
+            87.0,
+            86.0,
+            85.0,
+            84.0,
+            83.0,
+            82.0,
+            81.0,
+        ]
+        "###);
+
+        let range = reader
+            .range(
+                UNIX_EPOCH + Duration::from_secs(15 * 60),
+                UNIX_EPOCH + Duration::from_secs(17 * 60),
+            )?
+            .snapshots;
+
+        insta::assert_debug_snapshot!(range.iter().map(|s| s.percentage.unwrap()).collect::<Vec<_>>(), @r###"
+        [
+            85.0,
+            84.0,
+        ]
+        "###);
+
+        // reopening picks up the size of the full current file and rotates it
+        drop(writer);
+        let mut writer = HistoryWriter::open(dir.path(), rotation)?;
+        writer.append(&snapshot(20 * 60, 80.0))?;
+
+        insta::assert_debug_snapshot!(reader.range(UNIX_EPOCH, UNIX_EPOCH + Duration::from_secs(3600))?.snapshots.len(), @"11");
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_line() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let rotation = Rotation::default();
+
+        let mut writer = HistoryWriter::open(dir.path(), rotation)?;
+        writer.append(&snapshot(0, 3.0))?;
+        writer.append(&snapshot(60, 2.0))?;
+        drop(writer);
+
+        // the battery died halfway through writing a snapshot
+        let path = dir.path().join("battery.csv");
+        let mut file = OpenOptions::new().append(true).open(&path)?;
+        write!(file, "120000,discharging,1.")?;
+        drop(file);
+
+        let reader = HistoryReader::open(dir.path());
+        let history = reader.range(UNIX_EPOCH, UNIX_EPOCH + Duration::from_secs(3600))?;
+
+        insta::assert_debug_snapshot!(history.snapshots.len(), @"2");
+        insta::assert_snapshot!(history.skipped[0].to_string().replace(&*dir.path().to_string_lossy(), "<dir>"), @r###"<dir>/battery.csv:4: expected 9 fields, got 3: "120000,discharging,1.""###);
+
+        // recording carries on from a line of its own
+        let mut writer = HistoryWriter::open(dir.path(), rotation)?;
+        writer.append(&snapshot(180, 80.0))?;
+
+        let history = reader.range(UNIX_EPOCH, UNIX_EPOCH + Duration::from_secs(3600))?;
+        insta::assert_debug_snapshot!(history.snapshots.iter().map(|s| s.percentage.unwrap()).collect::<Vec<_>>(), @r###"
+        [
+            3.0,
+            2.0,
+            80.0,
+        ]
+        "###);
+        insta::assert_debug_snapshot!(history.skipped.len(), @"1");
+
+        Ok(())
+    }
+
+    #[test]
+    fn everything() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let mut writer = HistoryWriter::open(dir.path(), Rotation::default())?;
+        writer.append(&snapshot(0, 80.0))?;
+        writer.append(&snapshot(60, 79.0))?;
+
+        let history = HistoryReader::open(dir.path()).last(Duration::MAX)?;
+        insta::assert_debug_snapshot!(history.snapshots.len(), @"2");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use std::time::{
+    Duration,
+    SystemTime,
+};
+
+use super::Snapshot;
+use crate::battery_info::device_state::DeviceState;
+
+/// Summary of one stretch of running on battery, from being unplugged to being plugged back in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DischargeSession {
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub start_percentage: Option<f64>,
+    pub end_percentage: Option<f64>,
+    /// W
+    pub average_power: Option<f64>,
+    /// W
+    pub peak_power: Option<f64>,
+    /// Wh
+    pub energy_consumed: Option<f64>,
+    /// False if the snapshots ran out before the device was plugged in again
+    pub complete: bool,
+}
+
+impl DischargeSession {
+    pub fn duration(&self) -> Duration {
+        self.end.duration_since(self.start).unwrap_or_default()
+    }
+
+    fn from_snapshots(snapshots: &[&Snapshot], complete: bool) -> Option<Self> {
+        let (first, last) = (snapshots.first()?, snapshots.last()?);
+
+        let peak_power = snapshots
+            .iter()
+            .filter_map(|s| s.energy_rate)
+            .reduce(f64::max);
+
+        // prefer the change in stored energy, fall back to integrating the reported rate
+        let energy_consumed = match (first.energy, last.energy) {
+            (Some(start), Some(end)) => Some(start - end),
+            _ => integrate_rate(snapshots),
+        };
+
+        let hours = last
+            .timestamp
+            .duration_since(first.timestamp)
+            .unwrap_or_default()
+            .as_secs_f64()
+            / 3600.0;
+
+        let average_power = match (energy_consumed, hours > 0.0) {
+            (Some(energy), true) => Some(energy / hours),
+            _ => None,
+        };
+
+        Some(Self {
+            start: first.timestamp,
+            end: last.timestamp,
+            start_percentage: first.percentage,
+            end_percentage: last.percentage,
+            average_power,
+            peak_power,
+            energy_consumed,
+            complete,
+        })
+    }
+}
+
+/// Trapezoidal integral of the energy rate in Wh, `None` unless every snapshot has a rate.
+fn integrate_rate(snapshots: &[&Snapshot]) -> Option<f64> {
+    snapshots.windows(2).try_fold(0.0, |total, pair| {
+        let hours = pair[1]
+            .timestamp
+            .duration_since(pair[0].timestamp)
+            .unwrap_or_default()
+            .as_secs_f64()
+            / 3600.0;
+
+        Some(total + (pair[0].energy_rate? + pair[1].energy_rate?) / 2.0 * hours)
+    })
+}
+
+/// Split `snapshots`, ordered by time, into discharge sessions.
+///
+/// A session starts at the first discharging snapshot and ends at the last one before the state
+/// turns to charging, fully charged or pending charge. Snapshots with an unknown state neither
+/// start nor end a session.
+pub fn discharge_sessions(snapshots: &[Snapshot]) -> Vec<DischargeSession> {
+    let mut sessions = Vec::new();
+    let mut current: Vec<&Snapshot> = Vec::new();
+
+    for snapshot in snapshots {
+        match snapshot.device_state {
+            Some(DeviceState::Discharging | DeviceState::PendingDischarge | DeviceState::Empty) => {
+                current.push(snapshot)
+            }
+            Some(
+                DeviceState::Charging | DeviceState::FullyCharged | DeviceState::PendingCharge,
+            ) => {
+                sessions.extend(DischargeSession::from_snapshots(&current, true));
+                current.clear();
+            }
+            _ => {}
+        }
+    }
+
+    sessions.extend(DischargeSession::from_snapshots(&current, false));
+
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    fn snapshot(minutes: u64, state: DeviceState, energy: Option<f64>, rate: f64) -> Snapshot {
+        Snapshot {
+            timestamp: UNIX_EPOCH + Duration::from_secs(minutes * 60),
+            device_state: Some(state),
+            percentage: energy.map(|e| e * 2.0),
+            energy,
+            energy_full: Some(50.0),
+            energy_rate: Some(rate),
+            time_until: None,
+            warning_level: None,
+            battery_level: None,
+        }
+    }
+
+    #[test]
+    fn sessions() {
+        use DeviceState::*;
+
+        let snapshots = [
+            snapshot(0, FullyCharged, Some(50.0), 0.0),
+            snapshot(10, Discharging, Some(49.0), 6.0),
+            snapshot(40, Discharging, Some(46.0), 12.0),
+            snapshot(70, Discharging, Some(43.0), 6.0),
+            snapshot(71, Charging, Some(43.0), 30.0),
+            snapshot(100, Unknown, None, 0.0),
+            snapshot(130, Discharging, None, 10.0),
+            snapshot(190, Discharging, None, 10.0),
+        ];
+
+        let sessions = discharge_sessions(&snapshots);
+
+        insta::assert_debug_snapshot!(sessions.iter().map(|s| s.duration()).collect::<Vec<_>>(), @r###"
+        [
+            3600s,
+            3600s,
+        ]
+        "###);
+
+        insta::assert_debug_snapshot!(sessions, @r###"
+        [
+            DischargeSession {
+                start: SystemTime {
+                    tv_sec: 600,
+                    tv_nsec: 0,
+                },
+                end: SystemTime {
+                    tv_sec: 4200,
+                    tv_nsec: 0,
+                },
+                start_percentage: Some(
+                    98.0,
+                ),
+                end_percentage: Some(
+                    86.0,
+                ),
+                average_power: Some(
+                    6.0,
+                ),
+                peak_power: Some(
+                    12.0,
+                ),
+                energy_consumed: Some(
+                    6.0,
+                ),
+                complete: true,
+            },
+            DischargeSession {
+                start: SystemTime {
+                    tv_sec: 7800,
+                    tv_nsec: 0,
+                },
+                end: SystemTime {
+                    tv_sec: 11400,
+                    tv_nsec: 0,
+                },
+                start_percentage: None,
+                end_percentage: None,
+                average_power: Some(
+                    10.0,
+                ),
+                peak_power: Some(
+                    10.0,
+                ),
+                energy_consumed: Some(
+                    10.0,
+                ),
+                complete: false,
+            },
+        ]
+        "###);
+    }
+}
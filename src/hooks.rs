@@ -0,0 +1,417 @@
+use std::{
+    collections::HashMap,
+    os::unix::process::CommandExt,
+    process::{
+        Child,
+        Command,
+        Stdio,
+    },
+    thread::sleep,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use crate::{
+    battery_info::{
+        event::{
+            BatteryEvent,
+            LevelThresholds,
+        },
+        time_until::TimeUntil,
+    },
+    battery_interface::Device,
+};
+
+/// How a hook command ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookStatus {
+    /// Exit code, `None` if the command was killed by a signal
+    Exited(Option<i32>),
+    /// Killed after running longer than the timeout
+    TimedOut,
+    /// Not run, the same event fired within the debounce period
+    Debounced,
+    /// Couldn't be run or waited for, e.g. the shell failing to start
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookRun {
+    pub event: BatteryEvent,
+    pub command: String,
+    pub status: HookStatus,
+}
+
+/// Runs shell commands when [`BatteryEvent`]s fire.
+///
+/// Commands run through `sh -c` with the reading that caused the event in the environment:
+/// `LV_EVENT`, `LV_DEVICE`, `LV_PERCENTAGE`, `LV_STATE`, `LV_WARNING_LEVEL`, and
+/// `LV_TIME_TO_EMPTY`/`LV_TIME_TO_FULL` in seconds. Variables without a value are set but empty.
+///
+/// Each command runs in a process group of its own, a command that times out is killed along
+/// with everything it started.
+#[derive(Debug, Clone)]
+pub struct HookRunner {
+    hooks: HashMap<BatteryEvent, Vec<String>>,
+    timeout: Duration,
+    debounce: Duration,
+    thresholds: Option<LevelThresholds>,
+    /// By event and device id
+    last_fired: HashMap<(BatteryEvent, String), Instant>,
+}
+
+impl Default for HookRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HookRunner {
+    pub fn new() -> Self {
+        Self {
+            hooks: HashMap::new(),
+            timeout: Duration::from_secs(30),
+            debounce: Duration::from_secs(5),
+            thresholds: None,
+            last_fired: HashMap::new(),
+        }
+    }
+
+    /// Run `command` on `event`, after any commands already added for it.
+    pub fn with_hook(mut self, event: BatteryEvent, command: impl Into<String>) -> Self {
+        self.hooks.entry(event).or_default().push(command.into());
+        self
+    }
+
+    /// Kill commands that are still running after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Ignore an event that fires again for the same device within `debounce` of its last run,
+    /// e.g. a flaky charger.
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Have [`HookRunner::watch`] fire level events once the percentage crosses `thresholds`,
+    /// not only when UPower's warning level changes.
+    pub fn with_level_thresholds(mut self, thresholds: LevelThresholds) -> Self {
+        self.thresholds = Some(thresholds);
+        self
+    }
+
+    /// Run the hooks for `event` on `device`, one after the other. A command that fails to run
+    /// doesn't keep the ones after it from running.
+    pub fn fire(&mut self, event: BatteryEvent, device: &Device, now: Instant) -> Vec<HookRun> {
+        let Some(commands) = self.hooks.get(&event) else {
+            return vec![];
+        };
+
+        let key = (event, device.id.clone());
+
+        let debounced = self
+            .last_fired
+            .get(&key)
+            .is_some_and(|last| now.saturating_duration_since(*last) < self.debounce);
+
+        if !debounced {
+            self.last_fired.insert(key, now);
+        }
+
+        commands
+            .iter()
+            .map(|command| {
+                let status = match debounced {
+                    true => HookStatus::Debounced,
+                    false => self
+                        .run(command, event, device)
+                        .unwrap_or_else(|e| HookStatus::Failed(format!("{:#}", e))),
+                };
+
+                HookRun {
+                    event,
+                    command: command.clone(),
+                    status,
+                }
+            })
+            .collect()
+    }
+
+    /// List devices every `interval` and fire hooks for the events between listings, passing
+    /// each run to `on_run` along with the device it ran for.
+    pub fn watch(
+        &mut self,
+        interval: Duration,
+        mut read: impl FnMut() -> anyhow::Result<Vec<Device>>,
+        mut on_run: impl FnMut(&Device, &HookRun),
+    ) -> anyhow::Result<()> {
+        let mut prev = read()?;
+
+        loop {
+            sleep(interval);
+
+            let Ok(next) = read() else {
+                continue;
+            };
+
+            let events =
+                BatteryEvent::detect_devices_with_thresholds(&prev, &next, self.thresholds);
+
+            for (event, device) in events {
+                for run in self.fire(event, device, Instant::now()) {
+                    on_run(device, &run);
+                }
+            }
+
+            prev = next;
+        }
+    }
+
+    fn run(
+        &self,
+        command: &str,
+        event: BatteryEvent,
+        device: &Device,
+    ) -> anyhow::Result<HookStatus> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .envs(environment(event, device))
+            .stdin(Stdio::null())
+            .process_group(0)
+            .spawn()?;
+
+        let deadline = Instant::now() + self.timeout;
+
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(HookStatus::Exited(status.code()));
+            }
+
+            if Instant::now() >= deadline {
+                kill_group(&mut child)?;
+                child.wait()?;
+                return Ok(HookStatus::TimedOut);
+            }
+
+            sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+/// Kill `child` and the processes it started, it leads a process group of its own.
+fn kill_group(child: &mut Child) -> std::io::Result<()> {
+    // the leader's pid is the group's id
+    let group = child.id() as libc::pid_t;
+
+    // SAFETY: killpg only sends a signal, no memory is passed to it
+    match unsafe { libc::killpg(group, libc::SIGKILL) } {
+        0 => Ok(()),
+        _ => child.kill(),
+    }
+}
+
+fn environment(event: BatteryEvent, device: &Device) -> [(&'static str, String); 7] {
+    let info = &device.info;
+
+    let percentage = info.percentage.map(|p| p.to_string());
+    let state = info.device_state.map(|s| s.to_string());
+    let warning_level = info.warning_level.map(|w| w.to_string());
+
+    let (time_to_empty, time_to_full) = match info.time_until {
+        Some(TimeUntil::Empty(d)) => (Some(d.as_secs().to_string()), None),
+        Some(TimeUntil::Full(d)) => (None, Some(d.as_secs().to_string())),
+        _ => (None, None),
+    };
+
+    [
+        ("LV_EVENT", event.to_string()),
+        ("LV_DEVICE", device.id.clone()),
+        ("LV_PERCENTAGE", percentage.unwrap_or_default()),
+        ("LV_STATE", state.unwrap_or_default()),
+        ("LV_WARNING_LEVEL", warning_level.unwrap_or_default()),
+        ("LV_TIME_TO_EMPTY", time_to_empty.unwrap_or_default()),
+        ("LV_TIME_TO_FULL", time_to_full.unwrap_or_default()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        battery_info::{
+            device_state::DeviceState,
+            BatteryInfoProperties,
+        },
+        test_utils::batt_info,
+    };
+
+    fn device() -> Device {
+        let info = batt_info([
+            BatteryInfoProperties::Percentage(18.5.into()),
+            BatteryInfoProperties::DeviceState(DeviceState::Discharging),
+            BatteryInfoProperties::TimeUntil(TimeUntil::Empty(Duration::from_secs(2700))),
+        ]);
+
+        Device {
+            id: "BAT0".to_owned(),
+            info,
+        }
+    }
+
+    #[test]
+    fn environment_variables() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("env");
+
+        let mut runner = HookRunner::new().with_hook(
+            BatteryEvent::LevelLow,
+            format!(
+                "printf '%s|' \"$LV_EVENT\" \"$LV_DEVICE\" \"$LV_PERCENTAGE\" \"$LV_STATE\" \
+                 \"$LV_WARNING_LEVEL\" \"$LV_TIME_TO_EMPTY\" \"$LV_TIME_TO_FULL\" > {:?}",
+                output
+            ),
+        );
+
+        let runs = runner.fire(BatteryEvent::LevelLow, &device(), Instant::now());
+
+        insta::assert_debug_snapshot!(runs.iter().map(|r| &r.status).collect::<Vec<_>>(), @r###"
+        [
+            Exited(
+                Some(
+                    0,
+                ),
+            ),
+        ]
+        "###);
+        insta::assert_snapshot!(fs::read_to_string(output)?, @"level_low|BAT0|18.5|discharging||2700||");
+
+        Ok(())
+    }
+
+    #[test]
+    fn timeout_and_debounce() -> anyhow::Result<()> {
+        let mut runner = HookRunner::new()
+            .with_hook(BatteryEvent::AcUnplugged, "sleep 5")
+            .with_hook(BatteryEvent::AcUnplugged, "exit 3")
+            .with_timeout(Duration::from_millis(100))
+            .with_debounce(Duration::from_secs(10));
+
+        let start = Instant::now();
+        let statuses = [0, 5, 15]
+            .into_iter()
+            .map(|secs| {
+                runner
+                    .fire(
+                        BatteryEvent::AcUnplugged,
+                        &device(),
+                        start + Duration::from_secs(secs),
+                    )
+                    .into_iter()
+                    .map(|r| r.status)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        insta::assert_debug_snapshot!(statuses, @r###"
+        [
+            [
+                TimedOut,
+                Exited(
+                    Some(
+                        3,
+                    ),
+                ),
+            ],
+            [
+                Debounced,
+                Debounced,
+            ],
+            [
+                TimedOut,
+                Exited(
+                    Some(
+                        3,
+                    ),
+                ),
+            ],
+        ]
+        "###);
+
+        // nothing configured for this event
+        insta::assert_debug_snapshot!(runner.fire(BatteryEvent::FullyCharged, &device(), start), @"[]");
+
+        // another device isn't debounced by the first one
+        let other = Device {
+            id: "BAT1".to_owned(),
+            ..device()
+        };
+        let runs = runner.fire(
+            BatteryEvent::AcUnplugged,
+            &other,
+            start + Duration::from_secs(20),
+        );
+        assert!(runs.iter().all(|run| run.status != HookStatus::Debounced));
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_hook_doesnt_stop_the_rest() {
+        // a nul byte can't be passed to `sh`, failing the spawn
+        let mut runner = HookRunner::new()
+            .with_hook(BatteryEvent::AcPlugged, "echo \0")
+            .with_hook(BatteryEvent::AcPlugged, "exit 0");
+
+        let runs = runner.fire(BatteryEvent::AcPlugged, &device(), Instant::now());
+
+        insta::assert_debug_snapshot!(runs.into_iter().map(|r| r.status).collect::<Vec<_>>(), @r###"
+        [
+            Failed(
+                "nul byte found in provided data",
+            ),
+            Exited(
+                Some(
+                    0,
+                ),
+            ),
+        ]
+        "###);
+    }
+
+    #[test]
+    fn timeout_kills_what_the_hook_started() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let pid_file = dir.path().join("pid");
+
+        let mut runner = HookRunner::new()
+            .with_hook(
+                BatteryEvent::LevelLow,
+                format!("sleep 30 & echo $! > {:?}; wait", pid_file),
+            )
+            .with_timeout(Duration::from_millis(200));
+
+        let runs = runner.fire(BatteryEvent::LevelLow, &device(), Instant::now());
+        assert_eq!(runs[0].status, HookStatus::TimedOut);
+
+        // a killed process lingers as a zombie until it's reaped
+        let stat = format!("/proc/{}/stat", fs::read_to_string(pid_file)?.trim());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while fs::read_to_string(&stat).is_ok_and(|stat| !stat.contains(") Z ")) {
+            assert!(
+                Instant::now() < deadline,
+                "the hook's sleep is still running"
+            );
+            sleep(Duration::from_millis(10));
+        }
+
+        Ok(())
+    }
+}
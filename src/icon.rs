@@ -0,0 +1,439 @@
+use std::{
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    env,
+    path::PathBuf,
+    sync::OnceLock,
+};
+
+use crate::battery_info::{
+    device_state::DeviceState,
+    BatteryInfo,
+    IconName,
+    Percentage,
+};
+
+pub mod theme;
+use theme::*;
+
+const EXTENSIONS: [&str; 3] = ["png", "svg", "xpm"];
+
+impl IconName {
+    /// Build the icon name UPower would report for `percentage` and `state`, for backends that
+    /// don't provide one, e.g. `battery-level-40-charging-symbolic`.
+    pub fn synthesize(percentage: Percentage, state: DeviceState) -> Self {
+        let level = ((*percentage).clamp(0.0, 100.0) / 10.0).round() as u32 * 10;
+
+        let name = match state {
+            DeviceState::FullyCharged => "battery-level-100-charged-symbolic".to_owned(),
+            DeviceState::Empty => "battery-level-0-symbolic".to_owned(),
+            DeviceState::Charging | DeviceState::PendingCharge => {
+                format!("battery-level-{}-charging-symbolic", level)
+            }
+            DeviceState::Discharging | DeviceState::PendingDischarge => {
+                format!("battery-level-{}-symbolic", level)
+            }
+            DeviceState::Unknown | DeviceState::Unrecognized(_) => {
+                "battery-missing-symbolic".to_owned()
+            }
+        };
+
+        name.into()
+    }
+
+    /// Names to try, in order, when a theme doesn't have this exact icon.
+    ///
+    /// `battery-level-*` names also try the older `battery-good` style before shorter names are
+    /// tried, symbolic icons fall back to every symbolic name before their full color counterparts.
+    pub fn fallbacks(&self) -> Vec<String> {
+        let (base, symbolic) = match self.strip_suffix("-symbolic") {
+            Some(base) => (base, true),
+            None => (self.as_str(), false),
+        };
+
+        let mut bases = vec![base.to_owned()];
+
+        if let Some(legacy) = legacy_name(base) {
+            bases.push(legacy);
+        }
+
+        // exact names first, then dropping one dash separated part at a time
+        let truncated = bases
+            .iter()
+            .cloned()
+            .chain(bases.iter().flat_map(|base| {
+                let parts = base.split('-').collect::<Vec<_>>();
+                (1..parts.len()).rev().map(move |n| parts[..n].join("-"))
+            }))
+            .collect::<Vec<_>>();
+
+        let mut seen = HashSet::new();
+
+        truncated
+            .iter()
+            .filter(|_| symbolic)
+            .map(|name| format!("{}-symbolic", name))
+            .chain(truncated.iter().cloned())
+            .filter(|name| seen.insert(name.clone()))
+            .collect()
+    }
+}
+
+/// Map `battery-level-40[-charging]` to the pre `battery-level-*` names most themes still ship.
+fn legacy_name(base: &str) -> Option<String> {
+    let rest = base.strip_prefix("battery-level-")?;
+
+    let (level, suffix) = match rest.split_once('-') {
+        Some((level, suffix)) => (level.parse::<u32>().ok()?, Some(suffix)),
+        None => (rest.parse::<u32>().ok()?, None),
+    };
+
+    let name = match level {
+        0 => "empty",
+        1..=10 => "caution",
+        11..=30 => "low",
+        31..=60 => "good",
+        _ => "full",
+    };
+
+    Some(match suffix {
+        Some(suffix) => format!("battery-{}-{}", name, suffix),
+        None => format!("battery-{}", name),
+    })
+}
+
+impl BatteryInfo {
+    /// The icon name reported by the backend, or one synthesized from percentage and state.
+    pub fn icon_name_or_synthesized(&self) -> Option<IconName> {
+        self.icon_name
+            .clone()
+            .or_else(|| Some(IconName::synthesize(self.percentage?, self.device_state?)))
+    }
+}
+
+/// Resolves icon names to files following the freedesktop icon theme specification.
+#[derive(Debug, Clone)]
+pub struct IconResolver {
+    theme: String,
+    base_dirs: Vec<PathBuf>,
+    size: u32,
+    scale: u32,
+    /// The theme chain, loaded on the first lookup
+    themes: OnceLock<Vec<IconTheme>>,
+}
+
+impl IconResolver {
+    /// Resolve icons from `theme`, searching the base directories from `$HOME` and the XDG
+    /// environment variables.
+    pub fn new(theme: impl Into<String>) -> Self {
+        Self {
+            theme: theme.into(),
+            base_dirs: default_base_dirs(),
+            size: 16,
+            scale: 1,
+            themes: OnceLock::new(),
+        }
+    }
+
+    pub fn with_base_dirs(mut self, base_dirs: Vec<PathBuf>) -> Self {
+        self.base_dirs = base_dirs;
+        self.themes = OnceLock::new();
+        self
+    }
+
+    pub fn with_size(mut self, size: u32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Resolve `icon_name` or one of its [fallbacks](IconName::fallbacks) to a file.
+    pub fn resolve(&self, icon_name: &IconName) -> Option<PathBuf> {
+        let themes = self.themes.get_or_init(|| self.theme_chain());
+
+        icon_name
+            .fallbacks()
+            .iter()
+            .find_map(|name| self.lookup(name, themes))
+    }
+
+    /// Resolve the icon for `batt_info`, synthesizing a name if the backend didn't provide one.
+    pub fn resolve_battery(&self, batt_info: &BatteryInfo) -> Option<PathBuf> {
+        self.resolve(&batt_info.icon_name_or_synthesized()?)
+    }
+
+    fn lookup(&self, name: &str, themes: &[IconTheme]) -> Option<PathBuf> {
+        themes
+            .iter()
+            .find_map(|theme| theme.lookup(name, self.size, self.scale, &EXTENSIONS))
+            .or_else(|| {
+                // unthemed icons placed directly in a base directory
+                self.base_dirs
+                    .iter()
+                    .flat_map(|dir| EXTENSIONS.map(|ext| dir.join(format!("{}.{}", name, ext))))
+                    .find(|path| path.is_file())
+            })
+    }
+
+    /// The selected theme followed by everything it inherits from, breadth first, with `hicolor`
+    /// always searched last.
+    ///
+    /// Loaded once per resolver, a theme installed or changed afterwards needs a new resolver.
+    fn theme_chain(&self) -> Vec<IconTheme> {
+        let mut loaded: HashMap<String, IconTheme> = HashMap::new();
+        let mut order = Vec::new();
+        let mut queue = vec![self.theme.clone()];
+
+        while !queue.is_empty() {
+            let name = queue.remove(0);
+
+            if loaded.contains_key(&name) || name == "hicolor" {
+                continue;
+            }
+
+            let Ok(theme) = IconTheme::load(&name, &self.base_dirs) else {
+                continue;
+            };
+
+            queue.extend(theme.inherits.iter().cloned());
+            order.push(name.clone());
+            loaded.insert(name, theme);
+        }
+
+        order
+            .into_iter()
+            .filter_map(|name| loaded.remove(&name))
+            .chain(IconTheme::load("hicolor", &self.base_dirs).ok())
+            .collect()
+    }
+}
+
+fn default_base_dirs() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".local/share")));
+
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_owned());
+
+    home.map(|home| home.join(".icons"))
+        .into_iter()
+        .chain(data_home.map(|dir| dir.join("icons")))
+        .chain(env::split_paths(&data_dirs).map(|dir| dir.join("icons")))
+        .chain([PathBuf::from("/usr/share/pixmaps")])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn write_theme(base: &std::path::Path, name: &str, index_theme: &str, icons: &[&str]) {
+        let root = base.join(name);
+
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("index.theme"), index_theme).unwrap();
+
+        for icon in icons {
+            let path = root.join(icon);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+    }
+
+    #[test]
+    fn synthesize() {
+        let names = [
+            (42.0, DeviceState::Discharging),
+            (47.0, DeviceState::Charging),
+            (100.0, DeviceState::FullyCharged),
+            (0.0, DeviceState::Empty),
+            (50.0, DeviceState::Unknown),
+        ]
+        .map(|(percentage, state)| IconName::synthesize(percentage.into(), state).to_string());
+
+        insta::assert_debug_snapshot!(names, @r###"
+        [
+            "battery-level-40-symbolic",
+            "battery-level-50-charging-symbolic",
+            "battery-level-100-charged-symbolic",
+            "battery-level-0-symbolic",
+            "battery-missing-symbolic",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn fallbacks() {
+        let icon_name = IconName::from("battery-level-40-charging-symbolic".to_owned());
+
+        insta::assert_debug_snapshot!(icon_name.fallbacks(), @r###"
+        [
+            "battery-level-40-charging-symbolic",
+            "battery-good-charging-symbolic",
+            "battery-level-40-symbolic",
+            "battery-level-symbolic",
+            "battery-symbolic",
+            "battery-good-symbolic",
+            "battery-level-40-charging",
+            "battery-good-charging",
+            "battery-level-40",
+            "battery-level",
+            "battery",
+            "battery-good",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn resolve() -> anyhow::Result<()> {
+        let base = tempfile::tempdir()?;
+
+        write_theme(
+            base.path(),
+            "Custom",
+            "
+            [Icon Theme]
+            Name=Custom
+            Inherits=Parent
+            Directories=16x16/status
+
+            [16x16/status]
+            Size=16
+            Type=Fixed
+            ",
+            &["16x16/status/battery-level-40-symbolic.svg"],
+        );
+
+        write_theme(
+            base.path(),
+            "Parent",
+            "
+            [Icon Theme]
+            Name=Parent
+            Directories=24x24/status,scalable/status,24x24@2/status
+
+            [24x24/status]
+            Size=24
+
+            [24x24@2/status]
+            Size=24
+            Scale=2
+
+            [scalable/status]
+            Size=48
+            MinSize=8
+            MaxSize=512
+            Type=Scalable
+            ",
+            &[
+                "24x24/status/battery-good-charging-symbolic.png",
+                "24x24@2/status/battery-good-charging-symbolic.png",
+                "scalable/status/battery-good-charging-symbolic.svg",
+            ],
+        );
+
+        write_theme(
+            base.path(),
+            "hicolor",
+            "
+            [Icon Theme]
+            Name=Hicolor
+            Directories=16x16/status
+
+            [16x16/status]
+            Size=16
+            ",
+            &["16x16/status/battery-missing.png"],
+        );
+
+        let resolver = IconResolver::new("Custom").with_base_dirs(vec![base.path().to_owned()]);
+
+        let relative = |path: Option<PathBuf>| {
+            path.map(|path| path.strip_prefix(base.path()).unwrap().to_owned())
+        };
+
+        let resolve = |resolver: &IconResolver, name: &str| {
+            relative(resolver.resolve(&IconName::from(name.to_owned())))
+        };
+
+        insta::assert_debug_snapshot!(resolve(&resolver, "battery-level-40-symbolic"), @r###"
+        Some(
+            "Custom/16x16/status/battery-level-40-symbolic.svg",
+        )
+        "###);
+
+        // found through Inherits using the legacy name, scalable matches exactly
+        insta::assert_debug_snapshot!(resolve(&resolver, "battery-level-40-charging-symbolic"), @r###"
+        Some(
+            "Parent/scalable/status/battery-good-charging-symbolic.svg",
+        )
+        "###);
+
+        let resolver = resolver.with_size(24).with_scale(2);
+
+        insta::assert_debug_snapshot!(resolve(&resolver, "battery-level-40-charging-symbolic"), @r###"
+        Some(
+            "Parent/24x24@2/status/battery-good-charging-symbolic.png",
+        )
+        "###);
+
+        insta::assert_debug_snapshot!(resolve(&resolver, "battery-missing-symbolic"), @r###"
+        Some(
+            "hicolor/16x16/status/battery-missing.png",
+        )
+        "###);
+
+        insta::assert_debug_snapshot!(resolve(&resolver, "ac-adapter"), @"None");
+
+        Ok(())
+    }
+
+    #[test]
+    fn huge_sizes() -> anyhow::Result<()> {
+        let base = tempfile::tempdir()?;
+
+        write_theme(
+            base.path(),
+            "Huge",
+            "
+            [Icon Theme]
+            Name=Huge
+            Directories=huge/status
+
+            [huge/status]
+            Size=4294967295
+            Scale=2
+            Threshold=4294967295
+            ",
+            &["huge/status/battery-empty.png"],
+        );
+
+        let resolver = IconResolver::new("Huge")
+            .with_base_dirs(vec![base.path().to_owned()])
+            .with_size(u32::MAX)
+            .with_scale(3);
+
+        let path = resolver.resolve(&IconName::from("battery-level-0".to_owned()));
+
+        insta::assert_debug_snapshot!(path.map(|path| path.strip_prefix(base.path()).unwrap().to_owned()), @r###"
+        Some(
+            "Huge/huge/status/battery-empty.png",
+        )
+        "###);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectoryType {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+/// One of the sub directories listed in a theme's `index.theme`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThemeDirectory {
+    pub path: String,
+    pub size: u32,
+    pub scale: u32,
+    pub min_size: u32,
+    pub max_size: u32,
+    pub threshold: u32,
+    pub directory_type: DirectoryType,
+}
+
+impl ThemeDirectory {
+    fn from_section(path: &str, section: &HashMap<String, String>) -> Option<Self> {
+        let get = |key: &str| section.get(key).and_then(|v| v.parse::<u32>().ok());
+
+        let size = get("Size")?;
+
+        let directory_type = match section.get("Type").map(String::as_str) {
+            Some("Fixed") => DirectoryType::Fixed,
+            Some("Scalable") => DirectoryType::Scalable,
+            _ => DirectoryType::Threshold,
+        };
+
+        Some(Self {
+            path: path.to_owned(),
+            size,
+            scale: get("Scale").unwrap_or(1),
+            min_size: get("MinSize").unwrap_or(size),
+            max_size: get("MaxSize").unwrap_or(size),
+            threshold: get("Threshold").unwrap_or(2),
+            directory_type,
+        })
+    }
+
+    pub fn matches_size(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+
+        match self.directory_type {
+            DirectoryType::Fixed => self.size == size,
+            DirectoryType::Scalable => (self.min_size..=self.max_size).contains(&size),
+            DirectoryType::Threshold => (self.size.saturating_sub(self.threshold)
+                ..=self.size.saturating_add(self.threshold))
+                .contains(&size),
+        }
+    }
+
+    pub fn size_distance(&self, size: u32, scale: u32) -> u32 {
+        let wanted = size.saturating_mul(scale);
+
+        let (min, max) = match self.directory_type {
+            DirectoryType::Fixed => (self.size, self.size),
+            DirectoryType::Scalable => (self.min_size, self.max_size),
+            DirectoryType::Threshold => (
+                self.size.saturating_sub(self.threshold),
+                self.size.saturating_add(self.threshold),
+            ),
+        };
+
+        let (min, max) = (
+            min.saturating_mul(self.scale),
+            max.saturating_mul(self.scale),
+        );
+
+        if wanted < min {
+            min - wanted
+        } else {
+            wanted.saturating_sub(max)
+        }
+    }
+}
+
+/// An icon theme as described by its `index.theme`, see the freedesktop icon theme specification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconTheme {
+    pub name: String,
+    pub inherits: Vec<String>,
+    pub directories: Vec<ThemeDirectory>,
+    /// Every base directory containing a directory named after the theme
+    pub roots: Vec<PathBuf>,
+}
+
+impl IconTheme {
+    /// Load `name` from the first base directory that has an `index.theme` for it.
+    pub fn load(name: &str, base_dirs: &[PathBuf]) -> anyhow::Result<Self> {
+        let roots = base_dirs
+            .iter()
+            .map(|dir| dir.join(name))
+            .filter(|dir| dir.is_dir())
+            .collect::<Vec<_>>();
+
+        let index = roots
+            .iter()
+            .map(|root| root.join("index.theme"))
+            .find(|index| index.is_file())
+            .ok_or_else(|| anyhow::anyhow!("no index.theme found for icon theme {:?}", name))?;
+
+        let mut theme = Self::parse(name, &fs::read_to_string(&index)?)?;
+        theme.roots = roots;
+
+        Ok(theme)
+    }
+
+    pub fn parse(name: &str, index_theme: &str) -> anyhow::Result<Self> {
+        let sections = parse_ini(index_theme);
+
+        let Some(icon_theme) = sections.get("Icon Theme") else {
+            anyhow::bail!(
+                "index.theme for {:?} is missing an [Icon Theme] section",
+                name
+            );
+        };
+
+        let list = |key: &str| {
+            icon_theme
+                .get(key)
+                .map(|v| {
+                    v.split(',')
+                        .map(str::trim)
+                        .filter(|v| !v.is_empty())
+                        .map(str::to_owned)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        };
+
+        let directories = list("Directories")
+            .into_iter()
+            .chain(list("ScaledDirectories"))
+            .filter_map(|dir| ThemeDirectory::from_section(&dir, sections.get(&dir)?))
+            .collect();
+
+        Ok(Self {
+            name: name.to_owned(),
+            inherits: list("Inherits"),
+            directories,
+            roots: Vec::new(),
+        })
+    }
+
+    /// Look up `icon_name` in this theme only, without following `Inherits`.
+    pub fn lookup(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        extensions: &[&str],
+    ) -> Option<PathBuf> {
+        let candidates = || {
+            self.directories.iter().flat_map(move |directory| {
+                self.roots.iter().flat_map(move |root| {
+                    extensions.iter().map(move |extension| {
+                        (
+                            directory,
+                            root.join(&directory.path)
+                                .join(format!("{}.{}", icon_name, extension)),
+                        )
+                    })
+                })
+            })
+        };
+
+        if let Some((_, path)) = candidates()
+            .find(|(directory, path)| directory.matches_size(size, scale) && path.is_file())
+        {
+            return Some(path);
+        }
+
+        candidates()
+            .filter(|(_, path)| path.is_file())
+            .min_by_key(|(directory, _)| directory.size_distance(size, scale))
+            .map(|(_, path)| path)
+    }
+}
+
+fn parse_ini(content: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut current = None;
+
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = Some(section.to_owned());
+            continue;
+        }
+
+        if let (Some(section), Some((key, value))) = (&current, line.split_once('=')) {
+            sections
+                .entry(section.clone())
+                .or_default()
+                .insert(key.trim().to_owned(), value.trim().to_owned());
+        }
+    }
+
+    sections
+}
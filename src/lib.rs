@@ -1,3 +1,17 @@
+pub mod aggregate;
 pub mod battery_interface;
 pub mod battery_info;
+pub mod config;
+pub mod daemon;
+pub mod glyph;
+pub mod history;
+pub mod hooks;
+pub mod icon;
+pub mod logind;
+pub mod openmetrics;
+pub mod peripheral;
+pub mod ups;
+
+#[cfg(test)]
+mod test_utils;
 
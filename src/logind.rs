@@ -0,0 +1,574 @@
+use std::{
+    fmt,
+    str::FromStr,
+    thread::sleep,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use anyhow::{
+    anyhow,
+    bail,
+};
+use serde::Deserialize;
+use zbus::blocking::Connection;
+
+use crate::battery_info::{
+    device_state::DeviceState,
+    warning_level::WarningLevel,
+    BatteryInfo,
+};
+
+/// `(what, who, why, mode, uid, pid)` of an inhibitor lock
+type Inhibitor = (String, String, String, String, u32, u32);
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1",
+    gen_async = false
+)]
+trait Manager {
+    fn suspend(&self, interactive: bool) -> zbus::Result<()>;
+
+    fn hibernate(&self, interactive: bool) -> zbus::Result<()>;
+
+    fn hybrid_sleep(&self, interactive: bool) -> zbus::Result<()>;
+
+    fn power_off(&self, interactive: bool) -> zbus::Result<()>;
+
+    fn can_suspend(&self) -> zbus::Result<String>;
+
+    fn can_hibernate(&self) -> zbus::Result<String>;
+
+    fn can_hybrid_sleep(&self) -> zbus::Result<String>;
+
+    fn can_power_off(&self) -> zbus::Result<String>;
+
+    fn list_inhibitors(&self) -> zbus::Result<Vec<Inhibitor>>;
+}
+
+/// What to do with the machine once the battery is critical.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum CriticalAction {
+    Suspend,
+    Hibernate,
+    HybridSleep,
+    PowerOff,
+}
+
+impl CriticalAction {
+    pub const ALL: [CriticalAction; 4] = [
+        CriticalAction::Suspend,
+        CriticalAction::Hibernate,
+        CriticalAction::HybridSleep,
+        CriticalAction::PowerOff,
+    ];
+
+    /// The inhibitor lock type that blocks this action.
+    fn inhibited_by(&self) -> &'static str {
+        match self {
+            CriticalAction::PowerOff => "shutdown",
+            _ => "sleep",
+        }
+    }
+
+    fn can(&self, proxy: &ManagerProxy) -> zbus::Result<String> {
+        match self {
+            CriticalAction::Suspend => proxy.can_suspend(),
+            CriticalAction::Hibernate => proxy.can_hibernate(),
+            CriticalAction::HybridSleep => proxy.can_hybrid_sleep(),
+            CriticalAction::PowerOff => proxy.can_power_off(),
+        }
+    }
+
+    fn perform(&self, proxy: &ManagerProxy) -> zbus::Result<()> {
+        match self {
+            CriticalAction::Suspend => proxy.suspend(false),
+            CriticalAction::Hibernate => proxy.hibernate(false),
+            CriticalAction::HybridSleep => proxy.hybrid_sleep(false),
+            CriticalAction::PowerOff => proxy.power_off(false),
+        }
+    }
+}
+
+impl fmt::Display for CriticalAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CriticalAction::Suspend => "suspend",
+            CriticalAction::Hibernate => "hibernate",
+            CriticalAction::HybridSleep => "hybrid-sleep",
+            CriticalAction::PowerOff => "poweroff",
+        })
+    }
+}
+
+impl FromStr for CriticalAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CriticalAction::ALL
+            .into_iter()
+            .find(|action| action.to_string() == s)
+            .ok_or_else(|| anyhow!("unknown critical action: {:?}", s))
+    }
+}
+
+impl TryFrom<String> for CriticalAction {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// What a [`CriticalWatcher::check`] did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The battery isn't critical
+    Idle,
+    /// Critical, but waiting for the grace period to run out
+    GracePeriod {
+        remaining: Duration,
+    },
+    /// The grace period ran out but these inhibitors still hold a blocking lock
+    WaitingForInhibitors {
+        who: Vec<String>,
+    },
+    Performed(CriticalAction),
+    /// Dry run, `CriticalAction` would have been performed
+    WouldPerform(CriticalAction),
+}
+
+/// Suspends, hibernates or powers off the machine through logind when the battery runs critical.
+///
+/// The battery is critical once UPower reports [`WarningLevel::Action`], or when discharging at or
+/// below the configured percentage. The action is taken after a grace period, extended for as
+/// long as another program holds a blocking inhibitor lock, up to a maximum delay. Plugging in
+/// the charger at any point cancels it.
+pub struct CriticalWatcher {
+    proxy: ManagerProxy<'static>,
+    actions: Vec<CriticalAction>,
+    percentage: Option<f64>,
+    grace_period: Duration,
+    max_inhibitor_delay: Duration,
+    dry_run: bool,
+    critical_since: Option<Instant>,
+}
+
+impl CriticalWatcher {
+    /// Watch using logind on `connection`, hibernating if possible and suspending otherwise.
+    pub fn new(connection: &Connection) -> anyhow::Result<Self> {
+        Ok(Self {
+            proxy: ManagerProxy::new(connection)?,
+            actions: vec![CriticalAction::Hibernate, CriticalAction::Suspend],
+            percentage: None,
+            grace_period: Duration::from_secs(60),
+            max_inhibitor_delay: Duration::from_secs(5 * 60),
+            dry_run: false,
+            critical_since: None,
+        })
+    }
+
+    /// Watch using logind on the system bus.
+    pub fn system() -> anyhow::Result<Self> {
+        Self::new(&Connection::system()?)
+    }
+
+    /// Actions in order of preference, the first one logind allows is taken.
+    pub fn with_actions(mut self, actions: Vec<CriticalAction>) -> Self {
+        self.actions = actions;
+        self
+    }
+
+    /// Also treat discharging at or below `percentage` as critical.
+    pub fn with_percentage(mut self, percentage: f64) -> Self {
+        self.percentage = Some(percentage);
+        self
+    }
+
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    /// How long past the grace period to wait for blocking inhibitors to be released.
+    pub fn with_max_inhibitor_delay(mut self, max_inhibitor_delay: Duration) -> Self {
+        self.max_inhibitor_delay = max_inhibitor_delay;
+        self
+    }
+
+    /// Check availability and inhibitors as usual but never perform the action.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn is_critical(&self, batt_info: &BatteryInfo) -> bool {
+        let discharging = matches!(
+            batt_info.device_state,
+            Some(DeviceState::Discharging | DeviceState::PendingDischarge | DeviceState::Empty)
+        );
+
+        let below_percentage = match (self.percentage, batt_info.percentage) {
+            (Some(threshold), Some(percentage)) => *percentage <= threshold,
+            _ => false,
+        };
+
+        let on_ac = matches!(
+            batt_info.device_state,
+            Some(DeviceState::Charging | DeviceState::FullyCharged | DeviceState::PendingCharge)
+        );
+
+        !on_ac
+            && (batt_info.warning_level == Some(WarningLevel::Action)
+                || (discharging && below_percentage))
+    }
+
+    /// Look at `batt_info` as observed at `now` and act on it if it has been critical for long
+    /// enough.
+    pub fn check(&mut self, batt_info: &BatteryInfo, now: Instant) -> anyhow::Result<Outcome> {
+        if !self.is_critical(batt_info) {
+            self.critical_since = None;
+            return Ok(Outcome::Idle);
+        }
+
+        let since = *self.critical_since.get_or_insert(now);
+        let elapsed = now.saturating_duration_since(since);
+
+        if elapsed < self.grace_period {
+            return Ok(Outcome::GracePeriod {
+                remaining: self.grace_period - elapsed,
+            });
+        }
+
+        let action = self.available_action()?;
+
+        let who = self.blocking_inhibitors(action)?;
+        if !who.is_empty() && elapsed < self.grace_period + self.max_inhibitor_delay {
+            return Ok(Outcome::WaitingForInhibitors { who });
+        }
+
+        if !self.dry_run {
+            // a failed action is tried again on the next check, without another grace period
+            action.perform(&self.proxy)?;
+        }
+
+        // start over with a fresh grace period should the machine come back still critical
+        self.critical_since = None;
+
+        Ok(match self.dry_run {
+            true => Outcome::WouldPerform(action),
+            false => Outcome::Performed(action),
+        })
+    }
+
+    /// Check the battery every `interval`.
+    ///
+    /// Failed checks are passed to `on_error` and checked again on the next reading, e.g. logind
+    /// not allowing any of the actions yet. Only losing the connection to logind, or having no
+    /// actions to take at all, ends the watcher.
+    pub fn run(
+        &mut self,
+        interval: Duration,
+        mut read: impl FnMut() -> anyhow::Result<BatteryInfo>,
+        mut on_error: impl FnMut(anyhow::Error),
+    ) -> anyhow::Result<()> {
+        if self.actions.is_empty() {
+            bail!("no critical actions to take");
+        }
+
+        loop {
+            if let Ok(batt_info) = read() {
+                if let Err(e) = self.check(&batt_info, Instant::now()) {
+                    if !retryable(&e) {
+                        return Err(e);
+                    }
+
+                    on_error(e);
+                }
+            }
+
+            sleep(interval);
+        }
+    }
+
+    fn available_action(&self) -> anyhow::Result<CriticalAction> {
+        for action in &self.actions {
+            if action.can(&self.proxy)? == "yes" {
+                return Ok(*action);
+            }
+        }
+
+        bail!(
+            "none of the critical actions are available: {}",
+            self.actions
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    fn blocking_inhibitors(&self, action: CriticalAction) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .proxy
+            .list_inhibitors()?
+            .into_iter()
+            .filter(|(what, _, _, mode, _, _)| {
+                mode == "block" && what.split(':').any(|w| w == action.inhibited_by())
+            })
+            .map(|(_, who, why, _, _, _)| format!("{} ({})", who, why))
+            .collect())
+    }
+}
+
+/// Whether a failed check may succeed later, i.e. the connection to logind is still there.
+fn retryable(e: &anyhow::Error) -> bool {
+    !matches!(
+        e.downcast_ref::<zbus::Error>(),
+        Some(zbus::Error::InputOutput(_))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        Mutex,
+    };
+
+    use super::*;
+    use crate::test_utils::{
+        p2p_connections,
+        reading,
+    };
+
+    #[derive(Clone, Default)]
+    struct Logind {
+        calls: Arc<Mutex<Vec<String>>>,
+        can_hibernate: String,
+        inhibitors: Arc<Mutex<Vec<Inhibitor>>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl Logind {
+        fn suspend(&self, interactive: bool) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("Suspend({})", interactive));
+        }
+
+        fn hibernate(&self, interactive: bool) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("Hibernate({})", interactive));
+        }
+
+        fn hybrid_sleep(&self, _interactive: bool) {}
+
+        fn power_off(&self, _interactive: bool) {}
+
+        fn can_suspend(&self) -> String {
+            "yes".to_owned()
+        }
+
+        fn can_hibernate(&self) -> String {
+            self.can_hibernate.clone()
+        }
+
+        fn can_hybrid_sleep(&self) -> String {
+            "na".to_owned()
+        }
+
+        fn can_power_off(&self) -> String {
+            "yes".to_owned()
+        }
+
+        fn list_inhibitors(&self) -> Vec<Inhibitor> {
+            self.inhibitors.lock().unwrap().clone()
+        }
+    }
+
+    fn logind(logind: Logind) -> anyhow::Result<(Connection, Connection)> {
+        p2p_connections(move |builder| builder.serve_at("/org/freedesktop/login1", logind))
+    }
+
+    #[test]
+    fn critical() -> anyhow::Result<()> {
+        let (_server, client) = logind(Logind::default())?;
+        let watcher = CriticalWatcher::new(&client)?.with_percentage(5.0);
+
+        let critical = [
+            reading(3.0, DeviceState::Discharging, WarningLevel::NoWarning),
+            reading(10.0, DeviceState::Discharging, WarningLevel::Action),
+            reading(3.0, DeviceState::Charging, WarningLevel::Action),
+            reading(6.0, DeviceState::Discharging, WarningLevel::Low),
+        ]
+        .map(|batt_info| watcher.is_critical(&batt_info));
+
+        insta::assert_debug_snapshot!(critical, @r###"
+        [
+            true,
+            true,
+            false,
+            false,
+        ]
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn grace_period_and_inhibitors() -> anyhow::Result<()> {
+        let fake = Logind {
+            can_hibernate: "no".to_owned(),
+            ..Logind::default()
+        };
+        fake.inhibitors.lock().unwrap().push((
+            "sleep:idle".to_owned(),
+            "editor".to_owned(),
+            "saving files".to_owned(),
+            "block".to_owned(),
+            1000,
+            42,
+        ));
+
+        let (_server, client) = logind(fake.clone())?;
+        let mut watcher = CriticalWatcher::new(&client)?
+            .with_grace_period(Duration::from_secs(30))
+            .with_max_inhibitor_delay(Duration::from_secs(60));
+
+        let critical = reading(2.0, DeviceState::Discharging, WarningLevel::Action);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let outcomes = [
+            watcher.check(&critical, at(0))?,
+            watcher.check(&critical, at(20))?,
+            watcher.check(&critical, at(40))?,
+            // plugged in, the next critical reading starts a new grace period
+            watcher.check(
+                &reading(2.0, DeviceState::Charging, WarningLevel::NoWarning),
+                at(50),
+            )?,
+            watcher.check(&critical, at(60))?,
+            watcher.check(&critical, at(100))?,
+            watcher.check(&critical, at(160))?,
+        ];
+
+        insta::assert_debug_snapshot!(outcomes, @r###"
+        [
+            GracePeriod {
+                remaining: 30s,
+            },
+            GracePeriod {
+                remaining: 10s,
+            },
+            WaitingForInhibitors {
+                who: [
+                    "editor (saving files)",
+                ],
+            },
+            Idle,
+            GracePeriod {
+                remaining: 30s,
+            },
+            WaitingForInhibitors {
+                who: [
+                    "editor (saving files)",
+                ],
+            },
+            Performed(
+                Suspend,
+            ),
+        ]
+        "###);
+
+        insta::assert_debug_snapshot!(fake.calls.lock().unwrap(), @r###"
+        [
+            "Suspend(false)",
+        ]
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dry_run() -> anyhow::Result<()> {
+        let fake = Logind {
+            can_hibernate: "yes".to_owned(),
+            ..Logind::default()
+        };
+        fake.inhibitors.lock().unwrap().push((
+            "shutdown".to_owned(),
+            "updater".to_owned(),
+            "installing".to_owned(),
+            "block".to_owned(),
+            0,
+            1,
+        ));
+
+        let (_server, client) = logind(fake.clone())?;
+        let mut watcher = CriticalWatcher::new(&client)?
+            .with_grace_period(Duration::ZERO)
+            .with_dry_run(true);
+
+        let outcome = watcher.check(
+            &reading(1.0, DeviceState::Discharging, WarningLevel::Action),
+            Instant::now(),
+        )?;
+
+        insta::assert_debug_snapshot!(outcome, @r###"
+        WouldPerform(
+            Hibernate,
+        )
+        "###);
+        insta::assert_debug_snapshot!(fake.calls.lock().unwrap().len(), @"0");
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_survives_failed_checks() -> anyhow::Result<()> {
+        let (server, client) = logind(Logind::default())?;
+        let mut watcher = CriticalWatcher::new(&client)?
+            .with_actions(vec![CriticalAction::HybridSleep])
+            .with_grace_period(Duration::ZERO);
+
+        let mut server = Some(server);
+        let mut reads = 0;
+        let mut errors = vec![];
+
+        // hybrid sleep is never available, until logind goes away on the third reading
+        let result = watcher.run(
+            Duration::ZERO,
+            || {
+                reads += 1;
+                if reads == 3 {
+                    drop(server.take());
+                }
+
+                Ok(reading(1.0, DeviceState::Discharging, WarningLevel::Action))
+            },
+            |e| errors.push(e.to_string()),
+        );
+
+        insta::assert_debug_snapshot!(reads, @"3");
+        insta::assert_debug_snapshot!(errors, @r###"
+        [
+            "none of the critical actions are available: hybrid-sleep",
+            "none of the critical actions are available: hybrid-sleep",
+        ]
+        "###);
+        // broken pipe or connection reset, depending on timing
+        assert!(!retryable(&result.unwrap_err()));
+
+        Ok(())
+    }
+}
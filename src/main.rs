@@ -0,0 +1,565 @@
+use std::{
+    env,
+    net::TcpListener,
+    path::PathBuf,
+    process::ExitCode,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{
+    anyhow,
+    bail,
+};
+use low_voltage::{
+    aggregate::{
+        Aggregate,
+        Pack,
+    },
+    battery_info::BatteryInfo,
+    battery_interface::{
+        acpi::Acpi,
+        fallback::{
+            Answered,
+            Fallback,
+        },
+        replay::{
+            Recorder,
+            Replay,
+            Timing,
+        },
+        sysfs::Sysfs,
+        upower::UPower,
+        Device,
+        Listing,
+    },
+    config::{
+        self,
+        Backend,
+        Config,
+        ConfigLoader,
+        DeviceSelection,
+        OutputFormat,
+    },
+    daemon::Daemon,
+    hooks::HookStatus,
+    openmetrics,
+    peripheral::{
+        self,
+        PeripheralEvent,
+        PeripheralTracker,
+    },
+    ups,
+};
+
+const USAGE: &str = "\
+usage: low-voltage [--config <file>] [--set <key>=<value>]... <command> [options]
+
+options:
+    --config <file>
+        read <file> instead of $XDG_CONFIG_HOME/low-voltage/config.toml
+    --set <key>=<value>
+        override a configuration key, e.g. --set thresholds.low=15
+
+commands:
+    status [--format <text|glyph|openmetrics>] [--device <display|all|id>]
+        print the state of the selected devices once, with the batteries behind
+        display when [aggregation] combines them
+    metrics [--listen <addr>] [--once]
+        serve OpenMetrics on http://<addr>/metrics, 127.0.0.1:9101 by default,
+        or print a single scrape to stdout with --once
+    critical [--percentage <n>] [--actions <a,b>] [--grace <secs>] [--dry-run]
+        suspend, hibernate or power off through logind when the battery is
+        critical, trying hibernate then suspend by default
+    peripherals [--watch]
+        list the batteries of mice, headsets and the like, or keep printing them
+        as they come and go and when one gets low
+    ups
+        print the state of the UPSes UPower knows about
+    record <file> [--interval <secs>]
+        write what display reads to <file> every 10 seconds by default, for
+        replay to play back later
+    replay <file> [--speed <n>]
+        print the readings recorded in <file> as far apart as they were
+        recorded, or <n> times faster
+    hooks
+        run the commands in [hooks.on] of the configuration on battery events
+    daemon [--interval <secs>] [--debounce <secs>]
+        publish the battery state on the session bus as
+        io.github.JoakimPaulsson.LowVoltage, reading every 2 seconds and
+        publishing minor changes at most every 5 seconds by default
+";
+
+/// Where the configuration comes from, the command line layered on top of the files.
+#[derive(Default)]
+struct Options {
+    config_file: Option<PathBuf>,
+    overrides: Vec<(String, String)>,
+}
+
+impl Options {
+    fn set(&mut self, key: &str, value: impl Into<String>) {
+        self.overrides.push((key.to_owned(), value.into()));
+    }
+
+    fn config(&self) -> anyhow::Result<Config> {
+        let mut loader = ConfigLoader::new();
+
+        for path in config::system_config_paths() {
+            loader = loader.with_file(path);
+        }
+
+        if let Some(path) = self.config_file.clone().or_else(config::user_config_path) {
+            loader = loader.with_file(path);
+        }
+
+        loader = loader.with_env(env::vars());
+
+        for (key, value) in &self.overrides {
+            loader = loader.with_override(key, value);
+        }
+
+        for name in loader.ignored() {
+            eprintln!("low-voltage: ignoring {}, not a configuration key", name);
+        }
+
+        loader.load()
+    }
+}
+
+fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+
+    let (options, args) = match parse_options(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("low-voltage: {:#}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let result = match args.first().map(String::as_str) {
+        Some("status") => status(options, &args[1..]),
+        Some("metrics") => metrics(options, &args[1..]),
+        Some("critical") => critical(options, &args[1..]),
+        Some("peripherals") => peripherals(options, &args[1..]),
+        Some("ups") => ups(options, &args[1..]),
+        Some("record") => record(options, &args[1..]),
+        Some("replay") => replay(&args[1..]),
+        Some("hooks") => hooks(options, &args[1..]),
+        Some("daemon") => daemon(options, &args[1..]),
+        Some("-h" | "--help") => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        _ => {
+            eprint!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("low-voltage: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The options before the command, and the command with its arguments.
+fn parse_options(mut args: &[String]) -> anyhow::Result<(Options, &[String])> {
+    let mut options = Options::default();
+
+    loop {
+        match args {
+            [flag, file, rest @ ..] if flag == "--config" => {
+                options.config_file = Some(file.into());
+                args = rest;
+            }
+            [flag, set, rest @ ..] if flag == "--set" => {
+                let (key, value) = set
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("--set expects <key>=<value>, got {:?}", set))?;
+
+                options.set(key, value);
+                args = rest;
+            }
+            [flag] if flag == "--config" || flag == "--set" => bail!("{} expects a value", flag),
+            _ => return Ok((options, args)),
+        }
+    }
+}
+
+/// Every device of the configured backend, and the backend that listed them.
+fn answered_devices(config: &Config) -> anyhow::Result<Answered<Vec<Device>>> {
+    let answered = match config.backend {
+        Backend::Auto => Fallback::standard(&config.aggregator()).listing()?,
+        Backend::UPower => only(UPower::new()?.listing()?, "upower"),
+        Backend::Sysfs => only(Sysfs::new().listing()?, "sysfs"),
+        Backend::Acpi => only(Acpi::new().listing()?, "acpi"),
+    };
+
+    // a device unplugged while listing is worth a mention, not a failed command
+    for skipped in &answered.value.skipped {
+        eprintln!("low-voltage: skipped {}", skipped);
+    }
+
+    Ok(Answered {
+        value: answered.value.devices,
+        source: answered.source,
+        failures: answered.failures,
+    })
+}
+
+/// `listing` as the answer of the only source asked.
+fn only(listing: Listing, source: &str) -> Answered<Listing> {
+    Answered {
+        value: listing,
+        source: source.to_owned(),
+        failures: vec![],
+    }
+}
+
+/// Every device of the configured backend.
+fn devices(config: &Config) -> anyhow::Result<Vec<Device>> {
+    Ok(answered_devices(config)?.value)
+}
+
+/// The batteries combined by the configured policy, `None` when UPower's display device is
+/// used instead.
+fn aggregate(config: &Config) -> anyhow::Result<Option<Aggregate>> {
+    if config.aggregation.policy.is_none() && config.backend == Backend::UPower {
+        return Ok(None);
+    }
+
+    let devices = answered_devices(config)?;
+
+    if config.aggregation.policy.is_none() && devices.source == "upower" {
+        return Ok(None);
+    }
+
+    match config.aggregator().aggregate(devices.value) {
+        Some(aggregate) => Ok(Some(aggregate)),
+        None => bail!("no battery to aggregate"),
+    }
+}
+
+/// The reading `display` stands for.
+fn battery_info(config: &Config) -> anyhow::Result<BatteryInfo> {
+    match aggregate(config)? {
+        Some(aggregate) => Ok(aggregate.info),
+        None => UPower::new()?.battery_info(),
+    }
+}
+
+/// The devices picked by `device` in the configuration, from the configured backend, and the
+/// batteries behind `display` when it's aggregated here.
+fn selected_devices(config: &Config) -> anyhow::Result<(Vec<Device>, Vec<Pack>)> {
+    if config.device == DeviceSelection::Display {
+        if let Some(aggregate) = aggregate(config)? {
+            let display = Device {
+                id: "display".to_owned(),
+                info: aggregate.info,
+            };
+
+            return Ok((vec![display], aggregate.packs));
+        }
+
+        let upower = UPower::new()?;
+
+        return Ok((
+            vec![Device {
+                id: upower.get_display_device()?.to_string(),
+                info: upower.battery_info()?,
+            }],
+            vec![],
+        ));
+    }
+
+    let devices = devices(config)?
+        .into_iter()
+        .filter(|device| config.device.matches(&device.id))
+        .collect::<Vec<_>>();
+
+    if devices.is_empty() {
+        bail!("no device matches {:?}", config.device);
+    }
+
+    Ok((devices, vec![]))
+}
+
+fn status(mut options: Options, args: &[String]) -> anyhow::Result<()> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow!("{} expects a value", arg))?;
+
+        match arg.as_str() {
+            "--format" => options.set("format", format!("{:?}", value)),
+            "--device" => options.set("device", format!("{:?}", value)),
+            other => bail!("unexpected argument to status: {:?}", other),
+        }
+    }
+
+    let config = options.config()?;
+    let (devices, packs) = selected_devices(&config)?;
+
+    match config.format {
+        OutputFormat::Text => {
+            for device in devices {
+                println!("{}", device);
+            }
+
+            if packs.len() > 1 {
+                for pack in packs {
+                    println!("  {}", pack);
+                }
+            }
+        }
+        OutputFormat::Glyph => {
+            let renderer = config.glyph_renderer();
+
+            for device in devices {
+                println!("{}", renderer.render(&device.info));
+            }
+        }
+        OutputFormat::OpenMetrics => print!("{}", openmetrics::encode(&devices)),
+    }
+
+    Ok(())
+}
+
+fn metrics(options: Options, args: &[String]) -> anyhow::Result<()> {
+    let mut listen = "127.0.0.1:9101".to_owned();
+    let mut once = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => {
+                listen = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--listen expects an address"))?
+                    .clone()
+            }
+            "--once" => once = true,
+            other => bail!("unexpected argument to metrics: {:?}", other),
+        }
+    }
+
+    let config = options.config()?;
+
+    if once {
+        print!("{}", openmetrics::encode(&devices(&config)?));
+        return Ok(());
+    }
+
+    openmetrics::serve(TcpListener::bind(&listen)?, || devices(&config))
+}
+
+fn critical(mut options: Options, args: &[String]) -> anyhow::Result<()> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow!("{} expects a value", arg))
+        };
+
+        match arg.as_str() {
+            "--percentage" => options.set("thresholds.critical", value()?),
+            "--actions" => {
+                let actions = value()?
+                    .split(',')
+                    .map(|action| format!("{:?}", action))
+                    .collect::<Vec<_>>();
+
+                options.set("critical.actions", format!("[{}]", actions.join(", ")))
+            }
+            "--grace" => options.set("critical.grace_period", value()?),
+            "--dry-run" => options.set("critical.dry_run", "true"),
+            other => bail!("unexpected argument to critical: {:?}", other),
+        }
+    }
+
+    let config = options.config()?;
+    let mut watcher = config.critical_watcher(&zbus::blocking::Connection::system()?)?;
+
+    watcher.run(
+        Duration::from_secs(10),
+        || battery_info(&config),
+        |e| eprintln!("low-voltage: critical battery: {:#}", e),
+    )
+}
+
+fn peripherals(options: Options, args: &[String]) -> anyhow::Result<()> {
+    let watch = match args {
+        [] => false,
+        [flag] if flag == "--watch" => true,
+        [other, ..] => bail!("unexpected argument to peripherals: {:?}", other),
+    };
+
+    let config = options.config()?;
+
+    if !watch {
+        for peripheral in peripheral::peripherals(&devices(&config)?) {
+            println!("{}", peripheral);
+        }
+
+        return Ok(());
+    }
+
+    let mut tracker = PeripheralTracker::new().with_low_percentage(config.thresholds.low);
+
+    loop {
+        if let Ok(devices) = devices(&config) {
+            for event in tracker.update(&devices) {
+                match event {
+                    PeripheralEvent::Added(p) => println!("added {}", p),
+                    PeripheralEvent::Removed(p) => println!("removed {}", p),
+                    PeripheralEvent::Low(p) => println!("low {}", p),
+                }
+            }
+        }
+
+        thread::sleep(Duration::from_secs(5));
+    }
+}
+
+fn ups(options: Options, args: &[String]) -> anyhow::Result<()> {
+    if let Some(arg) = args.first() {
+        bail!("unexpected argument to ups: {:?}", arg);
+    }
+
+    let config = options.config()?;
+    let upses = ups::ups_devices(&devices(&config)?);
+
+    if upses.is_empty() {
+        bail!("no UPS found");
+    }
+
+    for ups in upses {
+        println!("{}", ups);
+    }
+
+    Ok(())
+}
+
+fn record(options: Options, args: &[String]) -> anyhow::Result<()> {
+    let (file, mut args) = match args {
+        [file, rest @ ..] if !file.starts_with("--") => (file, rest.iter()),
+        _ => bail!("record expects a file to write to"),
+    };
+
+    let mut interval = Duration::from_secs(10);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--interval" => {
+                interval = seconds(
+                    arg,
+                    args.next()
+                        .ok_or_else(|| anyhow!("--interval expects a number of seconds"))?
+                        .parse()?,
+                )?
+            }
+            other => bail!("unexpected argument to record: {:?}", other),
+        }
+    }
+
+    let config = options.config()?;
+
+    Recorder::create(file)?.poll(interval, || battery_info(&config))
+}
+
+fn replay(args: &[String]) -> anyhow::Result<()> {
+    let (file, mut args) = match args {
+        [file, rest @ ..] if !file.starts_with("--") => (file, rest.iter()),
+        _ => bail!("replay expects a recording to play"),
+    };
+
+    let mut timing = Timing::Original;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--speed" => {
+                timing = Timing::Accelerated(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--speed expects a number"))?
+                        .parse()?,
+                )
+            }
+            other => bail!("unexpected argument to replay: {:?}", other),
+        }
+    }
+
+    for info in Replay::open(file, timing)?.play() {
+        let display = Device {
+            id: "display".to_owned(),
+            info,
+        };
+
+        println!("{}", display);
+    }
+
+    Ok(())
+}
+
+fn hooks(options: Options, args: &[String]) -> anyhow::Result<()> {
+    if let Some(arg) = args.first() {
+        bail!("unexpected argument to hooks: {:?}", arg);
+    }
+
+    let config = options.config()?;
+
+    config.hook_runner().watch(
+        Duration::from_secs(5),
+        || devices(&config),
+        |device, run| match &run.status {
+            HookStatus::TimedOut => eprintln!(
+                "low-voltage: {} hook for {} timed out: {}",
+                run.event, device.id, run.command
+            ),
+            HookStatus::Failed(error) => eprintln!(
+                "low-voltage: {} hook for {} failed to run: {}: {}",
+                run.event, device.id, run.command, error
+            ),
+            _ => {}
+        },
+    )
+}
+
+fn daemon(options: Options, args: &[String]) -> anyhow::Result<()> {
+    let mut interval = Duration::from_secs(2);
+    let mut debounce = Duration::from_secs(5);
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let secs = args
+            .next()
+            .ok_or_else(|| anyhow!("{} expects a number of seconds", arg))?
+            .parse()?;
+
+        match arg.as_str() {
+            "--interval" => interval = seconds(arg, secs)?,
+            "--debounce" => debounce = seconds(arg, secs)?,
+            other => bail!("unexpected argument to daemon: {:?}", other),
+        }
+    }
+
+    let config = options.config()?;
+
+    Daemon::session()?
+        .with_debounce(debounce)
+        .with_level_thresholds(config.level_thresholds())
+        .run(interval, || battery_info(&config))
+}
+
+/// `secs` given to `flag` as a duration, failing for negative, NaN and too large numbers.
+fn seconds(flag: &str, secs: f64) -> anyhow::Result<Duration> {
+    match Duration::try_from_secs_f64(secs) {
+        Ok(duration) => Ok(duration),
+        Err(_) => bail!("{} expects a number of seconds, got {:?}", flag, secs),
+    }
+}
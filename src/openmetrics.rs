@@ -0,0 +1,440 @@
+use std::{
+    fmt::Write as _,
+    io::{
+        BufRead,
+        BufReader,
+        Write,
+    },
+    net::{
+        TcpListener,
+        TcpStream,
+    },
+    time::Duration,
+};
+
+use crate::{
+    battery_info::{
+        device_state::DeviceState,
+        BatteryInfo,
+    },
+    battery_interface::Device,
+};
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// How long a scrape may take to send its request or read the response, before the exporter
+/// gives up on it and moves on to the next one.
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    unit: Option<&'static str>,
+    value: fn(&BatteryInfo) -> Option<f64>,
+}
+
+const GAUGES: [Gauge; 8] = [
+    Gauge {
+        name: "battery_percent",
+        help: "Charge of the device in percent",
+        unit: Some("percent"),
+        value: |info| info.percentage.map(|p| *p),
+    },
+    Gauge {
+        name: "battery_energy_joules",
+        help: "Energy currently stored in the device",
+        unit: Some("joules"),
+        value: |info| info.energy.map(|e| *e * 3600.0),
+    },
+    Gauge {
+        name: "battery_energy_full_joules",
+        help: "Energy stored in the device when fully charged",
+        unit: Some("joules"),
+        value: |info| info.energy_full.map(|e| *e * 3600.0),
+    },
+    Gauge {
+        name: "battery_energy_rate_watts",
+        help: "Rate the device is charged or discharged at",
+        unit: Some("watts"),
+        value: |info| info.energy_rate.map(|e| *e),
+    },
+    Gauge {
+        name: "battery_voltage_volts",
+        help: "Voltage of the device",
+        unit: Some("volts"),
+        value: |info| info.voltage.map(|v| *v),
+    },
+    Gauge {
+        name: "battery_temperature_celsius",
+        help: "Temperature of the device",
+        unit: Some("celsius"),
+        value: |info| info.temperature.map(|t| *t),
+    },
+    Gauge {
+        name: "battery_charge_cycles",
+        help: "Number of charge cycles the device has been through",
+        unit: None,
+        value: |info| info.charge_cycles.filter(|c| **c >= 0).map(|c| *c as f64),
+    },
+    Gauge {
+        name: "battery_power_supply",
+        help: "Whether the device powers the system",
+        unit: None,
+        value: |info| info.power_supply.map(|p| if *p { 1.0 } else { 0.0 }),
+    },
+];
+
+/// Encode `devices` in the OpenMetrics text format, one sample per device and metric.
+pub fn encode(devices: &[Device]) -> String {
+    let mut output = String::new();
+
+    for gauge in GAUGES.iter() {
+        let samples = devices
+            .iter()
+            .filter_map(|device| Some((device, (gauge.value)(&device.info)?)))
+            .collect::<Vec<_>>();
+
+        if samples.is_empty() {
+            continue;
+        }
+
+        let _ = writeln!(output, "# TYPE {} gauge", gauge.name);
+        if let Some(unit) = gauge.unit {
+            let _ = writeln!(output, "# UNIT {} {}", gauge.name, unit);
+        }
+        let _ = writeln!(output, "# HELP {} {}", gauge.name, gauge.help);
+
+        for (device, value) in samples {
+            let _ = writeln!(
+                output,
+                "{}{{{}}} {}",
+                gauge.name,
+                labels(device),
+                number(value)
+            );
+        }
+    }
+
+    let states = devices
+        .iter()
+        .filter_map(|device| Some((device, device.info.device_state?)))
+        .collect::<Vec<_>>();
+
+    if !states.is_empty() {
+        output.push_str("# TYPE battery_state stateset\n");
+        output.push_str("# HELP battery_state State of the device\n");
+
+        for (device, state) in states {
+            for candidate in DeviceState::ALL {
+                let _ = writeln!(
+                    output,
+                    "battery_state{{{},battery_state=\"{}\"}} {}",
+                    labels(device),
+                    candidate,
+                    (candidate == state) as u8
+                );
+            }
+
+            if let DeviceState::Unrecognized(_) = state {
+                let _ = writeln!(
+                    output,
+                    "battery_state{{{},battery_state=\"{}\"}} 1",
+                    labels(device),
+                    state
+                );
+            }
+        }
+    }
+
+    output.push_str("# EOF\n");
+    output
+}
+
+fn labels(device: &Device) -> String {
+    let mut labels = format!("device=\"{}\"", escape(&device.id));
+
+    if let Some(device_type) = device.info.device_type {
+        let _ = write!(labels, ",type=\"{}\"", escape(&device_type.to_string()));
+    }
+
+    if let Some(model) = device.info.model.as_ref().filter(|m| !m.is_empty()) {
+        let _ = write!(labels, ",model=\"{}\"", escape(model));
+    }
+
+    labels
+}
+
+/// `value` as OpenMetrics writes numbers, which spells out the special values.
+fn number(value: f64) -> String {
+    match value {
+        v if v.is_nan() => "NaN".to_owned(),
+        f64::INFINITY => "+Inf".to_owned(),
+        f64::NEG_INFINITY => "-Inf".to_owned(),
+        v => v.to_string(),
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serve `GET /metrics` on `listener` until it fails, calling `devices` for every scrape.
+///
+/// Connections are handled one at a time, a client that stalls for longer than
+/// [`CLIENT_TIMEOUT`] is dropped.
+pub fn serve(
+    listener: TcpListener,
+    devices: impl Fn() -> anyhow::Result<Vec<Device>>,
+) -> anyhow::Result<()> {
+    serve_with_timeout(listener, CLIENT_TIMEOUT, devices)
+}
+
+fn serve_with_timeout(
+    listener: TcpListener,
+    timeout: Duration,
+    devices: impl Fn() -> anyhow::Result<Vec<Device>>,
+) -> anyhow::Result<()> {
+    for stream in listener.incoming() {
+        // a misbehaving client shouldn't take the exporter down
+        let _ = handle_connection(stream?, timeout, &devices);
+    }
+
+    Ok(())
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    timeout: Duration,
+    devices: &impl Fn() -> anyhow::Result<Vec<Device>>,
+) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone()?);
+    reader.read_line(&mut request_line)?;
+
+    // drain the headers, the request body is never used
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => match devices() {
+            Ok(devices) => ("200 OK", CONTENT_TYPE, encode(&devices)),
+            Err(e) => (
+                "500 Internal Server Error",
+                "text/plain; charset=utf-8",
+                format!("{:#}\n", e),
+            ),
+        },
+        (Some("GET"), _) => (
+            "404 Not Found",
+            "text/plain; charset=utf-8",
+            "not found\n".to_owned(),
+        ),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain; charset=utf-8",
+            "method not allowed\n".to_owned(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::{
+        battery_info::{
+            device_type::DeviceType,
+            BatteryInfoProperties,
+        },
+        test_utils::batt_info,
+    };
+
+    fn devices() -> Vec<Device> {
+        let bat0 = batt_info([
+            BatteryInfoProperties::DeviceType(DeviceType::Battery),
+            BatteryInfoProperties::DeviceState(DeviceState::Discharging),
+            BatteryInfoProperties::Percentage(81.5.into()),
+            BatteryInfoProperties::Energy(40.0.into()),
+            BatteryInfoProperties::EnergyRate(6.25.into()),
+            BatteryInfoProperties::ChargeCycles((-1).into()),
+            BatteryInfoProperties::Model("5B10W13930 \"LGC\"".to_owned().into()),
+        ]);
+
+        let mut ac = BatteryInfo::new();
+        ac.set_propertry(BatteryInfoProperties::DeviceType(DeviceType::LinePower));
+        ac.set_propertry(BatteryInfoProperties::PowerSupply(true.into()));
+
+        vec![
+            Device {
+                id: "/org/freedesktop/UPower/devices/battery_BAT0".to_owned(),
+                info: bat0,
+            },
+            Device {
+                id: "/org/freedesktop/UPower/devices/line_power_AC".to_owned(),
+                info: ac,
+            },
+        ]
+    }
+
+    #[test]
+    fn encode_devices() {
+        insta::assert_snapshot!(encode(&devices()), @r###"
+        # TYPE battery_percent gauge
+        # UNIT battery_percent percent
+        # HELP battery_percent Charge of the device in percent
+        battery_percent{device="/org/freedesktop/UPower/devices/battery_BAT0",type="battery",model="5B10W13930 \"LGC\""} 81.5
+        # TYPE battery_energy_joules gauge
+        # UNIT battery_energy_joules joules
+        # HELP battery_energy_joules Energy currently stored in the device
+        battery_energy_joules{device="/org/freedesktop/UPower/devices/battery_BAT0",type="battery",model="5B10W13930 \"LGC\""} 144000
+        # TYPE battery_energy_rate_watts gauge
+        # UNIT battery_energy_rate_watts watts
+        # HELP battery_energy_rate_watts Rate the device is charged or discharged at
+        battery_energy_rate_watts{device="/org/freedesktop/UPower/devices/battery_BAT0",type="battery",model="5B10W13930 \"LGC\""} 6.25
+        # TYPE battery_power_supply gauge
+        # HELP battery_power_supply Whether the device powers the system
+        battery_power_supply{device="/org/freedesktop/UPower/devices/line_power_AC",type="line-power"} 1
+        # TYPE battery_state stateset
+        # HELP battery_state State of the device
+        battery_state{device="/org/freedesktop/UPower/devices/battery_BAT0",type="battery",model="5B10W13930 \"LGC\"",battery_state="unknown"} 0
+        battery_state{device="/org/freedesktop/UPower/devices/battery_BAT0",type="battery",model="5B10W13930 \"LGC\"",battery_state="charging"} 0
+        battery_state{device="/org/freedesktop/UPower/devices/battery_BAT0",type="battery",model="5B10W13930 \"LGC\"",battery_state="discharging"} 1
+        battery_state{device="/org/freedesktop/UPower/devices/battery_BAT0",type="battery",model="5B10W13930 \"LGC\"",battery_state="empty"} 0
+        battery_state{device="/org/freedesktop/UPower/devices/battery_BAT0",type="battery",model="5B10W13930 \"LGC\"",battery_state="fully-charged"} 0
+        battery_state{device="/org/freedesktop/UPower/devices/battery_BAT0",type="battery",model="5B10W13930 \"LGC\"",battery_state="pending-charge"} 0
+        battery_state{device="/org/freedesktop/UPower/devices/battery_BAT0",type="battery",model="5B10W13930 \"LGC\"",battery_state="pending-discharge"} 0
+        # EOF
+        "###);
+    }
+
+    #[test]
+    fn serve_metrics() -> anyhow::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+
+        std::thread::spawn(move || serve(listener, || Ok(devices())));
+
+        let request = |request: &str| -> anyhow::Result<String> {
+            let mut stream = TcpStream::connect(addr)?;
+            stream.write_all(request.as_bytes())?;
+
+            let mut response = String::new();
+            stream.read_to_string(&mut response)?;
+
+            Ok(response)
+        };
+
+        let response = request("GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+
+        insta::assert_debug_snapshot!(response.lines().take(3).collect::<Vec<_>>(), @r###"
+        [
+            "HTTP/1.1 200 OK",
+            "Content-Type: application/openmetrics-text; version=1.0.0; charset=utf-8",
+            "Content-Length: 2038",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(response.ends_with("# EOF\n"), @"true");
+
+        let response = request("GET / HTTP/1.1\r\n\r\n")?;
+
+        insta::assert_debug_snapshot!(response.lines().next(), @r###"
+        Some(
+            "HTTP/1.1 404 Not Found",
+        )
+        "###);
+
+        Ok(())
+    }
+
+    #[test]
+    fn special_values_and_unrecognized_state() {
+        let info = batt_info([
+            BatteryInfoProperties::Percentage(f64::NAN.into()),
+            BatteryInfoProperties::EnergyRate(f64::INFINITY.into()),
+            BatteryInfoProperties::Voltage(f64::NEG_INFINITY.into()),
+            BatteryInfoProperties::DeviceState(DeviceState::Unrecognized(9)),
+        ]);
+
+        let device = Device {
+            id: "BAT0".to_owned(),
+            info,
+        };
+
+        insta::assert_snapshot!(encode(&[device]), @r###"
+        # TYPE battery_percent gauge
+        # UNIT battery_percent percent
+        # HELP battery_percent Charge of the device in percent
+        battery_percent{device="BAT0"} NaN
+        # TYPE battery_energy_rate_watts gauge
+        # UNIT battery_energy_rate_watts watts
+        # HELP battery_energy_rate_watts Rate the device is charged or discharged at
+        battery_energy_rate_watts{device="BAT0"} +Inf
+        # TYPE battery_voltage_volts gauge
+        # UNIT battery_voltage_volts volts
+        # HELP battery_voltage_volts Voltage of the device
+        battery_voltage_volts{device="BAT0"} -Inf
+        # TYPE battery_state stateset
+        # HELP battery_state State of the device
+        battery_state{device="BAT0",battery_state="unknown"} 0
+        battery_state{device="BAT0",battery_state="charging"} 0
+        battery_state{device="BAT0",battery_state="discharging"} 0
+        battery_state{device="BAT0",battery_state="empty"} 0
+        battery_state{device="BAT0",battery_state="fully-charged"} 0
+        battery_state{device="BAT0",battery_state="pending-charge"} 0
+        battery_state{device="BAT0",battery_state="pending-discharge"} 0
+        battery_state{device="BAT0",battery_state="unrecognized-9"} 1
+        # EOF
+
+        "###);
+    }
+
+    #[test]
+    fn stalled_client() -> anyhow::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+
+        std::thread::spawn(move || {
+            serve_with_timeout(listener, Duration::from_millis(100), || Ok(devices()))
+        });
+
+        // connects and never sends a request
+        let _stalled = TcpStream::connect(addr)?;
+
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n")?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        insta::assert_debug_snapshot!(response.lines().next(), @r###"
+        Some(
+            "HTTP/1.1 200 OK",
+        )
+        "###);
+
+        Ok(())
+    }
+}
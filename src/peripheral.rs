@@ -0,0 +1,295 @@
+use std::{
+    collections::HashSet,
+    fmt,
+};
+
+use crate::{
+    battery_info::{
+        battery_level::BatteryLevel,
+        device_state::DeviceState,
+        device_type::DeviceType,
+        label::Labeled,
+    },
+    battery_interface::Device,
+};
+
+/// How charged a peripheral is, as precisely as it reports it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Charge {
+    Percentage(f64),
+    /// Devices that only report a coarse level, their percentage is a guess by UPower
+    Level(BatteryLevel),
+    Unknown,
+}
+
+/// The battery of a device that doesn't power the system, e.g. a mouse or a headset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Peripheral {
+    pub id: String,
+    pub device_type: DeviceType,
+    pub model: Option<String>,
+    pub charge: Charge,
+    pub state: Option<DeviceState>,
+}
+
+impl Peripheral {
+    /// `None` for the batteries powering the system, line power, UPSes and the like.
+    ///
+    /// A device of type [`DeviceType::Battery`] counts when it doesn't power the system, e.g. a
+    /// peripheral the kernel doesn't know the type of.
+    pub fn from_device(device: &Device) -> Option<Self> {
+        let info = &device.info;
+        let device_type = info.device_type?;
+        let powers_system = info.power_supply.map(|p| *p);
+
+        if !(device_type.is_peripheral()
+            || device_type == DeviceType::Battery && powers_system == Some(false))
+        {
+            return None;
+        }
+
+        let charge = match (info.battery_level, info.percentage) {
+            (Some(level), _)
+                if !matches!(level, BatteryLevel::Unknown | BatteryLevel::NotApplicable) =>
+            {
+                Charge::Level(level)
+            }
+            (_, Some(percentage)) => Charge::Percentage(*percentage),
+            _ => Charge::Unknown,
+        };
+
+        Some(Self {
+            id: device.id.clone(),
+            device_type,
+            model: info.model.as_ref().map(|model| model.as_str().to_owned()),
+            charge,
+            state: info.device_state,
+        })
+    }
+
+    /// Whether the charge is at or below `percentage`, coarse levels count when low or critical.
+    pub fn is_low(&self, percentage: f64) -> bool {
+        match self.charge {
+            Charge::Percentage(p) => p <= percentage,
+            Charge::Level(level) => matches!(level, BatteryLevel::Low | BatteryLevel::Critical),
+            Charge::Unknown => false,
+        }
+    }
+}
+
+impl fmt::Display for Peripheral {
+    /// One line summary, e.g. `Headset WH-1000XM4: 10%`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.device_type.default_label())?;
+
+        if let Some(model) = &self.model {
+            write!(f, " {}", model)?;
+        }
+
+        match self.charge {
+            Charge::Percentage(percentage) => write!(f, ": {:.0}%", percentage),
+            Charge::Level(level) => write!(f, ": {}", level.default_label().to_lowercase()),
+            Charge::Unknown => Ok(()),
+        }
+    }
+}
+
+/// Every peripheral among `devices`, in the same order.
+pub fn peripherals(devices: &[Device]) -> Vec<Peripheral> {
+    devices.iter().filter_map(Peripheral::from_device).collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PeripheralEvent {
+    Added(Peripheral),
+    Removed(Peripheral),
+    /// Fired once when the charge drops to the low percentage, again only after it recovered
+    Low(Peripheral),
+}
+
+/// Follows the peripherals between listings of devices.
+#[derive(Debug, Clone)]
+pub struct PeripheralTracker {
+    low: f64,
+    known: Vec<Peripheral>,
+    warned: HashSet<String>,
+}
+
+impl Default for PeripheralTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PeripheralTracker {
+    pub fn new() -> Self {
+        Self {
+            low: 20.0,
+            known: vec![],
+            warned: HashSet::new(),
+        }
+    }
+
+    /// Warn at or below `percentage`.
+    pub fn with_low_percentage(mut self, percentage: f64) -> Self {
+        self.low = percentage;
+        self
+    }
+
+    /// The peripherals as of the last update.
+    pub fn peripherals(&self) -> &[Peripheral] {
+        &self.known
+    }
+
+    /// Events since the previous listing, every peripheral is added on the first one.
+    pub fn update(&mut self, devices: &[Device]) -> Vec<PeripheralEvent> {
+        let next = peripherals(devices);
+        let mut events = Vec::new();
+
+        for prev in &self.known {
+            if !next.iter().any(|p| p.id == prev.id) {
+                self.warned.remove(&prev.id);
+                events.push(PeripheralEvent::Removed(prev.clone()));
+            }
+        }
+
+        for peripheral in &next {
+            if !self.known.iter().any(|p| p.id == peripheral.id) {
+                events.push(PeripheralEvent::Added(peripheral.clone()));
+            }
+
+            if !peripheral.is_low(self.low) {
+                self.warned.remove(&peripheral.id);
+            } else if self.warned.insert(peripheral.id.clone()) {
+                events.push(PeripheralEvent::Low(peripheral.clone()));
+            }
+        }
+
+        self.known = next;
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        battery_info::BatteryInfoProperties,
+        test_utils::batt_info,
+    };
+
+    fn device(id: &str, device_type: DeviceType, props: Vec<BatteryInfoProperties>) -> Device {
+        let info = batt_info(
+            [BatteryInfoProperties::DeviceType(device_type)]
+                .into_iter()
+                .chain(props),
+        );
+
+        Device {
+            id: id.to_owned(),
+            info,
+        }
+    }
+
+    fn headset(percentage: f64) -> Device {
+        device(
+            "headset_dev_00_1B_66",
+            DeviceType::Headset,
+            vec![
+                BatteryInfoProperties::PowerSupply(false.into()),
+                BatteryInfoProperties::Percentage(percentage.into()),
+                BatteryInfoProperties::Model("WH-1000XM4".to_owned().into()),
+            ],
+        )
+    }
+
+    fn mouse() -> Device {
+        device(
+            "mouse_hidpp_battery_0",
+            DeviceType::Mouse,
+            vec![
+                BatteryInfoProperties::PowerSupply(false.into()),
+                BatteryInfoProperties::Percentage(55.0.into()),
+                BatteryInfoProperties::BatteryLevel(BatteryLevel::Normal),
+            ],
+        )
+    }
+
+    fn system() -> Vec<Device> {
+        vec![
+            device(
+                "battery_BAT0",
+                DeviceType::Battery,
+                vec![
+                    BatteryInfoProperties::PowerSupply(true.into()),
+                    BatteryInfoProperties::Percentage(80.0.into()),
+                ],
+            ),
+            device("line_power_AC", DeviceType::LinePower, vec![]),
+        ]
+    }
+
+    #[test]
+    fn filter_peripherals() {
+        let mut devices = system();
+        devices.extend([headset(10.0), mouse()]);
+
+        let listed = peripherals(&devices)
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>();
+
+        insta::assert_debug_snapshot!(listed, @r###"
+        [
+            "Headset WH-1000XM4: 10%",
+            "Mouse: normal",
+        ]
+        "###);
+    }
+
+    #[test]
+    fn tracker_events() {
+        let mut tracker = PeripheralTracker::new().with_low_percentage(15.0);
+
+        let listings = [
+            vec![mouse()],
+            vec![mouse(), headset(40.0)],
+            vec![mouse(), headset(10.0)],
+            vec![mouse(), headset(9.0)],
+            vec![headset(30.0)],
+        ];
+
+        let events = listings
+            .iter()
+            .map(|devices| {
+                tracker
+                    .update(devices)
+                    .into_iter()
+                    .map(|event| match event {
+                        PeripheralEvent::Added(p) => format!("added {}", p),
+                        PeripheralEvent::Removed(p) => format!("removed {}", p),
+                        PeripheralEvent::Low(p) => format!("low {}", p),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        insta::assert_debug_snapshot!(events, @r###"
+        [
+            [
+                "added Mouse: normal",
+            ],
+            [
+                "added Headset WH-1000XM4: 40%",
+            ],
+            [
+                "low Headset WH-1000XM4: 10%",
+            ],
+            [],
+            [
+                "removed Mouse: normal",
+            ],
+        ]
+        "###);
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    os::unix::net::UnixStream,
+    thread,
+};
+
+use zbus::{
+    blocking::{
+        connection::Builder,
+        Connection,
+    },
+    interface,
+    zvariant::{
+        ObjectPath,
+        OwnedObjectPath,
+    },
+    Guid,
+    SignalContext,
+};
+
+use crate::battery_info::{
+    device_state::DeviceState,
+    device_type::DeviceType,
+    warning_level::WarningLevel,
+    BatteryInfo,
+    BatteryInfoProperties,
+};
+
+/// A peer to peer connection pair, the first end serving whatever `serve` registers on it.
+///
+/// Stands in for a bus service in tests without needing a running bus.
+pub(crate) fn p2p_connections(
+    serve: impl FnOnce(Builder<'static>) -> zbus::Result<Builder<'static>> + Send + 'static,
+) -> anyhow::Result<(Connection, Connection)> {
+    let (server, client) = UnixStream::pair()?;
+
+    let server = thread::spawn(move || {
+        serve(Builder::unix_stream(server).server(Guid::generate())?.p2p())?.build()
+    });
+
+    let client = Builder::unix_stream(client).p2p().build()?;
+    let server = server
+        .join()
+        .map_err(|_| anyhow::anyhow!("server thread panicked"))??;
+
+    Ok((server, client))
+}
+
+/// A reading holding just `props`, later ones replacing earlier ones of the same kind.
+pub(crate) fn batt_info(props: impl IntoIterator<Item = BatteryInfoProperties>) -> BatteryInfo {
+    let mut batt_info = BatteryInfo::new();
+    props
+        .into_iter()
+        .for_each(|prop| batt_info.set_propertry(prop));
+    batt_info
+}
+
+/// A reading with just the percentage, state and warning level, what level and glyph logic look at.
+pub(crate) fn reading(
+    percentage: f64,
+    state: DeviceState,
+    warning_level: WarningLevel,
+) -> BatteryInfo {
+    batt_info([
+        BatteryInfoProperties::Percentage(percentage.into()),
+        BatteryInfoProperties::DeviceState(state),
+        BatteryInfoProperties::WarningLevel(warning_level),
+    ])
+}
+
+pub(crate) const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+
+/// Stands in for `org.freedesktop.UPower`, serve it at [`UPOWER_PATH`].
+pub(crate) struct FakeUPower {
+    pub(crate) devices: Vec<OwnedObjectPath>,
+}
+
+#[interface(name = "org.freedesktop.UPower")]
+impl FakeUPower {
+    fn enumerate_devices(&self) -> Vec<OwnedObjectPath> {
+        self.devices.clone()
+    }
+
+    fn get_display_device(&self) -> OwnedObjectPath {
+        ObjectPath::from_static_str_unchecked("/org/freedesktop/UPower/devices/DisplayDevice")
+            .into()
+    }
+
+    #[zbus(signal)]
+    pub(crate) async fn device_added(
+        ctxt: &SignalContext<'_>,
+        device: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub(crate) async fn device_removed(
+        ctxt: &SignalContext<'_>,
+        device: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+}
+
+/// Stands in for an `org.freedesktop.UPower.Device`, with only the properties tests need.
+#[derive(Debug, Clone)]
+pub(crate) struct FakeDevice {
+    pub(crate) device_type: DeviceType,
+    pub(crate) state: DeviceState,
+    pub(crate) percentage: f64,
+    pub(crate) power_supply: bool,
+    pub(crate) model: String,
+    pub(crate) time_to_empty: i64,
+    pub(crate) time_to_full: i64,
+}
+
+impl FakeDevice {
+    pub(crate) fn new(device_type: DeviceType, state: DeviceState, percentage: f64) -> Self {
+        Self {
+            device_type,
+            state,
+            percentage,
+            power_supply: matches!(device_type, DeviceType::Battery | DeviceType::Ups),
+            model: String::new(),
+            time_to_empty: 0,
+            time_to_full: 0,
+        }
+    }
+}
+
+#[interface(name = "org.freedesktop.UPower.Device")]
+impl FakeDevice {
+    #[zbus(property, name = "Type")]
+    fn device_type(&self) -> u32 {
+        self.device_type.into()
+    }
+
+    #[zbus(property)]
+    fn state(&self) -> u32 {
+        self.state.into()
+    }
+
+    #[zbus(property)]
+    fn percentage(&self) -> f64 {
+        self.percentage
+    }
+
+    #[zbus(property)]
+    fn power_supply(&self) -> bool {
+        self.power_supply
+    }
+
+    #[zbus(property)]
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    #[zbus(property)]
+    fn time_to_empty(&self) -> i64 {
+        self.time_to_empty
+    }
+
+    #[zbus(property)]
+    fn time_to_full(&self) -> i64 {
+        self.time_to_full
+    }
+}
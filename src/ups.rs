@@ -0,0 +1,196 @@
+use std::{
+    fmt,
+    time::Duration,
+};
+
+use crate::{
+    battery_info::{
+        device_state::DeviceState,
+        device_type::DeviceType,
+        time_until::TimeUntil,
+    },
+    battery_interface::Device,
+};
+
+/// Whether a UPS has mains power.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsStatus {
+    Online,
+    OnBattery,
+    Unknown,
+}
+
+impl From<Option<DeviceState>> for UpsStatus {
+    fn from(state: Option<DeviceState>) -> Self {
+        match state {
+            Some(
+                DeviceState::Charging | DeviceState::FullyCharged | DeviceState::PendingCharge,
+            ) => UpsStatus::Online,
+            Some(DeviceState::Discharging | DeviceState::PendingDischarge | DeviceState::Empty) => {
+                UpsStatus::OnBattery
+            }
+            _ => UpsStatus::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for UpsStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UpsStatus::Online => "online",
+            UpsStatus::OnBattery => "on battery",
+            UpsStatus::Unknown => "unknown",
+        })
+    }
+}
+
+/// An uninterruptible power supply, as UPower reports it.
+///
+/// Events for UPSes, e.g. [`BatteryEvent::UpsOnBattery`], come out of
+/// [`BatteryEvent::detect_devices`] like those of any other device.
+///
+/// [`BatteryEvent::UpsOnBattery`]: crate::battery_info::event::BatteryEvent::UpsOnBattery
+/// [`BatteryEvent::detect_devices`]: crate::battery_info::event::BatteryEvent::detect_devices
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ups {
+    pub id: String,
+    pub model: Option<String>,
+    pub status: UpsStatus,
+    pub charge: Option<f64>,
+    /// How long the UPS lasts on battery, only known while it's on battery
+    pub runtime: Option<Duration>,
+}
+
+impl Ups {
+    /// `None` for anything but a [`DeviceType::Ups`].
+    pub fn from_device(device: &Device) -> Option<Self> {
+        let info = &device.info;
+
+        if info.device_type != Some(DeviceType::Ups) {
+            return None;
+        }
+
+        let status = UpsStatus::from(info.device_state);
+
+        Some(Self {
+            id: device.id.clone(),
+            model: info.model.as_ref().map(|model| model.as_str().to_owned()),
+            status,
+            charge: info.percentage.map(|p| *p),
+            runtime: match info.time_until {
+                Some(TimeUntil::Empty(runtime)) if status == UpsStatus::OnBattery => Some(runtime),
+                _ => None,
+            },
+        })
+    }
+}
+
+impl fmt::Display for Ups {
+    /// One line summary, e.g. `UPS Back-UPS ES 700: on battery, 64%, 0h 20m remaining`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("UPS")?;
+
+        if let Some(model) = &self.model {
+            write!(f, " {}", model)?;
+        }
+
+        write!(f, ": {}", self.status)?;
+
+        if let Some(charge) = self.charge {
+            write!(f, ", {:.0}%", charge)?;
+        }
+
+        if let Some(runtime) = self.runtime {
+            let minutes = runtime.as_secs() / 60;
+            write!(f, ", {}h {}m remaining", minutes / 60, minutes % 60)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Every UPS among `devices`, in the same order.
+pub fn ups_devices(devices: &[Device]) -> Vec<Ups> {
+    devices.iter().filter_map(Ups::from_device).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        battery_info::event::BatteryEvent,
+        battery_interface::upower::UPower,
+        test_utils::{
+            p2p_connections,
+            FakeDevice,
+            FakeUPower,
+            UPOWER_PATH,
+        },
+    };
+
+    const UPS: &str = "/org/freedesktop/UPower/devices/ups_hiddev0";
+    const BATTERY: &str = "/org/freedesktop/UPower/devices/battery_BAT0";
+
+    #[test]
+    fn mock_upower() -> anyhow::Result<()> {
+        let mut ups = FakeDevice::new(DeviceType::Ups, DeviceState::Discharging, 64.0);
+        ups.model = "Back-UPS ES 700".to_owned();
+        ups.time_to_empty = 1260;
+
+        let (server, client) = p2p_connections(move |builder| {
+            builder
+                .serve_at(
+                    UPOWER_PATH,
+                    FakeUPower {
+                        devices: vec![BATTERY.try_into().unwrap(), UPS.try_into().unwrap()],
+                    },
+                )?
+                .serve_at(
+                    BATTERY,
+                    FakeDevice::new(DeviceType::Battery, DeviceState::Discharging, 80.0),
+                )?
+                .serve_at(UPS, ups)
+        })?;
+
+        let upower = UPower::with_connection(&client)?;
+
+        let on_battery = upower.devices()?;
+        insta::assert_debug_snapshot!(
+            ups_devices(&on_battery).iter().map(|ups| ups.to_string()).collect::<Vec<_>>(),
+            @r###"
+        [
+            "UPS Back-UPS ES 700: on battery, 64%, 0h 21m remaining",
+        ]
+        "###
+        );
+
+        // mains power is back
+        server
+            .object_server()
+            .interface::<_, FakeDevice>(UPS)?
+            .get_mut()
+            .state = DeviceState::Charging;
+
+        let online = upower.devices()?;
+        insta::assert_debug_snapshot!(
+            ups_devices(&online).iter().map(|ups| ups.to_string()).collect::<Vec<_>>(),
+            @r###"
+        [
+            "UPS Back-UPS ES 700: online, 64%",
+        ]
+        "###
+        );
+
+        let events = BatteryEvent::detect_devices(&on_battery, &online)
+            .into_iter()
+            .map(|(event, device)| format!("{} {}", event, device.id))
+            .collect::<Vec<_>>();
+        insta::assert_debug_snapshot!(events, @r###"
+        [
+            "ups_online /org/freedesktop/UPower/devices/ups_hiddev0",
+        ]
+        "###);
+
+        Ok(())
+    }
+}